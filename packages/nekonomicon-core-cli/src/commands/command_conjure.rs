@@ -0,0 +1,103 @@
+use nekonomicon_core::diagnostic::{RenderMode, render, render_all};
+use nekonomicon_core::interpreter::outcome::{EXIT_ERROR, EXIT_REJECTED, EXIT_USAGE};
+use nekonomicon_core::{Interpreter, analyze, parse_with_recovery};
+
+/// This function handles the "conjure" command, which runs a .spell file and exits
+/// with the code of its outcome, closing with a `[SUCC]` or `[ERRO]` line on stderr,
+/// or the outcome as JSON in json mode.
+///
+/// # Arguments
+///
+/// - `arguments` (`&[String]`) - The spell path followed by optional modifiers,
+///   e.g. `with mode 'educative'` or `with iterations '1000000'`.
+pub fn command_conjure_handler(arguments: &[String]) {
+    let path = spell_path(arguments, "conjure");
+    let mode = render_mode(arguments);
+    let source = read_spell(path);
+
+    let (program, diagnostics) = parse_with_recovery(&source);
+    if !diagnostics.is_empty() {
+        eprintln!("{}", render_all(&diagnostics, &source, mode));
+        std::process::exit(EXIT_REJECTED);
+    }
+
+    // Mistakes visible in the source stop the spell before its first command runs.
+    let diagnostics = analyze(&program);
+    if !diagnostics.is_empty() {
+        eprintln!("{}", render_all(&diagnostics, &source, mode));
+        if diagnostics.iter().any(|diagnostic| diagnostic.is_error()) {
+            std::process::exit(EXIT_REJECTED);
+        }
+    }
+
+    let mut interpreter = Interpreter::new();
+    if let Some(limit) = max_iterations(arguments) {
+        interpreter.set_max_iterations(limit);
+    }
+    match interpreter.run(&program) {
+        Ok(outcome) => {
+            match mode {
+                RenderMode::Json => eprintln!("{}", outcome.to_json()),
+                _ => eprintln!("{}", outcome.summary()),
+            }
+            std::process::exit(outcome.status.exit_code());
+        }
+        Err(diagnostic) => {
+            eprintln!("{}", render(&diagnostic, &source, mode));
+            std::process::exit(EXIT_ERROR);
+        }
+    }
+}
+
+/// First argument of a spell command, exiting with a hint when it is missing.
+pub fn spell_path<'a>(arguments: &'a [String], command: &str) -> &'a str {
+    match arguments.first() {
+        Some(path) => path,
+        None => {
+            eprintln!("No spell provided. Use 'neko help {command}' for usage information.");
+            std::process::exit(EXIT_USAGE);
+        }
+    }
+}
+
+/// Parses "with mode <value>", defaulting to pretty output.
+pub fn render_mode(arguments: &[String]) -> RenderMode {
+    match modifier(arguments, "mode").map(str::parse) {
+        Some(Ok(mode)) => mode,
+        Some(Err(message)) => {
+            eprintln!("{message}");
+            std::process::exit(EXIT_USAGE);
+        }
+        None => RenderMode::default(),
+    }
+}
+
+/// Parses "with iterations <count>", the most passes any one loop may make.
+pub fn max_iterations(arguments: &[String]) -> Option<usize> {
+    let count = modifier(arguments, "iterations")?;
+    match count.parse() {
+        Ok(count) => Some(count),
+        Err(_) => {
+            eprintln!("Iterations must be a whole number, not '{count}'");
+            std::process::exit(EXIT_USAGE);
+        }
+    }
+}
+
+/// The value after "with <name>", quotes removed.
+fn modifier<'a>(arguments: &'a [String], name: &str) -> Option<&'a str> {
+    arguments
+        .windows(3)
+        .find(|window| window[0] == "with" && window[1] == name)
+        .map(|window| window[2].trim_matches('\''))
+}
+
+pub fn read_spell(path: &str) -> String {
+    match std::fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(error) => {
+            eprintln!("Cannot read spell '{path}': {error}");
+            std::process::exit(EXIT_USAGE);
+        }
+    }
+}
@@ -0,0 +1,30 @@
+use nekonomicon_core::diagnostic::render_all;
+use nekonomicon_core::interpreter::outcome::EXIT_REJECTED;
+use nekonomicon_core::{analyze, parse_with_recovery};
+
+use super::command_conjure::{read_spell, render_mode, spell_path};
+
+/// This function handles the "groom" command, which reports every syntax error
+/// and every mistake the analysis pass finds in a .spell file without running it
+///
+/// # Arguments
+///
+/// - `arguments` (`&[String]`) - The spell path followed by optional modifiers,
+///   e.g. `with mode 'json'`.
+pub fn command_groom_handler(arguments: &[String]) {
+    let path = spell_path(arguments, "groom");
+    let mode = render_mode(arguments);
+    let source = read_spell(path);
+
+    let (program, mut diagnostics) = parse_with_recovery(&source);
+    diagnostics.extend(analyze(&program));
+    if diagnostics.is_empty() {
+        println!("'{path}' is well groomed.");
+        return;
+    }
+
+    eprintln!("{}", render_all(&diagnostics, &source, mode));
+    if diagnostics.iter().any(|diagnostic| diagnostic.is_error()) {
+        std::process::exit(EXIT_REJECTED);
+    }
+}
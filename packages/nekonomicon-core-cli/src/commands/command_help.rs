@@ -188,6 +188,10 @@ fn print_conjure_help() {
         "  {}        Optional arguments passed to the script",
         "[args]".bright_blue()
     );
+    println!(
+        "  {}  Stop any loop after this many passes (default 100000)",
+        "with iterations <n>".bright_blue()
+    );
     println!();
     println!("{}", "EXAMPLES:".bright_yellow().bold());
     println!(
@@ -242,13 +246,13 @@ fn print_summon_help() {
         "  {}  Built-in standard library modules (versionless)",
         "STD Modules".bright_green()
     );
-    println!("  {}      cabinet, vault, network, text, math, etc.");
+    println!("      cabinet, vault, network, text, math, etc.");
     println!();
     println!(
         "  {}      Third-party modules (require version)",
         "External Modules".bright_green()
     );
-    println!("  {}      mymodule:1.2.3, company/auth:2.0.0");
+    println!("      mymodule:1.2.3, company/auth:2.0.0");
     println!();
     println!("{}", "EXAMPLES:".bright_yellow().bold());
     println!(
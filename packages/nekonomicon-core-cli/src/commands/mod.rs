@@ -1,3 +1,5 @@
 //! Module for CLI commands
+pub mod command_conjure;
+pub mod command_groom;
 pub mod command_help;
 pub mod command_story;
@@ -3,6 +3,8 @@ use std::env;
 
 mod commands;
 
+use crate::commands::command_conjure::command_conjure_handler;
+use crate::commands::command_groom::command_groom_handler;
 use crate::commands::command_help::command_help_handler;
 use crate::commands::command_story::command_story_handler;
 
@@ -14,6 +16,8 @@ fn main() {
     let mut commands: HashMap<&str, CommandHandler> = HashMap::new();
     commands.insert("story", command_story_handler);
     commands.insert("help", command_help_handler);
+    commands.insert("conjure", command_conjure_handler);
+    commands.insert("groom", command_groom_handler);
 
     // Parse command-line arguments
     let args: Vec<String> = env::args().collect();
@@ -0,0 +1,537 @@
+//! Checks that run before a spell is conjured, so that mistakes visible in the
+//! source do not stop a long spell halfway through.
+//!
+//! The pass follows bindings in source order: it knows which names were sealed and
+//! where, and which may hold null.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::{
+    Check, Command, CommandKind, Comparator, Condition, Expression, Function, Guard, Instruction,
+    Logic, Loop, LoopKind, Program, Quantifier, Reference, Segment, Sigil, Sink, SinkMode,
+    Statement,
+};
+use crate::diagnostic::Diagnostic;
+use crate::interpreter::Namespace;
+use crate::interpreter::{safe, sink};
+use crate::span::{Span, Spanned};
+
+pub const NULLABLE_UNCHECKED: &str = "E-NULLABLE-UNCHECKED";
+
+/// Every problem found in `program`, in source order. Errors would fail at runtime;
+/// warnings point at code that may.
+pub fn analyze(program: &Program) -> Vec<Diagnostic> {
+    let mut analyzer = Analyzer::default();
+    for statement in &program.statements {
+        analyzer.statement(statement);
+    }
+    analyzer.diagnostics
+}
+
+/// What the pass knows about a bound name.
+#[derive(Debug, Default, Clone, Copy)]
+struct Declared {
+    /// Where it was sealed.
+    sealed: Option<Span>,
+    nullable: bool,
+}
+
+type Key = (Namespace, String);
+
+/// The names a condition shows are not null: when it holds, and when it does not.
+#[derive(Debug, Default, Clone)]
+struct Proof {
+    holds: Vec<Key>,
+    fails: Vec<Key>,
+}
+
+impl Proof {
+    fn negate(self) -> Proof {
+        Proof {
+            holds: self.fails,
+            fails: self.holds,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Analyzer {
+    declared: HashMap<Key, Declared>,
+    /// Nullable names a null check guards at this point, or that were warned about.
+    checked: HashSet<Key>,
+    /// What the flags `decide` stored show, by flag.
+    flags: HashMap<Key, Proof>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Analyzer {
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Command(command) => {
+                self.command(command);
+                if let Some(sink) = &command.sink {
+                    self.sink(sink, false);
+                }
+            }
+            Statement::Assignment(assignment) => {
+                // Passing a value on is not using it, so nullable values may flow.
+                let null = assignment
+                    .values
+                    .iter()
+                    .any(|value| value.node == Instruction::Null);
+                if let Some(sink) = &assignment.sink {
+                    self.sink(sink, null);
+                }
+            }
+            Statement::Calculation(calculation) => {
+                self.expression(&calculation.expression);
+                if let Some(sink) = &calculation.sink {
+                    self.sink(sink, false);
+                }
+            }
+            Statement::Decision(decision) => {
+                let proof = self.condition(&decision.condition);
+                if let Some(sink) = &decision.sink {
+                    self.sink(sink, false);
+                    if let Reference::Record { .. } = &sink.target.node {
+                        self.flags.insert(key(&sink.target.node), proof);
+                    }
+                }
+            }
+            Statement::Conditional(conditional) => {
+                let proof = self.guard(&conditional.guard.node);
+                self.branch(&conditional.then, &proof.holds);
+                if let Some(otherwise) = &conditional.otherwise {
+                    self.branch(otherwise, &proof.fails);
+                }
+                // A branch that ends the spell leaves the rest to the other case.
+                if exits(&conditional.then) {
+                    self.checked.extend(proof.fails);
+                } else if conditional.otherwise.as_deref().is_some_and(exits) {
+                    self.checked.extend(proof.holds);
+                }
+            }
+            Statement::Loop(repetition) => {
+                let proof = match &repetition.kind {
+                    LoopKind::Repeat(count) => {
+                        self.instruction(&count.node, count.span);
+                        Proof::default()
+                    }
+                    LoopKind::While(guard) => self.guard(&guard.node),
+                    LoopKind::Count {
+                        counter,
+                        step,
+                        limit,
+                        ..
+                    } => {
+                        self.read(&counter.node, counter.span);
+                        self.instruction(&step.node, step.span);
+                        self.instruction(&limit.node, limit.span);
+                        Proof::default()
+                    }
+                    LoopKind::Foreach { source, .. } => {
+                        self.read(&source.node, source.span);
+                        Proof::default()
+                    }
+                };
+                self.body(repetition, &proof.holds);
+            }
+            Statement::Function(function) => self.function(function),
+            Statement::Container(container) => self.sink(&container.sink, false),
+            Statement::Error(_) => {}
+        }
+    }
+
+    /// Checks a block in a scope of its own, with `guarded` known not to be null:
+    /// what it binds is gone after `end`.
+    fn branch(&mut self, statements: &[Statement], guarded: &[Key]) {
+        let declared = self.declared.clone();
+        let checked = self.checked.clone();
+        let flags = self.flags.clone();
+        self.checked.extend(guarded.iter().cloned());
+        for statement in statements {
+            self.statement(statement);
+        }
+        self.declared = declared;
+        self.checked = checked;
+        self.flags = flags;
+    }
+
+    /// Checks a loop body like a branch; a `foreach` item is bound inside it.
+    fn body(&mut self, repetition: &Loop, guarded: &[Key]) {
+        let declared = self.declared.clone();
+        if let LoopKind::Foreach { item, .. } = &repetition.kind {
+            self.declared.insert(
+                key(&item.node),
+                Declared {
+                    sealed: item.node.sigil().is_sealed().then_some(item.span),
+                    nullable: item.node.sigil().is_nullable(),
+                },
+            );
+        }
+        self.branch(&repetition.body, guarded);
+        self.declared = declared;
+    }
+
+    /// Checks a function body on its own: it sees its parameters, never the records
+    /// bound around its definition.
+    fn function(&mut self, function: &Function) {
+        let declared = std::mem::take(&mut self.declared);
+        let checked = std::mem::take(&mut self.checked);
+        let flags = std::mem::take(&mut self.flags);
+        for parameter in &function.parameters {
+            let target = &parameter.target;
+            self.declared.insert(
+                key(&target.node),
+                Declared {
+                    sealed: target.node.sigil().is_sealed().then_some(target.span),
+                    nullable: parameter.optional || target.node.sigil().is_nullable(),
+                },
+            );
+        }
+        for statement in &function.body {
+            self.statement(statement);
+        }
+        self.declared = declared;
+        self.checked = checked;
+        self.flags = flags;
+    }
+
+    fn guard(&mut self, guard: &Guard) -> Proof {
+        match guard {
+            Guard::Flags { quantifier, flags } => {
+                // What every flag shows when it holds, and when it does not.
+                let (mut holds, mut fails) = (Vec::new(), Vec::new());
+                for flag in flags {
+                    self.read(&flag.node, flag.span);
+                    if let Some(proof) = self.flags.get(&key(&flag.node)) {
+                        holds.extend(proof.holds.iter().cloned());
+                        fails.extend(proof.fails.iter().cloned());
+                    }
+                }
+                match quantifier {
+                    Quantifier::One => Proof { holds, fails },
+                    Quantifier::All => Proof {
+                        holds,
+                        fails: Vec::new(),
+                    },
+                    Quantifier::Any => Proof {
+                        holds: Vec::new(),
+                        fails,
+                    },
+                    Quantifier::None => Proof {
+                        holds: fails,
+                        fails: Vec::new(),
+                    },
+                    Quantifier::Exclusive => Proof::default(),
+                }
+            }
+            Guard::Decide(condition) => self.condition(condition),
+        }
+    }
+
+    fn command(&mut self, command: &Command) {
+        let instructions = command.instructions.iter().chain(
+            command
+                .modifiers
+                .iter()
+                .filter_map(|modifier| modifier.value.as_ref()),
+        );
+        for instruction in instructions {
+            self.instruction(&instruction.node, instruction.span);
+        }
+    }
+
+    /// Reads the operands left to right and returns what the condition shows. A null
+    /// check covers only what it guards, as in `@?x is not null and @?x > 3`.
+    fn condition(&mut self, condition: &Spanned<Condition>) -> Proof {
+        match &condition.node {
+            Condition::Operand(operand) => {
+                self.instruction(operand, condition.span);
+                match operand {
+                    Instruction::Reference(flag) => {
+                        self.flags.get(&key(flag)).cloned().unwrap_or_default()
+                    }
+                    _ => Proof::default(),
+                }
+            }
+            Condition::Check {
+                subject,
+                check: Check::Null,
+                negated,
+            } => {
+                let proof = null_check(&subject.node);
+                if *negated { proof.negate() } else { proof }
+            }
+            Condition::Compare {
+                comparator,
+                left,
+                right,
+            } if left.node == Instruction::Null || right.node == Instruction::Null => {
+                let subject = if left.node == Instruction::Null {
+                    &right.node
+                } else {
+                    &left.node
+                };
+                match comparator {
+                    Comparator::Equal => null_check(subject),
+                    Comparator::NotEqual => null_check(subject).negate(),
+                    _ => Proof::default(),
+                }
+            }
+            Condition::Compare { left, right, .. } => {
+                self.instruction(&left.node, left.span);
+                self.instruction(&right.node, right.span);
+                Proof::default()
+            }
+            Condition::Check { subject, .. } => {
+                self.instruction(&subject.node, subject.span);
+                Proof::default()
+            }
+            Condition::Not(inner) => self.condition(inner).negate(),
+            Condition::Logic {
+                operator,
+                left,
+                right,
+            } => {
+                let left = self.condition(left);
+                // The right side runs only when the left did not settle the result.
+                let guarded = match operator {
+                    Logic::And => left.holds.clone(),
+                    Logic::Or => left.fails.clone(),
+                    Logic::Xor => Vec::new(),
+                };
+                let checked = self.checked.clone();
+                self.checked.extend(guarded);
+                let right = self.condition(right);
+                self.checked = checked;
+                match operator {
+                    Logic::And => Proof {
+                        holds: [left.holds, right.holds].concat(),
+                        fails: Vec::new(),
+                    },
+                    Logic::Or => Proof {
+                        holds: Vec::new(),
+                        fails: [left.fails, right.fails].concat(),
+                    },
+                    Logic::Xor => Proof::default(),
+                }
+            }
+        }
+    }
+
+    fn expression(&mut self, expression: &Spanned<Expression>) {
+        match &expression.node {
+            Expression::Operand(operand) => self.instruction(operand, expression.span),
+            Expression::Negate(inner) => self.expression(inner),
+            Expression::Binary { left, right, .. } => {
+                self.expression(left);
+                self.expression(right);
+            }
+        }
+    }
+
+    fn instruction(&mut self, instruction: &Instruction, span: Span) {
+        match instruction {
+            Instruction::Reference(reference) => self.read(reference, span),
+            Instruction::Template(template) => {
+                for segment in &template.segments {
+                    if let Segment::Interpolation(reference) = segment {
+                        self.read(reference, reference.span);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Warns about a read of a nullable binding nothing checked for null.
+    fn read(&mut self, reference: &Reference, span: Span) {
+        let nullable = reference.sigil().is_nullable()
+            || self
+                .declared
+                .get(&key(reference))
+                .is_some_and(|declared| declared.nullable);
+        let safe = match reference {
+            Reference::Container { path, .. } => path.first().is_some_and(|step| step.optional()),
+            Reference::Record { .. } => false,
+        };
+        if !nullable || safe || self.checked.contains(&key(reference)) {
+            return;
+        }
+        let name = format!("{}?{}", prefix(reference), reference.name());
+        self.diagnostics.push(
+            Diagnostic::warning(
+                NULLABLE_UNCHECKED,
+                format!("'{}' may be null and is used without a null check", name),
+            )
+            .with_span(span)
+            .with_label(span, "may be null here")
+            .with_help(format!(
+                "Read it where a check guards it: if decide {} is not null.",
+                name
+            )),
+        );
+        // One warning per name is enough.
+        self.checked.insert(key(reference));
+    }
+
+    fn sink(&mut self, sink: &Sink, null: bool) {
+        let target = &sink.target.node;
+        let span = sink.target.span;
+        let seal = sink.mode == SinkMode::Save || target.sigil().is_sealed();
+        let projected = matches!(target, Reference::Container { path, .. } if !path.is_empty());
+
+        if safe::is_reserved(target) {
+            self.diagnostics.push(sink::reserved(target, span));
+            return;
+        }
+        let declared = self.declared.entry(key(target)).or_default();
+        if let Some(sealed) = declared.sealed {
+            self.diagnostics
+                .push(sink::rewrite_sealed(target, seal, span).with_label(sealed, "sealed here"));
+            return;
+        }
+        if seal && projected {
+            self.diagnostics.push(sink::seal_projection(target, span));
+            return;
+        }
+        if !projected {
+            declared.sealed = seal.then_some(span);
+            declared.nullable |= null || target.sigil() == Sigil::Nullable;
+        }
+    }
+}
+
+/// What `subject is null` shows: when it does not hold, the subject is not null.
+fn null_check(subject: &Instruction) -> Proof {
+    match subject {
+        Instruction::Reference(reference) => Proof {
+            holds: Vec::new(),
+            fails: vec![key(reference)],
+        },
+        _ => Proof::default(),
+    }
+}
+
+/// Whether a block ends the spell, or the loop it is in, on its last line.
+fn exits(statements: &[Statement]) -> bool {
+    matches!(
+        statements.last(),
+        Some(Statement::Command(command)) if command.kind == CommandKind::Intrinsic
+            && matches!(command.name.node.as_str(), "success" | "fail" | "stop")
+    )
+}
+
+fn key(reference: &Reference) -> Key {
+    (Namespace::of(reference), reference.name().to_string())
+}
+
+fn prefix(reference: &Reference) -> &'static str {
+    match reference {
+        Reference::Record { .. } => "@",
+        Reference::Container { .. } => "::",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::container;
+    use crate::interpreter::RECORD_SEALED;
+    use crate::parse;
+
+    fn codes(source: &str) -> Vec<(String, String)> {
+        analyze(&parse(source).expect("Parse failed"))
+            .into_iter()
+            .map(|diagnostic| {
+                let span = diagnostic.span.expect("Diagnostic has no span");
+                (diagnostic.code, source[span.start..span.end].to_string())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_seal_mistakes() {
+        let source = "'a' into @!x.\n'b' into @!x.\n'c' into @x.\n\
+                      'host' into !::config:host.\n\
+                      container :a 'x' into !::data.\n'y' into !::data:(0).\n\
+                      'z' into ::data:a.";
+        assert_eq!(
+            codes(source),
+            [
+                (sink::DOUBLE_SEAL.to_string(), "@!x".to_string()),
+                (RECORD_SEALED.to_string(), "@x".to_string()),
+                (
+                    sink::SEAL_PROJECTION.to_string(),
+                    "!::config:host".to_string()
+                ),
+                (container::SEALED.to_string(), "!::data:(0)".to_string()),
+                (container::SEALED.to_string(), "::data:a".to_string()),
+            ]
+        );
+        let first = &analyze(&parse(source).unwrap())[0];
+        assert_eq!(first.labels[1].span.line, 1);
+        assert!(codes("'a' into @x.\n'b' into @x.\n'c' into @!y.").is_empty());
+        let branches = "true into @ok.\nif @ok\n  'a' into @!x.\nelse\n  'b' into @!x.\nend\n\
+                        'c' into @x.";
+        assert!(codes(branches).is_empty());
+        assert_eq!(
+            codes("container :code 'x' into ::error."),
+            [(sink::RESERVED.to_string(), "::error".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_nullable_reads_need_a_check() {
+        let warned = codes("null into @?maybe.\nsay @maybe.\nsay 'again @{maybe}'.");
+        assert_eq!(
+            warned,
+            [(NULLABLE_UNCHECKED.to_string(), "@maybe".to_string())]
+        );
+        assert!(
+            codes("null into @?maybe.\ndecide @?maybe is not null into @ok.\nif @ok\n  say @maybe.\nend")
+                .is_empty()
+        );
+        assert!(codes("null into @?m.\ndecide @?m == null or @m > 1 into @ok.").is_empty());
+        assert!(codes("say ::?config:?port.").is_empty());
+        assert_eq!(
+            codes("null into @?n.\ncalculate @n + 1 into @m."),
+            [(NULLABLE_UNCHECKED.to_string(), "@n".to_string())]
+        );
+        assert!(codes("null into @?a.\n@?a into @?b.").is_empty());
+    }
+
+    #[test]
+    fn test_null_checks_cover_what_they_guard() {
+        let unguarded = [
+            "decide @?x is not null into @ok.\nsay @x.",
+            "decide @?x is null into @missing.\nif @missing\n  say @x.\nend",
+            "if decide @?x is not null\n  say 'a'.\nend\nsay @x.",
+            "if decide @?x is not null\n  say 'a'.\nelse\n  say @x.\nend",
+            "decide @?x is not null or @x > 1 into @ok.",
+            "decide @?x is not null into @ok.\nif any @ok @ok\n  say @x.\nend",
+        ];
+        for source in unguarded {
+            let source = format!("null into @?x.\n{source}");
+            assert_eq!(
+                codes(&source),
+                [(NULLABLE_UNCHECKED.to_string(), "@x".to_string())],
+                "{source}"
+            );
+        }
+        let guarded = [
+            "if decide @?x is not null\n  say @x.\nend",
+            "if decide @?x == null\n  say 'none'.\nelse\n  say @x.\nend",
+            "if decide not @?x is null\n  say @x.\nend",
+            "decide @?x is null into @missing.\nif @missing\n  fail.\nend\nsay @x.",
+            "decide @?x is not null into @ok.\nif all @ok @ok\n  say @x.\nend",
+            "decide @?x is not null into @ok.\nif decide @ok and @x > 1\n  say 'big'.\nend",
+            "while decide @?x != null\n  say @x.\n  stop.\nend",
+        ];
+        for source in guarded {
+            let source = format!("null into @?x.\n{source}");
+            assert!(codes(&source).is_empty(), "{source}");
+        }
+    }
+}
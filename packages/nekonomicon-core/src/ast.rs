@@ -0,0 +1,791 @@
+use std::borrow::Cow;
+use std::fmt;
+
+use crate::schema::Schema;
+use crate::span::{Span, Spanned};
+use crate::value::Lens;
+
+/// A whole spell: every statement in source order.
+#[derive(Debug, Clone, Default)]
+pub struct Program {
+    pub statements: Vec<Statement>,
+    /// Every comment in source order, for formatters and doc generators.
+    pub comments: Vec<Comment>,
+}
+
+/// A `~ line` or `~~~ block ~~~` comment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    pub kind: CommentKind,
+    /// Text between the markers, untrimmed.
+    pub text: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommentKind {
+    Line,
+    Block { terminated: bool },
+}
+
+/// A single unit of work terminated by `.`.
+#[derive(Debug, Clone)]
+pub enum Statement {
+    Command(Command),
+    /// `container ... into ::name.`
+    Container(Container),
+    /// Values stored or checked without a command: `'5' into @count.`
+    Assignment(Assignment),
+    /// `calculate <expression> into @result.`
+    Calculation(Calculation),
+    /// `decide <condition> into @flag.`
+    Decision(Decision),
+    /// `if <guard> ... [else ...] end`
+    Conditional(Conditional),
+    /// `repeat`, `while`, `increase`, `decrease` or `foreach` ... `end`
+    Loop(Loop),
+    /// `function <name> ... end`
+    Function(Function),
+    /// Source that failed to parse; the matching diagnostic explains why.
+    Error(Span),
+}
+
+impl Statement {
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::Command(command) => command.span,
+            Statement::Container(container) => container.span,
+            Statement::Assignment(assignment) => assignment.span,
+            Statement::Calculation(calculation) => calculation.span,
+            Statement::Decision(decision) => decision.span,
+            Statement::Conditional(conditional) => conditional.span,
+            Statement::Loop(repetition) => repetition.span,
+            Statement::Function(function) => function.span,
+            Statement::Error(span) => *span,
+        }
+    }
+
+    /// The clauses before the statement; blocks and definitions take none.
+    pub fn clauses(&self) -> &[Spanned<Clause>] {
+        match self {
+            Statement::Command(command) => &command.clauses,
+            Statement::Assignment(assignment) => &assignment.clauses,
+            Statement::Calculation(calculation) => &calculation.clauses,
+            Statement::Decision(decision) => &decision.clauses,
+            Statement::Container(_)
+            | Statement::Conditional(_)
+            | Statement::Loop(_)
+            | Statement::Function(_)
+            | Statement::Error(_) => &[],
+        }
+    }
+}
+
+/// `[clause(s)] <name> [instruction(s)] [modifier(s)] [is &schema] [sink] [on platform].`
+#[derive(Debug, Clone)]
+pub struct Command {
+    pub clauses: Vec<Spanned<Clause>>,
+    pub kind: CommandKind,
+    /// Intrinsic or module name, e.g. `say` or `cabinet`.
+    pub name: Spanned<String>,
+    /// Bare words right after the name, e.g. `delete file` in `cabinet delete file 'x'`.
+    pub actions: Vec<Spanned<String>>,
+    pub instructions: Vec<Spanned<Instruction>>,
+    pub modifiers: Vec<Modifier>,
+    /// `is &schema`: the result must match before it is stored.
+    pub schema: Option<Spanned<String>>,
+    /// Where the result goes, e.g. `into @name`.
+    pub sink: Option<Sink>,
+    pub platform: Option<Spanned<Platform>>,
+    pub span: Span,
+}
+
+impl Command {
+    pub fn has_clause(&self, clause: &Clause) -> bool {
+        self.clauses.iter().any(|spanned| spanned.node == *clause)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandKind {
+    Intrinsic,
+    /// Module action; user function calls share this shape and are resolved by name.
+    Module,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Clause {
+    Safe,
+    Async {
+        tag: Option<String>,
+    },
+    Sensitive,
+    /// `!!! sensitive`: sensitive values may reach an egress context.
+    SensitiveEgress,
+    Elevated,
+}
+
+/// `[clause(s)] <value(s)> [as lens] [is &schema] [sink] [on platform].`
+///
+/// Several values make an array, as they do in a container field.
+#[derive(Debug, Clone)]
+pub struct Assignment {
+    pub clauses: Vec<Spanned<Clause>>,
+    pub values: Vec<Spanned<Instruction>>,
+    pub lens: Option<Spanned<Lens>>,
+    pub schema: Option<Spanned<String>>,
+    pub sink: Option<Sink>,
+    pub platform: Option<Spanned<Platform>>,
+    pub span: Span,
+}
+
+impl Assignment {
+    pub fn has_clause(&self, clause: &Clause) -> bool {
+        self.clauses.iter().any(|spanned| spanned.node == *clause)
+    }
+}
+
+/// `[clause(s)] calculate <expression> [sink] [on platform].`
+#[derive(Debug, Clone)]
+pub struct Calculation {
+    pub clauses: Vec<Spanned<Clause>>,
+    pub expression: Spanned<Expression>,
+    pub sink: Option<Sink>,
+    pub platform: Option<Spanned<Platform>>,
+    pub span: Span,
+}
+
+impl Calculation {
+    pub fn has_clause(&self, clause: &Clause) -> bool {
+        self.clauses.iter().any(|spanned| spanned.node == *clause)
+    }
+}
+
+/// An arithmetic expression, nested by precedence.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    /// A number literal, a record or anything else that must hold a number.
+    Operand(Instruction),
+    /// `-@a`
+    Negate(Box<Spanned<Expression>>),
+    Binary {
+        operator: Operator,
+        left: Box<Spanned<Expression>>,
+        right: Box<Spanned<Expression>>,
+    },
+}
+
+impl Expression {
+    /// Every operand, left to right.
+    pub fn operands(&self) -> Vec<&Instruction> {
+        match self {
+            Expression::Operand(instruction) => vec![instruction],
+            Expression::Negate(inner) => inner.operands(),
+            Expression::Binary { left, right, .. } => {
+                let mut operands = left.operands();
+                operands.extend(right.operands());
+                operands
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    Power,
+    /// `a // n`: the n-th root of a.
+    Root,
+}
+
+impl Operator {
+    pub fn from_symbol(symbol: &str) -> Option<Operator> {
+        match symbol {
+            "+" => Some(Operator::Add),
+            "-" => Some(Operator::Subtract),
+            "*" => Some(Operator::Multiply),
+            "/" => Some(Operator::Divide),
+            "%" => Some(Operator::Remainder),
+            "**" => Some(Operator::Power),
+            "//" => Some(Operator::Root),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Operator::Add => "+",
+            Operator::Subtract => "-",
+            Operator::Multiply => "*",
+            Operator::Divide => "/",
+            Operator::Remainder => "%",
+            Operator::Power => "**",
+            Operator::Root => "//",
+        }
+    }
+
+    /// Binding strength: powers and roots, then products, then sums.
+    pub fn precedence(&self) -> u8 {
+        match self {
+            Operator::Add | Operator::Subtract => 1,
+            Operator::Multiply | Operator::Divide | Operator::Remainder => 2,
+            Operator::Power | Operator::Root => 3,
+        }
+    }
+}
+
+/// `[clause(s)] decide <condition> [sink] [on platform].`
+#[derive(Debug, Clone)]
+pub struct Decision {
+    pub clauses: Vec<Spanned<Clause>>,
+    pub condition: Spanned<Condition>,
+    pub sink: Option<Sink>,
+    pub platform: Option<Spanned<Platform>>,
+    pub span: Span,
+}
+
+impl Decision {
+    pub fn has_clause(&self, clause: &Clause) -> bool {
+        self.clauses.iter().any(|spanned| spanned.node == *clause)
+    }
+}
+
+/// A condition, composed strictly left to right: `@a and @b or @c` is
+/// `(@a and @b) or @c`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    /// A value that must be a boolean.
+    Operand(Instruction),
+    /// `@a > @b`
+    Compare {
+        comparator: Comparator,
+        left: Spanned<Instruction>,
+        right: Spanned<Instruction>,
+    },
+    /// `@a is null`, `@a is not integer`, `::user is &User`
+    Check {
+        subject: Spanned<Instruction>,
+        check: Check,
+        negated: bool,
+    },
+    /// `not @a`
+    Not(Box<Spanned<Condition>>),
+    Logic {
+        operator: Logic,
+        left: Box<Spanned<Condition>>,
+        right: Box<Spanned<Condition>>,
+    },
+}
+
+impl Condition {
+    /// Every operand, left to right.
+    pub fn operands(&self) -> Vec<&Instruction> {
+        match self {
+            Condition::Operand(instruction) => vec![instruction],
+            Condition::Compare { left, right, .. } => vec![&left.node, &right.node],
+            Condition::Check { subject, .. } => vec![&subject.node],
+            Condition::Not(inner) => inner.operands(),
+            Condition::Logic { left, right, .. } => {
+                let mut operands = left.operands();
+                operands.extend(right.operands());
+                operands
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparator {
+    Greater,
+    Less,
+    GreaterOrEqual,
+    LessOrEqual,
+    Equal,
+    NotEqual,
+    /// `equals`: the exact text, where `==` compares numbers by value.
+    Equals,
+    Contains,
+    StartsWith,
+    EndsWith,
+    /// `matches`: a regular expression anywhere in the text.
+    Matches,
+    /// `has`: like `contains`, read as "has this character".
+    Has,
+}
+
+impl Comparator {
+    pub fn from_symbol(symbol: &str) -> Option<Comparator> {
+        match symbol {
+            ">" => Some(Comparator::Greater),
+            "<" => Some(Comparator::Less),
+            ">=" => Some(Comparator::GreaterOrEqual),
+            "<=" => Some(Comparator::LessOrEqual),
+            "==" => Some(Comparator::Equal),
+            "!=" => Some(Comparator::NotEqual),
+            "equals" => Some(Comparator::Equals),
+            "contains" => Some(Comparator::Contains),
+            "starts with" => Some(Comparator::StartsWith),
+            "ends with" => Some(Comparator::EndsWith),
+            "matches" => Some(Comparator::Matches),
+            "has" => Some(Comparator::Has),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Comparator::Greater => ">",
+            Comparator::Less => "<",
+            Comparator::GreaterOrEqual => ">=",
+            Comparator::LessOrEqual => "<=",
+            Comparator::Equal => "==",
+            Comparator::NotEqual => "!=",
+            Comparator::Equals => "equals",
+            Comparator::Contains => "contains",
+            Comparator::StartsWith => "starts with",
+            Comparator::EndsWith => "ends with",
+            Comparator::Matches => "matches",
+            Comparator::Has => "has",
+        }
+    }
+
+    /// Whether this compares text rather than numbers or values.
+    pub fn is_text(&self) -> bool {
+        !matches!(
+            self,
+            Comparator::Greater
+                | Comparator::Less
+                | Comparator::GreaterOrEqual
+                | Comparator::LessOrEqual
+                | Comparator::Equal
+                | Comparator::NotEqual
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Logic {
+    And,
+    Or,
+    Xor,
+}
+
+impl Logic {
+    pub fn from_word(word: &str) -> Option<Logic> {
+        match word {
+            "and" => Some(Logic::And),
+            "or" => Some(Logic::Or),
+            "xor" => Some(Logic::Xor),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Logic::And => "and",
+            Logic::Or => "or",
+            Logic::Xor => "xor",
+        }
+    }
+}
+
+/// What `is` checks a value against.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Check {
+    Null,
+    /// `is empty`: empty text, array or container.
+    Empty,
+    /// `is whitespace`: text with nothing but whitespace.
+    Whitespace,
+    /// `is integer`, `is decimal`, `is bool`, `is string`
+    Type(Schema),
+    /// `is &Schema`
+    Schema(String),
+}
+
+/// `if [quantifier] <guard> <statement(s)> [else <statement(s)>] end`
+#[derive(Debug, Clone)]
+pub struct Conditional {
+    pub guard: Spanned<Guard>,
+    pub then: Vec<Statement>,
+    pub otherwise: Option<Vec<Statement>>,
+    pub span: Span,
+}
+
+/// What a block checks before it runs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Guard {
+    /// `@flag`, or `any`, `all`, `none` or `exclusive` with two or more flags.
+    Flags {
+        quantifier: Quantifier,
+        flags: Vec<Spanned<Reference>>,
+    },
+    /// `decide <condition>`
+    Decide(Spanned<Condition>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quantifier {
+    /// A single flag, with no keyword.
+    One,
+    Any,
+    All,
+    None,
+    Exclusive,
+}
+
+impl Quantifier {
+    pub fn from_word(word: &str) -> Option<Quantifier> {
+        match word {
+            "any" => Some(Quantifier::Any),
+            "all" => Some(Quantifier::All),
+            "none" => Some(Quantifier::None),
+            "exclusive" => Some(Quantifier::Exclusive),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Quantifier::One => "",
+            Quantifier::Any => "any",
+            Quantifier::All => "all",
+            Quantifier::None => "none",
+            Quantifier::Exclusive => "exclusive",
+        }
+    }
+}
+
+/// A block run again and again until its header says to stop.
+#[derive(Debug, Clone)]
+pub struct Loop {
+    pub kind: LoopKind,
+    pub body: Vec<Statement>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum LoopKind {
+    /// `repeat <count>`
+    Repeat(Spanned<Instruction>),
+    /// `while <guard>`, checked before every pass.
+    While(Spanned<Guard>),
+    /// `increase @i by <step> to <limit>`, or `decrease`.
+    Count {
+        direction: Direction,
+        counter: Spanned<Reference>,
+        step: Spanned<Instruction>,
+        limit: Spanned<Instruction>,
+    },
+    /// `foreach from ::container into @item`
+    Foreach {
+        source: Spanned<Reference>,
+        item: Spanned<Reference>,
+    },
+}
+
+impl LoopKind {
+    /// The word the loop starts with.
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            LoopKind::Repeat(_) => "repeat",
+            LoopKind::While(_) => "while",
+            LoopKind::Count { direction, .. } => direction.as_str(),
+            LoopKind::Foreach { .. } => "foreach",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Increase,
+    Decrease,
+}
+
+impl Direction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Increase => "increase",
+            Direction::Decrease => "decrease",
+        }
+    }
+}
+
+/// `function <name> ... end`, with its `parameter` lines lifted out of the body.
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub name: Spanned<String>,
+    /// Positional parameters first, by position, then named ones as declared.
+    pub parameters: Vec<Parameter>,
+    pub body: Vec<Statement>,
+    pub span: Span,
+}
+
+/// `[safe] parameter <position | name> into @record.`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parameter {
+    pub key: ParameterKey,
+    pub target: Spanned<Reference>,
+    /// Declared `safe`, or named: the call may leave it out and it holds null.
+    pub optional: bool,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParameterKey {
+    /// `parameter 1`, counted from one.
+    Position(usize),
+    /// `parameter environment`, passed as `with environment <value>`.
+    Name(String),
+}
+
+/// `into`, `update` or `save` followed by the binding that receives a value.
+#[derive(Debug, Clone)]
+pub struct Sink {
+    pub mode: SinkMode,
+    pub target: Spanned<Reference>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SinkMode {
+    /// Creates the binding or overwrites it.
+    Into,
+    /// Overwrites a binding that must already exist.
+    Update,
+    /// Binds and seals, as `@!` or `::!` would.
+    Save,
+}
+
+impl SinkMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SinkMode::Into => "into",
+            SinkMode::Update => "update",
+            SinkMode::Save => "save",
+        }
+    }
+}
+
+/// A `container` block: fields nested by indentation, stored into a container.
+#[derive(Debug, Clone)]
+pub struct Container {
+    pub fields: Vec<Field>,
+    /// The sink after the fields, e.g. `into ::name`.
+    pub sink: Sink,
+    pub span: Span,
+}
+
+/// `:name` followed by its values or, on deeper lines, by its own fields.
+#[derive(Debug, Clone)]
+pub struct Field {
+    pub name: Spanned<String>,
+    pub sigil: Sigil,
+    pub node: FieldNode,
+    pub span: Span,
+}
+
+/// What a field holds, decided by context as `container.md` describes.
+#[derive(Debug, Clone)]
+pub enum FieldNode {
+    /// One value: `:name 'Alex'`.
+    Value(Spanned<Instruction>),
+    /// Several values: `:colors 'red' 'blue'`.
+    Array(Vec<Spanned<Instruction>>),
+    /// No value, followed by more deeply indented fields.
+    Container(Vec<Field>),
+}
+
+/// Positional input of a command.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instruction {
+    Word(String),
+    Text(String),
+    /// `42`, `-7`: kept as written, since literals are text internally.
+    Integer(String),
+    /// `0.1`, `-3.25`: kept as written.
+    Decimal(String),
+    Boolean(bool),
+    Null,
+    /// A literal with `@{..}` interpolations, resolved at runtime.
+    Template(Template),
+    /// `@name`, `::name` and their sealed or nullable forms.
+    Reference(Reference),
+}
+
+impl Instruction {
+    /// The instruction as text, the way literals are stored; references as written.
+    pub fn text(&self) -> Cow<'_, str> {
+        match self {
+            Instruction::Word(text)
+            | Instruction::Text(text)
+            | Instruction::Integer(text)
+            | Instruction::Decimal(text) => Cow::Borrowed(text),
+            Instruction::Boolean(true) => Cow::Borrowed("true"),
+            Instruction::Boolean(false) => Cow::Borrowed("false"),
+            Instruction::Null => Cow::Borrowed("null"),
+            Instruction::Template(template) => Cow::Borrowed(&template.raw),
+            Instruction::Reference(reference) => Cow::Owned(reference.to_string()),
+        }
+    }
+}
+
+/// A string literal split into text and interpolated references.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    pub segments: Vec<Segment>,
+    /// The literal with escapes resolved and interpolations as written.
+    pub raw: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    Text(String),
+    /// `@{name}`, `@!{name}`, `@?{name}` or `@{::container:field}`.
+    Interpolation(Spanned<Reference>),
+}
+
+/// A named binding as written in the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reference {
+    /// `@name`, `@!name` or `@?name`.
+    Record { name: String, sigil: Sigil },
+    /// `::name` followed by its projection path, e.g. `::user:address:?city`.
+    Container {
+        name: String,
+        sigil: Sigil,
+        path: Vec<Projection>,
+    },
+}
+
+impl Reference {
+    pub fn name(&self) -> &str {
+        match self {
+            Reference::Record { name, .. } | Reference::Container { name, .. } => name,
+        }
+    }
+
+    pub fn sigil(&self) -> Sigil {
+        match self {
+            Reference::Record { sigil, .. } | Reference::Container { sigil, .. } => *sigil,
+        }
+    }
+}
+
+impl fmt::Display for Reference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reference::Record { name, sigil } => write!(f, "@{}{}", sigil.as_str(), name),
+            Reference::Container { name, sigil, path } => {
+                write!(f, "::{}{}", sigil.as_str(), name)?;
+                path.iter()
+                    .try_for_each(|projection| write!(f, "{}", projection))
+            }
+        }
+    }
+}
+
+/// Marker after `@` or `::`: sealed `!` or nullable `?`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Sigil {
+    #[default]
+    Plain,
+    Sealed,
+    Nullable,
+}
+
+impl Sigil {
+    /// Sealed bindings cannot be reassigned.
+    pub fn is_sealed(&self) -> bool {
+        *self == Sigil::Sealed
+    }
+
+    /// Nullable bindings may hold `null`.
+    pub fn is_nullable(&self) -> bool {
+        *self == Sigil::Nullable
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Sigil::Plain => "",
+            Sigil::Sealed => "!",
+            Sigil::Nullable => "?",
+        }
+    }
+}
+
+/// One step into a container: `:field`, `:?field`, `#0` or `#?0`.
+/// Optional steps yield null instead of failing when the target is missing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Projection {
+    Field { name: String, optional: bool },
+    Index { index: usize, optional: bool },
+}
+
+impl Projection {
+    /// Safe steps yield null instead of failing.
+    pub fn optional(&self) -> bool {
+        match self {
+            Projection::Field { optional, .. } | Projection::Index { optional, .. } => *optional,
+        }
+    }
+}
+
+impl fmt::Display for Projection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let optional = |optional: bool| if optional { "?" } else { "" };
+        match self {
+            Projection::Field { name, optional: o } => write!(f, ":{}{}", optional(*o), name),
+            Projection::Index { index, optional: o } => write!(f, "#{}{}", optional(*o), index),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Modifier {
+    pub mode: ModifierMode,
+    pub name: Spanned<String>,
+    pub value: Option<Spanned<Instruction>>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModifierMode {
+    With,
+    Without,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    Linux,
+    Mac,
+    Windows,
+}
+
+impl Platform {
+    pub fn from_name(name: &str) -> Option<Platform> {
+        match name {
+            "linux" => Some(Platform::Linux),
+            "mac" => Some(Platform::Mac),
+            "windows" => Some(Platform::Windows),
+            _ => None,
+        }
+    }
+
+    /// The platform the interpreter is running on, if it is one nekonomicon targets.
+    pub fn current() -> Option<Platform> {
+        if cfg!(target_os = "linux") {
+            Some(Platform::Linux)
+        } else if cfg!(target_os = "macos") {
+            Some(Platform::Mac)
+        } else if cfg!(target_os = "windows") {
+            Some(Platform::Windows)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,362 @@
+//! `calculate`: parsing arithmetic by precedence and evaluating it exactly.
+
+use crate::ast::{Expression, Instruction, Operator};
+use crate::diagnostic::Diagnostic;
+use crate::error::ParseError;
+use crate::number::{ArithmeticError, MAX_SCALE, Number};
+use crate::span::{Span, Spanned};
+use crate::value::Value;
+
+pub const NONNUM: &str = "E-CALCULATE-NONNUM";
+pub const DIVZERO: &str = "E-CALCULATE-DIVZERO";
+pub const NEGROOT: &str = "E-CALCULATE-NEGROOT";
+pub const BADOP: &str = "E-CALCULATE-BADOP";
+pub const PARENMISM: &str = "E-CALCULATE-PARENMISM";
+
+/// Words that belong to `decide`, reported as operators rather than as operands.
+const LOGIC_WORDS: [&str; 4] = ["and", "or", "xor", "not"];
+
+/// One piece of an arithmetic expression as the grammar reads it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Operand(Instruction),
+    /// Any operator symbol, supported or not.
+    Operator(String),
+    Open,
+    Close,
+}
+
+/// Builds the expression `tokens` spell out; `span` is the whole `calculate` statement.
+pub fn parse(tokens: &[Spanned<Token>], span: Span) -> Result<Spanned<Expression>, ParseError> {
+    let mut parser = ExpressionParser {
+        tokens,
+        position: 0,
+        end: Span {
+            start: span.end,
+            ..span
+        },
+    };
+    let expression = parser.expression(0)?;
+    match parser.tokens.get(parser.position) {
+        // Everything else stops the loop in `expression` with its own error.
+        Some(close) => Err(ParseError::new(
+            PARENMISM,
+            "')' has no matching '('",
+            close.span,
+        )),
+        None => Ok(expression),
+    }
+}
+
+struct ExpressionParser<'a> {
+    tokens: &'a [Spanned<Token>],
+    position: usize,
+    /// Where a missing operand is reported.
+    end: Span,
+}
+
+impl ExpressionParser<'_> {
+    /// Operators binding at least as tightly as `min`, left to right.
+    fn expression(&mut self, min: u8) -> Result<Spanned<Expression>, ParseError> {
+        let mut left = self.prefix()?;
+        while let Some(token) = self.tokens.get(self.position) {
+            let operator = match &token.node {
+                Token::Close => break,
+                Token::Operator(symbol) => {
+                    Operator::from_symbol(symbol).ok_or_else(|| bad_operator(symbol, token.span))?
+                }
+                Token::Operand(Instruction::Word(word)) if is_logic(word) => {
+                    return Err(bad_operator(word, token.span));
+                }
+                Token::Operand(_) | Token::Open => {
+                    let mut error = ParseError::new(
+                        BADOP,
+                        "Two operands need an operator between them",
+                        token.span,
+                    );
+                    error.expected.push("+ - * / % ** or //".to_string());
+                    return Err(error);
+                }
+            };
+            if operator.precedence() < min {
+                break;
+            }
+            self.position += 1;
+            let right = self.expression(operator.precedence() + 1)?;
+            let span = left.span.join(right.span);
+            left = Spanned::new(
+                Expression::Binary {
+                    operator,
+                    left: Box::new(left),
+                    right: Box::new(right),
+                },
+                span,
+            );
+        }
+        Ok(left)
+    }
+
+    /// An operand, a parenthesized expression or a negation.
+    fn prefix(&mut self) -> Result<Spanned<Expression>, ParseError> {
+        let Some(token) = self.tokens.get(self.position) else {
+            let message = match self
+                .position
+                .checked_sub(1)
+                .map(|last| &self.tokens[last].node)
+            {
+                Some(Token::Operator(symbol)) => {
+                    format!("'{}' needs a number on its right", symbol)
+                }
+                _ => "Expected a number".to_string(),
+            };
+            let mut error = ParseError::new(BADOP, message, self.end);
+            error.expected.push("a number or a record".to_string());
+            return Err(error);
+        };
+        self.position += 1;
+
+        match &token.node {
+            Token::Open => {
+                let inner = self.expression(0)?;
+                match self.tokens.get(self.position) {
+                    Some(close) if close.node == Token::Close => {
+                        self.position += 1;
+                        Ok(Spanned::new(inner.node, token.span.join(close.span)))
+                    }
+                    _ => Err(ParseError::new(
+                        PARENMISM,
+                        "'(' is never closed",
+                        token.span,
+                    )),
+                }
+            }
+            Token::Close => Err(ParseError::new(
+                PARENMISM,
+                "')' has no matching '('",
+                token.span,
+            )),
+            Token::Operator(symbol) if symbol == "-" || symbol == "+" => {
+                // Signs bind looser than powers: -2 ** 2 is -4.
+                let operand = self.expression(Operator::Power.precedence())?;
+                let span = token.span.join(operand.span);
+                Ok(match symbol.as_str() {
+                    "-" => Spanned::new(Expression::Negate(Box::new(operand)), span),
+                    _ => Spanned::new(operand.node, span),
+                })
+            }
+            Token::Operator(symbol) => match Operator::from_symbol(symbol) {
+                Some(_) => {
+                    let mut error = ParseError::new(
+                        BADOP,
+                        format!("'{}' needs a number on its left", symbol),
+                        token.span,
+                    );
+                    error.expected.push("a number or a record".to_string());
+                    Err(error)
+                }
+                None => Err(bad_operator(symbol, token.span)),
+            },
+            Token::Operand(Instruction::Word(word)) if is_logic(word) => {
+                Err(bad_operator(word, token.span))
+            }
+            Token::Operand(Instruction::Word(word)) => Err(ParseError::new(
+                NONNUM,
+                format!("'{}' is not a number", word),
+                token.span,
+            )),
+            Token::Operand(instruction) => Ok(Spanned::new(
+                Expression::Operand(instruction.clone()),
+                token.span,
+            )),
+        }
+    }
+}
+
+fn is_logic(word: &str) -> bool {
+    LOGIC_WORDS.contains(&word)
+}
+
+fn bad_operator(symbol: &str, span: Span) -> ParseError {
+    let mut error = ParseError::new(
+        BADOP,
+        format!("'{}' is not an arithmetic operator", symbol),
+        span,
+    );
+    error.expected.push("+ - * / % ** or //".to_string());
+    error
+}
+
+/// The number `expression` stands for, with `operand` giving the value of records
+/// and other non-literal operands.
+pub fn evaluate(
+    expression: &Spanned<Expression>,
+    operand: &dyn Fn(&Instruction, Span) -> Result<Value, Diagnostic>,
+) -> Result<Number, Diagnostic> {
+    let span = expression.span;
+    match &expression.node {
+        Expression::Operand(instruction) => {
+            let value = match instruction {
+                Instruction::Integer(text) | Instruction::Decimal(text) => {
+                    Value::from(text.as_str())
+                }
+                other => operand(other, span)?,
+            };
+            // Operands keep the decimals results keep; the rest is rounded off.
+            let number = Number::from_value(&value).map(|number| number.round_to(MAX_SCALE));
+            number.ok_or_else(|| {
+                Diagnostic::error(
+                    NONNUM,
+                    format!("calculate needs numbers, but this is {}", shown(&value)),
+                )
+                .with_span(span)
+                .with_label(span, "not a number")
+                .with_help("Check the value before calculating, or convert it with 'as decimal'.")
+            })
+        }
+        Expression::Negate(inner) => Ok(evaluate(inner, operand)?.negate()),
+        Expression::Binary {
+            operator,
+            left,
+            right,
+        } => {
+            let (a, b) = (evaluate(left, operand)?, evaluate(right, operand)?);
+            let result = match operator {
+                Operator::Add => a.plus(b),
+                Operator::Subtract => a.minus(b),
+                Operator::Multiply => a.multiply(b),
+                Operator::Divide => a.divide(b),
+                Operator::Remainder => a.remainder(b),
+                Operator::Power => a.power(b),
+                Operator::Root => a.root(b),
+            };
+            result.map_err(|error| match error {
+                // A negative power divides by its base, so there the zero is on the left.
+                ArithmeticError::DivisionByZero if *operator == Operator::Power => {
+                    Diagnostic::error(
+                        DIVZERO,
+                        "Division by zero while raising 0 to a negative power",
+                    )
+                    .with_span(span)
+                    .with_label(left.span, "this is zero")
+                }
+                ArithmeticError::DivisionByZero => {
+                    Diagnostic::error(DIVZERO, format!("'{}' by zero", operator.as_str()))
+                        .with_span(span)
+                        .with_label(right.span, "this is zero")
+                }
+                ArithmeticError::NegativeRoot => {
+                    Diagnostic::error(NEGROOT, format!("{} has no real root of degree {}", a, b))
+                        .with_span(span)
+                        .with_label(left.span, "negative")
+                        .with_help("Only odd whole roots, such as '// 3', accept negative numbers.")
+                }
+                ArithmeticError::Overflow => Diagnostic::error(
+                    BADOP,
+                    format!(
+                        "'{}' gives a result too large to represent",
+                        operator.as_str()
+                    ),
+                )
+                .with_span(span)
+                .with_label(span, "too large")
+                .with_help("Split the calculation or use smaller numbers."),
+            })
+        }
+    }
+}
+
+fn shown(value: &Value) -> String {
+    match value {
+        Value::Text(text) => format!("'{}'", text),
+        other => other.described(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Statement;
+    use crate::parse;
+
+    fn calculate(source: &str) -> Result<String, String> {
+        let program =
+            parse(&format!("calculate {source} into @x.")).map_err(|e| e.code.to_string())?;
+        let Statement::Calculation(calculation) = &program.statements[0] else {
+            panic!("Expected a calculation");
+        };
+        let operand = |instruction: &Instruction, _| match instruction {
+            Instruction::Reference(reference) if reference.name() == "ten" => Ok(Value::from("10")),
+            Instruction::Reference(_) => Ok(Value::from("ten")),
+            other => Ok(Value::from(other.text().as_ref())),
+        };
+        evaluate(&calculation.expression, &operand)
+            .map(|number| number.to_string())
+            .map_err(|error| error.code)
+    }
+
+    #[test]
+    fn test_precedence_and_grouping() {
+        for (source, result) in [
+            ("5 + 3", "8"),
+            ("2 + 3 * 4", "14"),
+            ("(2 + 3) * 4", "20"),
+            ("10 - 4 - 3", "3"),
+            ("100 / 4 / 5", "5"),
+            ("2 ** 3 ** 2", "64"),
+            ("-2 ** 2", "-4"),
+            ("2 * -3", "-6"),
+            ("10 % 3 + 16 // 2", "5"),
+            ("@ten * 0.1 + 0.2", "1.2"),
+            ("0.1 + 0.2", "0.3"),
+            ("((1))", "1"),
+            ("20000000000000000000 / 2", "10000000000000000000"),
+            ("100000000000000000000 / 100000000000000000000", "1"),
+            ("0.00000000000000000000000000000000000000000001 + 1", "1"),
+            ("0.0000000000000000015 * 2", "0.000000000000000004"),
+        ] {
+            assert_eq!(calculate(source), Ok(result.to_string()), "{source}");
+        }
+    }
+
+    #[test]
+    fn test_error_codes() {
+        for (source, code) in [
+            ("@name + 1", NONNUM),
+            ("'5' + x", NONNUM),
+            ("1 / (2 - 2)", DIVZERO),
+            ("5 % 0", DIVZERO),
+            ("(-16) // 2", NEGROOT),
+            ("2 ^ 3", BADOP),
+            ("@ten == 10", BADOP),
+            ("1 and 2", BADOP),
+            ("(1 + 2", PARENMISM),
+            ("1 + 2)", PARENMISM),
+            (")", PARENMISM),
+            ("0 ** -1", DIVZERO),
+            ("16 // 0", DIVZERO),
+            ("10 ** 100", BADOP),
+            ("1 +", BADOP),
+            ("1 2", BADOP),
+            ("1 (2)", BADOP),
+            ("* 2", BADOP),
+        ] {
+            assert_eq!(calculate(source), Err(code.to_string()), "{source}");
+        }
+    }
+
+    #[test]
+    fn test_division_by_zero_points_at_the_zero() {
+        for (source, zero) in [("1 / 0", "0"), ("0 ** -1", "0"), ("16 // 0", "0")] {
+            let source = format!("calculate {source} into @x.");
+            let program = parse(&source).unwrap();
+            let Statement::Calculation(calculation) = &program.statements[0] else {
+                panic!("Expected a calculation");
+            };
+            let operand =
+                |instruction: &Instruction, _| Ok(Value::from(instruction.text().as_ref()));
+            let error = evaluate(&calculation.expression, &operand).unwrap_err();
+            let label = error.labels[0].span;
+            assert_eq!(&source[label.start..label.end], zero, "{source}");
+        }
+    }
+}
@@ -0,0 +1,337 @@
+//! Runtime side of containers: building `container` blocks and walking projection
+//! paths such as `::data:colors#1`.
+
+use crate::ast::{Field, FieldNode, Instruction, Projection, Reference};
+use crate::diagnostic::Diagnostic;
+use crate::span::{Span, Spanned};
+use crate::value::Value;
+
+pub const FIELD_NOT_FOUND: &str = "E-CTNR-FIELD-NOT-FOUND";
+pub const INDEX_OUT_OF_BOUNDS: &str = "E-CTNR-INDEX-OUT-OF-BOUNDS";
+pub const NULL_ACCESS: &str = "E-CTNR-NULL-ACCESS";
+pub const TYPE_MISMATCH: &str = "E-CTNR-TYPE-MISMATCH";
+pub const INVALID_PROJECTION: &str = "E-CTNR-INVALID-PROJ";
+pub const SEALED: &str = "E-CTNR-SEALED";
+pub const UNDEFINED: &str = "E-CTNR-UNDEFINED";
+pub const SEAL_INHERITED: &str = "E-CTNR-SEAL-INHERITED";
+pub const DUPLICATE_FIELD: &str = "E-CTNR-DUPLICATE-FIELD";
+
+static NULL: Value = Value::Null;
+
+/// Builds the value of a `container` block, with `evaluate` giving each field value.
+///
+/// Containers are typed: number and boolean literals become typed nodes here,
+/// while everything else is whatever `evaluate` makes of it.
+pub fn build(
+    fields: &[Field],
+    evaluate: &dyn Fn(&Spanned<Instruction>) -> Result<Value, Diagnostic>,
+) -> Result<Value, Diagnostic> {
+    let node = |instruction: &Spanned<Instruction>| match typed(&instruction.node) {
+        Some(value) => Ok(value),
+        None => evaluate(instruction),
+    };
+
+    let mut map = Vec::with_capacity(fields.len());
+    for field in fields {
+        let value = match &field.node {
+            FieldNode::Value(value) => node(value)?,
+            FieldNode::Array(values) => {
+                Value::List(values.iter().map(node).collect::<Result<Vec<_>, _>>()?)
+            }
+            FieldNode::Container(children) => build(children, evaluate)?,
+        };
+        map.push((field.name.node.clone(), value));
+    }
+    Ok(Value::Map(map))
+}
+
+/// The typed node a number or boolean literal becomes inside a container.
+pub fn typed(instruction: &Instruction) -> Option<Value> {
+    match instruction {
+        Instruction::Integer(text) | Instruction::Decimal(text) => {
+            Some(Value::number(text).unwrap_or_else(|| Value::from(text.as_str())))
+        }
+        Instruction::Boolean(flag) => Some(Value::Boolean(*flag)),
+        _ => None,
+    }
+}
+
+/// Follows the projection path of `reference` from `root`, the value bound to its name.
+///
+/// Safe steps (`:?field`, `#?0`) yield null where a plain step fails, and a scalar
+/// answers `#0` as if it were a one-item array.
+pub fn project<'a>(
+    reference: &Reference,
+    root: &'a Value,
+    span: Span,
+) -> Result<&'a Value, Diagnostic> {
+    let Reference::Container { name, sigil, path } = reference else {
+        return Ok(root);
+    };
+
+    let mut current = root;
+    // The path walked so far, for messages: `::user:profile`.
+    let mut walked = format!("::{}{}", sigil.as_str(), name);
+    for step in path {
+        let error = |code: &str, message: String| {
+            Diagnostic::error(code, message)
+                .with_span(span)
+                .with_label(span, format!("'{step}' is applied to '{walked}'"))
+        };
+        current = match (step, current) {
+            (Projection::Field { optional: true, .. }, Value::Null)
+            | (Projection::Index { optional: true, .. }, Value::Null) => &NULL,
+            (_, Value::Null) => {
+                return Err(
+                    error(NULL_ACCESS, format!("'{walked}' is null")).with_help(format!(
+                        "Check it first with 'decide {walked} is not null', or use a safe \
+                         projection such as '{}'.",
+                        safe(step)
+                    )),
+                );
+            }
+            (Projection::Field { name, optional }, Value::Map(_)) => {
+                match (current.field(name), optional) {
+                    (Some(value), _) => value,
+                    (None, true) => &NULL,
+                    (None, false) => {
+                        return Err(error(
+                            FIELD_NOT_FOUND,
+                            format!("'{walked}' has no field '{name}'"),
+                        )
+                        .with_help(format!(
+                            "Check the field name, or write '{}' to get null when it is missing.",
+                            safe(step)
+                        )));
+                    }
+                }
+            }
+            (Projection::Field { .. }, other) => {
+                return Err(error(
+                    TYPE_MISMATCH,
+                    format!("'{walked}' is {}, not a container", other.described()),
+                )
+                .with_help("Use ':field' on containers and '#index' on arrays."));
+            }
+            (Projection::Index { index, optional }, Value::List(items)) => {
+                match (items.get(*index), optional) {
+                    (Some(value), _) => value,
+                    (None, true) => &NULL,
+                    (None, false) => return Err(out_of_bounds(error, *index, items.len(), step)),
+                }
+            }
+            (Projection::Index { index, optional }, scalar) if scalar.is_scalar() => {
+                match (index, optional) {
+                    (0, _) => current,
+                    (_, true) => &NULL,
+                    (_, false) => return Err(out_of_bounds(error, *index, 1, step)),
+                }
+            }
+            (Projection::Index { .. }, _) => {
+                return Err(error(
+                    INVALID_PROJECTION,
+                    format!("'{walked}' is a container, not an array"),
+                )
+                .with_help("Use ':field' to read a named field of a container."));
+            }
+        };
+        walked.push_str(&step.to_string());
+    }
+    Ok(current)
+}
+
+/// Writes `value` at the end of `path` inside `root`, creating missing fields and
+/// the containers on the way to them. Array items are replaced, never appended.
+pub fn assign(
+    root: &mut Value,
+    path: &[Projection],
+    value: Value,
+    span: Span,
+) -> Result<(), Diagnostic> {
+    let Some((step, rest)) = path.split_first() else {
+        *root = value;
+        return Ok(());
+    };
+    if root.is_null() && matches!(step, Projection::Field { .. }) {
+        *root = Value::Map(Vec::new());
+    }
+
+    let error = |code: &str, message: String| {
+        Diagnostic::error(code, message)
+            .with_span(span)
+            .with_label(span, format!("'{step}' cannot be written"))
+    };
+    let slot = match (step, root) {
+        (Projection::Field { name, .. }, Value::Map(fields)) => {
+            let index = match fields.iter().position(|(field, _)| field == name) {
+                Some(index) => index,
+                None => {
+                    fields.push((name.clone(), Value::Null));
+                    fields.len() - 1
+                }
+            };
+            &mut fields[index].1
+        }
+        (Projection::Index { index, .. }, Value::List(items)) => {
+            let length = items.len();
+            items
+                .get_mut(*index)
+                .ok_or_else(|| out_of_bounds(error, *index, length, step))?
+        }
+        (Projection::Field { .. }, other) => {
+            return Err(error(
+                TYPE_MISMATCH,
+                format!("Cannot write a field into {}", other.described()),
+            )
+            .with_help("Only containers have fields; store a container here first."));
+        }
+        (Projection::Index { .. }, other) => {
+            return Err(error(
+                INVALID_PROJECTION,
+                format!("Cannot write an index into {}", other.described()),
+            )
+            .with_help("Only arrays have indexes; use ':field' on containers."));
+        }
+    };
+    assign(slot, rest, value, span)
+}
+
+fn out_of_bounds(
+    error: impl Fn(&str, String) -> Diagnostic,
+    index: usize,
+    length: usize,
+    step: &Projection,
+) -> Diagnostic {
+    error(
+        INDEX_OUT_OF_BOUNDS,
+        format!("Index {index} is out of bounds for {length} item(s)"),
+    )
+    .with_help(format!(
+        "Valid indexes run from 0 to {}; write '{}' to get null instead.",
+        length.saturating_sub(1),
+        safe(step)
+    ))
+}
+
+/// The safe form of a projection step.
+fn safe(step: &Projection) -> Projection {
+    match step.clone() {
+        Projection::Field { name, .. } => Projection::Field {
+            name,
+            optional: true,
+        },
+        Projection::Index { index, .. } => Projection::Index {
+            index,
+            optional: true,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Instruction;
+    use crate::{Statement, parse};
+
+    fn text(value: &str) -> Value {
+        Value::Text(value.to_string())
+    }
+
+    fn user() -> Value {
+        Value::Map(vec![
+            ("name".to_string(), text("Alice")),
+            (
+                "tags".to_string(),
+                Value::List(vec![text("dev"), text("admin")]),
+            ),
+            ("tag".to_string(), text("red")),
+            (
+                "profile".to_string(),
+                Value::Map(vec![("email".to_string(), text("a@example.com"))]),
+            ),
+        ])
+    }
+
+    fn reference(source: &str) -> Reference {
+        let program = parse(&format!("say {source}.")).expect("Parse failed");
+        let Statement::Command(command) = &program.statements[0] else {
+            panic!("Expected a command");
+        };
+        match &command.instructions[0].node {
+            Instruction::Reference(reference) => reference.clone(),
+            other => panic!("Expected a reference, found {:?}", other),
+        }
+    }
+
+    fn resolve(source: &str) -> Result<Value, String> {
+        project(&reference(source), &user(), Span::default())
+            .cloned()
+            .map_err(|error| error.code)
+    }
+
+    #[test]
+    fn test_access_forms() {
+        let cases = [
+            ("::user:name", Ok(text("Alice"))),
+            ("::user:tags#1", Ok(text("admin"))),
+            ("::user:profile:email", Ok(text("a@example.com"))),
+            ("::user:tag#0", Ok(text("red"))),
+            ("::user:?phone", Ok(Value::Null)),
+            ("::user:tags#?5", Ok(Value::Null)),
+            ("::user:?settings:?theme", Ok(Value::Null)),
+            ("::user:?tags#?1", Ok(text("admin"))),
+            ("::user:phone", Err(FIELD_NOT_FOUND)),
+            ("::user:tags#5", Err(INDEX_OUT_OF_BOUNDS)),
+            ("::user:tag#1", Err(INDEX_OUT_OF_BOUNDS)),
+            ("::user:?settings:theme", Err(NULL_ACCESS)),
+            ("::user:name:first", Err(TYPE_MISMATCH)),
+            ("::user#0", Err(INVALID_PROJECTION)),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(
+                resolve(source),
+                expected.map_err(str::to_string),
+                "{source}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_build_nested_container() {
+        let source = "container\n  :name 'Alex'\n  :colors 'red' 'blue'\n  :profile\n    \
+                      :verified true\n  :age 30\ninto ::user.";
+        let program = parse(source).expect("Parse failed");
+        let Statement::Container(container) = &program.statements[0] else {
+            panic!("Expected a container block");
+        };
+        let evaluate =
+            |instruction: &Spanned<Instruction>| Ok(Value::Text(instruction.text().into_owned()));
+        let value = build(&container.fields, &evaluate).unwrap();
+        assert_eq!(
+            value,
+            Value::Map(vec![
+                ("name".to_string(), text("Alex")),
+                (
+                    "colors".to_string(),
+                    Value::List(vec![text("red"), text("blue")])
+                ),
+                (
+                    "profile".to_string(),
+                    Value::Map(vec![("verified".to_string(), Value::Boolean(true))]),
+                ),
+                ("age".to_string(), Value::Integer(30)),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_error_names_the_walked_path() {
+        let error =
+            project(&reference("::user:profile:phone"), &user(), Span::default()).unwrap_err();
+        assert_eq!(error.message, "'::user:profile' has no field 'phone'");
+        assert_eq!(
+            error.help,
+            ["Check the field name, or write ':?phone' to get null when it is missing."]
+        );
+    }
+}
@@ -0,0 +1,648 @@
+//! `decide`: comparisons and boolean logic, composed strictly left to right.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use regex::Regex;
+
+use crate::ast::{Check, Comparator, Condition, Instruction, Logic, Operator};
+use crate::diagnostic::Diagnostic;
+use crate::error::ParseError;
+use crate::number::Number;
+use crate::schema::{Schema, Schemas};
+use crate::span::{Span, Spanned};
+use crate::value::Value;
+
+pub const NONBOOL: &str = "E-DECIDE-NONBOOL";
+pub const TYPEMISMATCH: &str = "E-DECIDE-TYPEMISMATCH";
+pub const CHAIN: &str = "E-DECIDE-CHAIN";
+pub const EMPTY: &str = "E-DECIDE-EMPTY";
+pub const OPUNKNOWN: &str = "E-DECIDE-OPUNKNOWN";
+pub const NULLACCESS: &str = "E-DECIDE-NULLACCESS";
+pub const SCHEMAUNK: &str = "E-DECIDE-SCHEMAUNK";
+pub const PATTERN: &str = "E-DECIDE-PATTERN";
+
+/// One piece of a condition as the grammar reads it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Operand(Instruction),
+    /// Any operator symbol, supported or not.
+    Operator(String),
+    /// `&Name` after `is`.
+    Schema(String),
+    Open,
+    Close,
+}
+
+/// Builds the condition `tokens` spell out; `span` is the whole `decide` statement.
+pub fn parse(tokens: &[Spanned<Token>], span: Span) -> Result<Spanned<Condition>, ParseError> {
+    if tokens.is_empty() {
+        let mut error = ParseError::new(EMPTY, "decide has nothing to decide", span);
+        error.expected.push("a comparison or a boolean".to_string());
+        return Err(error);
+    }
+    let mut parser = ConditionParser {
+        tokens,
+        position: 0,
+        end: Span {
+            start: span.end,
+            ..span
+        },
+    };
+    parser.condition()
+}
+
+struct ConditionParser<'a> {
+    tokens: &'a [Spanned<Token>],
+    position: usize,
+    /// Where a missing operand is reported.
+    end: Span,
+}
+
+impl ConditionParser<'_> {
+    /// Terms joined by `and`, `or` and `xor`, each applied to everything on its left.
+    fn condition(&mut self) -> Result<Spanned<Condition>, ParseError> {
+        let mut left = self.term()?;
+        while let Some(token) = self.tokens.get(self.position) {
+            let operator = match &token.node {
+                Token::Operand(Instruction::Word(word)) => Logic::from_word(word),
+                _ => None,
+            };
+            let Some(operator) = operator else {
+                return Err(self.unexpected_after_term(token));
+            };
+            self.position += 1;
+            let right = self.term()?;
+            let span = left.span.join(right.span);
+            left = Spanned::new(
+                Condition::Logic {
+                    operator,
+                    left: Box::new(left),
+                    right: Box::new(right),
+                },
+                span,
+            );
+        }
+        Ok(left)
+    }
+
+    /// `not <term>`, a comparison, a check, or a single value.
+    fn term(&mut self) -> Result<Spanned<Condition>, ParseError> {
+        if let Some(token) = self.tokens.get(self.position)
+            && token.node == Token::Operand(Instruction::Word("not".to_string()))
+        {
+            self.position += 1;
+            let inner = self.term()?;
+            let span = token.span.join(inner.span);
+            return Ok(Spanned::new(Condition::Not(Box::new(inner)), span));
+        }
+
+        let left = self.operand()?;
+        let Some(token) = self.tokens.get(self.position) else {
+            return Ok(Spanned::new(Condition::Operand(left.node), left.span));
+        };
+        if let Token::Operator(symbol) = &token.node
+            && let Some((comparator, negated)) = comparison(symbol)
+        {
+            self.position += 1;
+            return self.compare(left, comparator, negated);
+        }
+        if token.node == Token::Operand(Instruction::Word("is".to_string())) {
+            self.position += 1;
+            return self.check(left);
+        }
+        Ok(Spanned::new(Condition::Operand(left.node), left.span))
+    }
+
+    /// `left` compared with the operand that follows; `negated` comparisons are
+    /// wrapped in `not`.
+    fn compare(
+        &mut self,
+        left: Spanned<Instruction>,
+        comparator: Comparator,
+        negated: bool,
+    ) -> Result<Spanned<Condition>, ParseError> {
+        let right = self.operand()?;
+        let span = left.span.join(right.span);
+        let comparison = Spanned::new(
+            Condition::Compare {
+                comparator,
+                left,
+                right,
+            },
+            span,
+        );
+        Ok(match negated {
+            true => Spanned::new(Condition::Not(Box::new(comparison)), span),
+            false => comparison,
+        })
+    }
+
+    /// What follows `is`: `[not] null`, `empty`, `whitespace`, `missing <text>`, a
+    /// type name or `&Schema`.
+    fn check(&mut self, subject: Spanned<Instruction>) -> Result<Spanned<Condition>, ParseError> {
+        let negated = self.tokens.get(self.position).is_some_and(|token| {
+            token.node == Token::Operand(Instruction::Word("not".to_string()))
+        });
+        if negated {
+            self.position += 1;
+        }
+        let token = self.tokens.get(self.position);
+        if token.is_some_and(|token| {
+            token.node == Token::Operand(Instruction::Word("missing".to_string()))
+        }) {
+            self.position += 1;
+            return self.compare(subject, Comparator::Has, !negated);
+        }
+        let check = match token.map(|token| &token.node) {
+            Some(Token::Operand(Instruction::Null)) => Some(Check::Null),
+            Some(Token::Operand(Instruction::Word(word))) if word == "empty" => Some(Check::Empty),
+            Some(Token::Operand(Instruction::Word(word))) if word == "whitespace" => {
+                Some(Check::Whitespace)
+            }
+            Some(Token::Operand(Instruction::Word(word))) => Schema::builtin(word).map(Check::Type),
+            Some(Token::Schema(name)) => Some(Check::Schema(name.clone())),
+            _ => None,
+        };
+        let Some(check) = check else {
+            let span = token.map_or(self.end, |token| token.span);
+            let mut error = ParseError::new(
+                ParseError::UNEXPECTED,
+                "Expected what 'is' checks for",
+                span,
+            );
+            error
+                .expected
+                .push("null, empty, whitespace, missing, a type or &Schema".to_string());
+            return Err(error);
+        };
+        self.position += 1;
+        let span = subject.span.join(self.tokens[self.position - 1].span);
+        Ok(Spanned::new(
+            Condition::Check {
+                subject,
+                check,
+                negated,
+            },
+            span,
+        ))
+    }
+
+    /// A record, a container or a literal.
+    fn operand(&mut self) -> Result<Spanned<Instruction>, ParseError> {
+        let Some(token) = self.tokens.get(self.position) else {
+            let mut error = ParseError::new(ParseError::UNEXPECTED, "Expected a value", self.end);
+            error
+                .expected
+                .push("a record, a container or a literal".to_string());
+            return Err(error);
+        };
+        match &token.node {
+            Token::Operand(Instruction::Word(word)) => {
+                let message = match word.as_str() {
+                    "and" | "or" | "xor" | "is" => format!("'{}' needs a value on its left", word),
+                    "not" => "'not' cannot follow a comparison; write it first".to_string(),
+                    _ => format!("'{}' is not a value; quote text as '{}'", word, word),
+                };
+                let mut error = ParseError::new(ParseError::UNEXPECTED, message, token.span);
+                error
+                    .expected
+                    .push("a record, a container or a literal".to_string());
+                Err(error)
+            }
+            Token::Operand(instruction) => {
+                self.position += 1;
+                Ok(Spanned::new(instruction.clone(), token.span))
+            }
+            _ => Err(self.unexpected_after_term(token)),
+        }
+    }
+
+    /// Why `token` cannot continue a condition.
+    fn unexpected_after_term(&self, token: &Spanned<Token>) -> ParseError {
+        match &token.node {
+            Token::Operator(symbol) if comparison(symbol).is_some() => ParseError::new(
+                CHAIN,
+                format!(
+                    "'{}' cannot follow a comparison; compare two values at a time",
+                    symbol
+                ),
+                token.span,
+            ),
+            Token::Operand(Instruction::Word(word)) if word == "is" => ParseError::new(
+                CHAIN,
+                "'is' cannot follow a comparison; check the value on its own line",
+                token.span,
+            ),
+            Token::Operator(symbol) if Operator::from_symbol(symbol).is_some() => ParseError::new(
+                OPUNKNOWN,
+                format!(
+                    "'{}' is arithmetic, which decide does not do; \
+                         calculate the value into a record first",
+                    symbol
+                ),
+                token.span,
+            ),
+            Token::Operator(symbol) => {
+                let hint = match symbol.as_str() {
+                    "=" => "; compare with '=='",
+                    "&&" | "&" => "; write 'and'",
+                    "||" | "|" => "; write 'or'",
+                    "!" => "; write 'not'",
+                    _ => "",
+                };
+                ParseError::new(
+                    OPUNKNOWN,
+                    format!("'{}' is not a decide operator{}", symbol, hint),
+                    token.span,
+                )
+            }
+            Token::Open | Token::Close => ParseError::new(
+                OPUNKNOWN,
+                "decide has no parentheses; split the condition into several decide lines",
+                token.span,
+            ),
+            Token::Operand(Instruction::Word(word)) => {
+                let mut error = ParseError::new(
+                    OPUNKNOWN,
+                    format!("'{}' is not a decide operator", word),
+                    token.span,
+                );
+                error.expected.push("and, or or xor".to_string());
+                error
+            }
+            Token::Operand(_) | Token::Schema(_) => {
+                let mut error =
+                    ParseError::new(ParseError::UNEXPECTED, "Expected an operator", token.span);
+                error
+                    .expected
+                    .push("a comparison, and, or, xor or is".to_string());
+                error
+            }
+        }
+    }
+}
+
+/// The comparator an operator names, and whether it is negated: `not contains`.
+fn comparison(symbol: &str) -> Option<(Comparator, bool)> {
+    match symbol.strip_prefix("not ") {
+        Some(rest) => Comparator::from_symbol(rest).map(|comparator| (comparator, true)),
+        None => Comparator::from_symbol(symbol).map(|comparator| (comparator, false)),
+    }
+}
+
+/// Patterns of `matches`, compiled once per place they are written, so that a
+/// `while` guard does not compile its pattern on every pass. A place keeps only
+/// its latest pattern, so the cache never outgrows the spell.
+#[derive(Debug, Default)]
+pub struct Patterns {
+    /// By where the pattern starts in the source.
+    compiled: RefCell<HashMap<usize, (String, Regex)>>,
+}
+
+impl Patterns {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether `text` matches `pattern`, written at `span`.
+    fn is_match(&self, pattern: &str, span: Span, text: &str) -> Result<bool, regex::Error> {
+        if let Some((_, regex)) = self
+            .compiled
+            .borrow()
+            .get(&span.start)
+            .filter(|(source, _)| source == pattern)
+        {
+            return Ok(regex.is_match(text));
+        }
+        let regex = Regex::new(pattern)?;
+        let holds = regex.is_match(text);
+        self.compiled
+            .borrow_mut()
+            .insert(span.start, (pattern.to_string(), regex));
+        Ok(holds)
+    }
+}
+
+/// Whether `condition` holds, with `operand` giving the value of each operand.
+///
+/// `and` and `or` stop as soon as their left side settles the result, so
+/// `@?x is not null and @?x > 3` never compares a null.
+pub fn evaluate(
+    condition: &Spanned<Condition>,
+    operand: &dyn Fn(&Instruction, Span) -> Result<Value, Diagnostic>,
+    schemas: &Schemas,
+    patterns: &Patterns,
+) -> Result<bool, Diagnostic> {
+    let span = condition.span;
+    match &condition.node {
+        Condition::Operand(instruction) => boolean(&operand(instruction, span)?, span),
+        Condition::Compare {
+            comparator,
+            left,
+            right,
+        } => {
+            let values = (
+                operand(&left.node, left.span)?,
+                operand(&right.node, right.span)?,
+            );
+            compare(*comparator, &values, (left.span, right.span), patterns)
+        }
+        Condition::Check {
+            subject,
+            check,
+            negated,
+        } => {
+            let value = operand(&subject.node, subject.span)?;
+            let holds = match check {
+                Check::Null => value.is_null(),
+                Check::Empty => match &value {
+                    Value::Null => return Err(null_access(subject.span)),
+                    Value::List(items) => items.is_empty(),
+                    Value::Map(fields) => fields.is_empty(),
+                    scalar => scalar.to_string().is_empty(),
+                },
+                Check::Whitespace => text(&value, "is whitespace", subject.span)?
+                    .chars()
+                    .all(char::is_whitespace),
+                Check::Type(schema) => schema.mismatch(&value).is_none(),
+                Check::Schema(name) => match schemas.get(name) {
+                    Some(schema) => schema.mismatch(&value).is_none(),
+                    None => {
+                        return Err(Diagnostic::error(
+                            SCHEMAUNK,
+                            format!("Schema '&{}' is not defined", name),
+                        )
+                        .with_span(span)
+                        .with_label(span, "unknown schema")
+                        .with_help("Built-in schemas are &integer, &decimal, &bool and &string."));
+                    }
+                },
+            };
+            Ok(holds != *negated)
+        }
+        Condition::Not(inner) => Ok(!evaluate(inner, operand, schemas, patterns)?),
+        Condition::Logic {
+            operator,
+            left,
+            right,
+        } => {
+            let left = evaluate(left, operand, schemas, patterns)?;
+            match operator {
+                Logic::And if !left => Ok(false),
+                Logic::Or if left => Ok(true),
+                Logic::And | Logic::Or => evaluate(right, operand, schemas, patterns),
+                Logic::Xor => Ok(left != evaluate(right, operand, schemas, patterns)?),
+            }
+        }
+    }
+}
+
+/// A boolean value; records hold `true` and `false` as text.
+fn boolean(value: &Value, span: Span) -> Result<bool, Diagnostic> {
+    match value {
+        Value::Boolean(flag) => Ok(*flag),
+        Value::Text(text) if text == "true" => Ok(true),
+        Value::Text(text) if text == "false" => Ok(false),
+        Value::Null => Err(null_access(span)),
+        other => Err(Diagnostic::error(
+            NONBOOL,
+            format!("decide needs a boolean, but this is {}", shown(other)),
+        )
+        .with_span(span)
+        .with_label(span, "not a boolean")
+        .with_help("Compare it instead, e.g. decide @answer == 'yes' into @agreed.")),
+    }
+}
+
+fn compare(
+    comparator: Comparator,
+    (left, right): &(Value, Value),
+    (left_span, right_span): (Span, Span),
+    patterns: &Patterns,
+) -> Result<bool, Diagnostic> {
+    match comparator {
+        Comparator::Equal => return Ok(left.equals(right)),
+        Comparator::NotEqual => return Ok(!left.equals(right)),
+        text_comparator if text_comparator.is_text() => {
+            let operator = text_comparator.as_str();
+            let (text, pattern) = (
+                text(left, operator, left_span)?,
+                text(right, operator, right_span)?,
+            );
+            return Ok(match text_comparator {
+                Comparator::Equals => text == pattern,
+                Comparator::StartsWith => text.starts_with(&pattern),
+                Comparator::EndsWith => text.ends_with(&pattern),
+                Comparator::Matches => match patterns.is_match(&pattern, right_span, &text) {
+                    Ok(holds) => holds,
+                    Err(error) => {
+                        return Err(Diagnostic::error(
+                            PATTERN,
+                            format!("'{}' is not a valid pattern", pattern),
+                        )
+                        .with_span(right_span)
+                        .with_label(right_span, "invalid pattern")
+                        .with_note(error.to_string())
+                        .with_help("Escape special characters such as '.' or '(' with '\\'."));
+                    }
+                },
+                _ => text.contains(&pattern),
+            });
+        }
+        _ => {}
+    }
+    for (value, span) in [(left, left_span), (right, right_span)] {
+        if value.is_null() {
+            return Err(null_access(span));
+        }
+    }
+    let (Some(a), Some(b)) = (Number::from_value(left), Number::from_value(right)) else {
+        let span = left_span.join(right_span);
+        return Err(Diagnostic::error(
+            TYPEMISMATCH,
+            format!(
+                "'{}' compares numbers, but got {} and {}",
+                comparator.as_str(),
+                shown(left),
+                shown(right)
+            ),
+        )
+        .with_span(span)
+        .with_label(span, "not two numbers")
+        .with_help("Use '==' or '!=' to compare text."));
+    };
+    Ok(match comparator {
+        Comparator::Greater => a > b,
+        Comparator::Less => a < b,
+        Comparator::GreaterOrEqual => a >= b,
+        Comparator::LessOrEqual => a <= b,
+        _ => unreachable!("handled above"),
+    })
+}
+
+/// The text of a scalar, for the text operators.
+fn text(value: &Value, operator: &str, span: Span) -> Result<String, Diagnostic> {
+    match value {
+        Value::Null => Err(null_access(span)),
+        Value::List(_) | Value::Map(_) => Err(Diagnostic::error(
+            TYPEMISMATCH,
+            format!(
+                "'{}' compares text, but got {}",
+                operator,
+                value.described()
+            ),
+        )
+        .with_span(span)
+        .with_label(span, "not text")
+        .with_help("Read a field or an item first, e.g. ::user:name.")),
+        scalar => Ok(scalar.to_string()),
+    }
+}
+
+fn null_access(span: Span) -> Diagnostic {
+    Diagnostic::error(NULLACCESS, "This value is null")
+        .with_span(span)
+        .with_label(span, "null")
+        .with_help("Check it first: decide @?value is not null and ... into @flag.")
+}
+
+fn shown(value: &Value) -> String {
+    match value {
+        Value::Text(text) => format!("'{}'", text),
+        other => other.described(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Statement;
+    use crate::parse;
+    use crate::schema::SchemaField;
+
+    fn decide(source: &str) -> Result<bool, String> {
+        let program =
+            parse(&format!("decide {source} into @x.")).map_err(|e| e.code.to_string())?;
+        let Statement::Decision(decision) = &program.statements[0] else {
+            panic!("Expected a decision");
+        };
+        let operand = |instruction: &Instruction, _| {
+            Ok(match instruction {
+                Instruction::Reference(reference) => match reference.name() {
+                    "age" => Value::from("30"),
+                    "yes" => Value::from("true"),
+                    "no" => Value::Boolean(false),
+                    "name" => Value::from("Ada"),
+                    "nothing" => Value::Null,
+                    _ => Value::Map(vec![("id".to_string(), Value::Integer(1))]),
+                },
+                Instruction::Null => Value::Null,
+                other => Value::from(other.text().as_ref()),
+            })
+        };
+        let mut schemas = Schemas::new();
+        schemas.define(
+            "Item",
+            Schema::Fields(vec![SchemaField {
+                name: "id".to_string(),
+                schema: Schema::Integer,
+                optional: false,
+            }]),
+        );
+        evaluate(&decision.condition, &operand, &schemas, &Patterns::new())
+            .map_err(|error| error.code)
+    }
+
+    #[test]
+    fn test_left_to_right_logic() {
+        for (source, result) in [
+            ("@age > 18", true),
+            ("@age <= 29.5", false),
+            ("@age == 30.0", true),
+            ("@name != 'Ada'", false),
+            ("@yes and @no", false),
+            ("@yes and @no or @yes", true),
+            ("@no or @yes and @no", false),
+            ("@yes xor @no", true),
+            ("not @no and @yes", true),
+            ("not not @yes", true),
+            ("@?nothing is null", true),
+            ("@?nothing is not null or @yes", true),
+            ("@?nothing is not null and @?nothing > 3", false),
+            ("@age is integer and @name is not integer", true),
+            ("@yes is bool", true),
+            ("::item is &Item", true),
+            ("@nothing == null", true),
+            ("@name equals 'Ada'", true),
+            ("@age equals '30.0'", false),
+            ("@name not equals 'Ada' or @name contains 'd'", true),
+            ("@name not contains 'x' and @name starts with 'A'", true),
+            ("@name ends with 'a' and @name has 'A'", true),
+            ("@name matches '^[A-Z][a-z]+$'", true),
+            ("@name not matches 'x'", true),
+            ("@name is missing '@' and not @name is empty", true),
+            ("' \t' is whitespace and '' is empty", true),
+            (
+                "0.0000000000000000000000000000000000000000001 > 100000000000000000000",
+                false,
+            ),
+            (
+                "100000000000000000000 > 0.0000000000000000000000000000000000000000001",
+                true,
+            ),
+        ] {
+            assert_eq!(decide(source), Ok(result), "{source}");
+        }
+    }
+
+    #[test]
+    fn test_error_codes() {
+        for (source, code) in [
+            ("@name", NONBOOL),
+            ("@name > 3", TYPEMISMATCH),
+            ("@nothing > 3", NULLACCESS),
+            ("@nothing and @yes", NULLACCESS),
+            ("::item is &Missing", SCHEMAUNK),
+            ("", EMPTY),
+            ("1 < @age < 40", CHAIN),
+            ("@age + 1 > 3", OPUNKNOWN),
+            ("@yes && @no", OPUNKNOWN),
+            ("(@yes or @no)", OPUNKNOWN),
+            ("@yes nand @no", OPUNKNOWN),
+            ("@yes and", ParseError::UNEXPECTED),
+            ("@age is purple", ParseError::UNEXPECTED),
+            ("@name matches '('", PATTERN),
+            ("::item contains 'x'", TYPEMISMATCH),
+            ("@nothing is empty", NULLACCESS),
+            ("@name contains 'a' starts with 'A'", CHAIN),
+        ] {
+            assert_eq!(decide(source), Err(code.to_string()), "{source}");
+        }
+    }
+
+    #[test]
+    fn test_patterns_compile_once() {
+        let patterns = Patterns::new();
+        let (here, there) = (
+            Span::default(),
+            Span {
+                start: 7,
+                ..Span::default()
+            },
+        );
+        assert_eq!(patterns.is_match("^A", here, "Ada"), Ok(true));
+        assert_eq!(patterns.is_match("^A", here, "Bob"), Ok(false));
+        assert_eq!(patterns.compiled.borrow().len(), 1);
+        assert!(patterns.is_match("(", there, "Ada").is_err());
+        assert_eq!(patterns.compiled.borrow().len(), 1);
+        // A pattern read from a record may change; its place keeps only the latest.
+        for pattern in ["^A", "^B", "^C"] {
+            assert_eq!(
+                patterns.is_match(pattern, there, "Bob"),
+                Ok(pattern == "^B")
+            );
+        }
+        assert_eq!(patterns.compiled.borrow().len(), 2);
+    }
+}
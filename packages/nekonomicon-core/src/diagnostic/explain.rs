@@ -0,0 +1,629 @@
+/// Teaching material for an error code, shown by the educative renderer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Explanation {
+    pub code: &'static str,
+    pub why: &'static str,
+    pub fixes: &'static [&'static str],
+}
+
+/// Looks up the explanation of an error code.
+pub fn explain(code: &str) -> Option<&'static Explanation> {
+    EXPLANATIONS
+        .iter()
+        .find(|explanation| explanation.code == code)
+}
+
+const EXPLANATIONS: &[Explanation] = &[
+    // Syntax
+    Explanation {
+        code: "E-SYNTAX-UNEXPECTED",
+        why: "Every command reads as [clause(s)] <intrinsic|module action> [instruction(s)] \
+              [modifier(s)] [on platform] and ends with the '.' terminator. The parser found \
+              something that does not fit at this place.",
+        fixes: &[
+            "Check that the previous command ends with '.'.",
+            "Keep 'on <platform>' as the very last part of the command.",
+            "Wrap text in single quotes: 'like this'.",
+        ],
+    },
+    Explanation {
+        code: "E-SYNTAX-UNTERMINATED-COMMENT",
+        why: "A block comment starts with '~~~' and runs until the next '~~~'. Without the \
+              closing marker, everything after it is read as comment and never runs.",
+        fixes: &[
+            "Close the block comment with '~~~' on its own line.",
+            "Use a single '~' for a comment that stops at the end of the line.",
+        ],
+    },
+    Explanation {
+        code: "E-SYNTAX-UNTERMINATED-LITERAL",
+        why: "A text literal starts at a single quote and ends at the next unescaped single \
+              quote. Literals may span lines, so a missing quote swallows the rest of the spell.",
+        fixes: &[
+            "Close the literal with a single quote: 'like this'.",
+            "Escape quotes that are part of the text: 'I\\'m here'.",
+        ],
+    },
+    Explanation {
+        code: "E-SYNTAX-ESCAPE",
+        why: "Inside a literal, '\\' starts an escape. Only \\' \\\\ \\n \\t \\r and \\u{..} \
+              are known; \\u{..} takes one to six hexadecimal digits of a valid character.",
+        fixes: &[
+            "Write '\\\\' for a literal backslash.",
+            "Check the code point in \\u{..}, e.g. \\u{e9} for 'é'.",
+        ],
+    },
+    Explanation {
+        code: "E-SYNTAX-INTERPOLATION",
+        why: "Inside a literal, '@{', '@!{' and '@?{' start an interpolation that must hold a \
+              record name or a container path such as ::user:name, and end with '}'.",
+        fixes: &[
+            "Write the name without its sigil inside the braces: 'Hello, @{name}'.",
+            "Escape the '@' to keep the text as is: 'mail\\@{domain}'.",
+        ],
+    },
+    Explanation {
+        code: "E-SYNTAX-SIGIL",
+        why: "After '@' or '::', '!' marks a sealed binding and '?' a nullable one. A sealed \
+              binding never changes, so it can never become null: the two cannot be combined.",
+        fixes: &[
+            "Use '@!name' for a constant or '@?name' for a value that may be null.",
+            "Write a single sigil: '@!!name' is not valid either.",
+        ],
+    },
+    Explanation {
+        code: "E-SYNTAX-INDENTATION",
+        why: "Inside a container block, a field indented deeper than the one above belongs to \
+              it. Only a bare field, one without values, can hold nested fields, and a field \
+              going back out must line up with an enclosing level.",
+        fixes: &[
+            "Indent nested fields under a field that has no value of its own.",
+            "Line fields of the same container up at the same column.",
+        ],
+    },
+    // Runtime
+    Explanation {
+        code: "E-RUNTIME-UNSUPPORTED",
+        why: "The spell parsed, but the interpreter has no implementation for this command \
+              or form yet.",
+        fixes: &["Check the command name, or use a documented intrinsic instead."],
+    },
+    Explanation {
+        code: "E-RUNTIME-BROKEN-STATEMENT",
+        why: "A statement that failed to parse cannot run. Running a spell stops before it.",
+        fixes: &["Fix the syntax errors reported for the spell, then run it again."],
+    },
+    Explanation {
+        code: "E-RUNTIME-BARE-WORD",
+        why: "Text is quoted. A bare word after 'say', 'ask', 'success' or 'fail' is not a \
+              value, so it would be dropped without a trace.",
+        fixes: &[
+            "Quote it: say 'hello'.",
+            "Write a record or container with its sigil: say @name.",
+        ],
+    },
+    Explanation {
+        code: "E-RECORD-SEALED",
+        why: "A record bound with '@!' is constant: once it holds a value, it never changes.",
+        fixes: &[
+            "Bind the new value to another record.",
+            "Drop the '!' where the record is first bound if it has to change.",
+        ],
+    },
+    Explanation {
+        code: "E-LENS-INVALID",
+        why: "Records are text. A lens reads that text as a type, and fails when the text \
+              does not have the shape of that type.",
+        fixes: &[
+            "'as integer' takes whole numbers: '42', '-7'.",
+            "'as decimal' takes numbers with an optional fraction: '4.5', '3'.",
+            "'as bool' takes 'true', 'false', '1' or '0'.",
+        ],
+    },
+    // Interpolation
+    Explanation {
+        code: "E-INTERP-UNBOUND",
+        why: "An interpolation names a record or container that holds no value at this point \
+              of the spell.",
+        fixes: &[
+            "Assign the value before using it: 'Alice' into @name.",
+            "Check the spelling of the name and of each projection.",
+        ],
+    },
+    Explanation {
+        code: "E-INTERP-SENSITIVE",
+        why: "The value is tainted as sensitive. Putting it into text could expose it, so \
+              interpolation refuses it unless the command accepts the risk.",
+        fixes: &[
+            "Start the command with '!!! sensitive' if exposing the value is intended.",
+            "Interpolate something derived from the secret instead, such as its length.",
+        ],
+    },
+    // Containers
+    Explanation {
+        code: "E-CTNR-FIELD-NOT-FOUND",
+        why: "A ':field' projection names a field the container does not have. Plain \
+              projections expect the field to exist.",
+        fixes: &[
+            "Check the spelling of the field and of the projections before it.",
+            "Use the safe form ':?field' to get null when the field may be missing.",
+        ],
+    },
+    Explanation {
+        code: "E-CTNR-INDEX-OUT-OF-BOUNDS",
+        why: "A '#index' projection is zero-based and must be smaller than the number of \
+              items. A scalar reads as a one-item array, so only '#0' exists.",
+        fixes: &[
+            "Use an index from 0 to the length minus one.",
+            "Use the safe form '#?index' to get null when the item may be missing.",
+        ],
+    },
+    Explanation {
+        code: "E-CTNR-NULL-ACCESS",
+        why: "A projection stepped into a null value. Only safe projections ':?field' and \
+              '#?index' may continue past null, and they yield null again.",
+        fixes: &[
+            "Check the value first: decide ::container is not null into @exists.",
+            "Make each step that may be null safe: ::user:?settings:?theme.",
+        ],
+    },
+    Explanation {
+        code: "E-CTNR-TYPE-MISMATCH",
+        why: "A ':field' projection only works on containers. Arrays and values have no \
+              named fields.",
+        fixes: &["Use ':field' for named nodes and '#index' for arrays."],
+    },
+    Explanation {
+        code: "E-CTNR-UNDEFINED",
+        why: "The container is used before any 'container ... into ::name' bound it, or the \
+              block that bound it has already ended.",
+        fixes: &[
+            "Define the container before this line.",
+            "Check the spelling of the container name.",
+        ],
+    },
+    Explanation {
+        code: "E-CTNR-SEALED",
+        why: "A container bound with '::!' is constant, and so is every field inside it.",
+        fixes: &[
+            "Bind the new data to another container.",
+            "Drop the '!' where the container is first bound if it has to change.",
+        ],
+    },
+    Explanation {
+        code: "E-CTNR-SEAL-INHERITED",
+        why: "Sealing chains: every field of a sealed container or sealed field is sealed \
+              already. Sealing one of them again says nothing new and hints at a mistake.",
+        fixes: &[
+            "Remove the '!' from the field; it inherits the seal from its parent.",
+            "Drop the seal on the parent if only some fields should be constant.",
+        ],
+    },
+    Explanation {
+        code: "E-CTNR-DUPLICATE-FIELD",
+        why: "Each field name appears once per container level, so that ':field' projections \
+              always point at a single node.",
+        fixes: &[
+            "Rename one of the fields.",
+            "Put several values after one field to make an array: :tags 'a' 'b'.",
+        ],
+    },
+    Explanation {
+        code: "E-SINK-DOUBLE-SEAL",
+        why: "A binding is sealed once, where it is first bound with '@!', '::!' or 'save'. \
+              Sealing it again would overwrite a constant.",
+        fixes: &[
+            "Bind the new value to another name.",
+            "Drop the second '!' or 'save' if the binding was meant to be read only.",
+        ],
+    },
+    Explanation {
+        code: "E-SINK-SEAL-PROJECTION",
+        why: "Seals belong to whole bindings. A single field cannot be sealed from outside \
+              its container.",
+        fixes: &[
+            "Seal the whole container: 'into ::!config'.",
+            "Mark the field sealed where the container block declares it: ':!host'.",
+        ],
+    },
+    Explanation {
+        code: "E-SINK-UPDATE-UNBOUND",
+        why: "'update' only overwrites: the record, container or field must already exist.",
+        fixes: &["Use 'into' to create the binding, or check the name for typos."],
+    },
+    Explanation {
+        code: "E-SINK-RECORD-SCALAR",
+        why: "Records hold a single piece of text. Arrays and containers need a container.",
+        fixes: &["Store the value into a container, e.g. '::items' instead of '@items'."],
+    },
+    Explanation {
+        code: "E-SINK-NULL",
+        why: "Only nullable bindings, written '@?' or '::?', may hold null.",
+        fixes: &["Bind it as nullable: 'null into @?maybe.'"],
+    },
+    Explanation {
+        code: "E-SINK-NO-VALUE",
+        why: "A sink or schema check needs a value, and this command does not produce one.",
+        fixes: &["Remove the 'into', 'update' or 'save' part of the command."],
+    },
+    Explanation {
+        code: "E-SINK-RESERVED",
+        why: "'::error' holds the last failure a 'safe' statement let through, with its \
+              code, message and line. Only the interpreter writes it.",
+        fixes: &[
+            "Store the value in a container with another name.",
+            "Read the failure with '::error:code' or '::error:message' instead.",
+        ],
+    },
+    Explanation {
+        code: "E-NULLABLE-UNCHECKED",
+        why: "A binding written '@?' or '::?' may hold null, and no null check guards this \
+              line. Reading a field of a null container fails at runtime.",
+        fixes: &[
+            "Check it first and read it only where the check holds: \
+             if decide @?value is not null, then use @value inside the block.",
+            "Use safe projections such as '::?config:?port' to read null instead of failing.",
+        ],
+    },
+    Explanation {
+        code: "E-SCHEMA-UNKNOWN",
+        why: "'is &name' checks against a schema that is neither built in nor defined.",
+        fixes: &["Use a built-in schema: &integer, &decimal, &bool or &string."],
+    },
+    Explanation {
+        code: "E-SCHEMA-MISMATCH",
+        why: "The value does not have the shape the schema asks for. The check runs before \
+              the sink, so nothing was stored.",
+        fixes: &[
+            "Check the value, e.g. with 'say', before it is validated.",
+            "Convert text first where a lens applies, e.g. 'as integer'.",
+        ],
+    },
+    Explanation {
+        code: "E-RUNTIME-INPUT-CLOSED",
+        why: "'ask' reads a line from standard input, which was closed before an answer came.",
+        fixes: &["Run the spell in a terminal, or pipe the answers into it."],
+    },
+    Explanation {
+        code: "E-CTNR-INVALID-PROJ",
+        why: "The projection cannot be applied: an index is used on a container, or it is \
+              too large to be a position.",
+        fixes: &[
+            "Use ':field' to read a named field of a container.",
+            "Check the projection path against the container definition.",
+        ],
+    },
+    // Calculate
+    Explanation {
+        code: "E-CALCULATE-NONNUM",
+        why: "calculate only works with numbers. One operand is text that cannot be read as an \
+              integer or a decimal.",
+        fixes: &[
+            "Check the value of each record used in the expression.",
+            "Use the text module for string operations.",
+        ],
+    },
+    Explanation {
+        code: "E-CALCULATE-DIVZERO",
+        why: "The right side of '/' or '%' evaluated to zero, and a number cannot be divided \
+              by zero.",
+        fixes: &[
+            "Check the divisor with decide before calculating: decide @divisor == 0 into @is_zero.",
+            "Pick a non-zero fallback value for the divisor.",
+        ],
+    },
+    Explanation {
+        code: "E-CALCULATE-NEGROOT",
+        why: "The '//' operator computes roots. An even root of a negative number has no real \
+              result.",
+        fixes: &[
+            "Make sure the value is zero or positive before taking an even root.",
+            "Use an odd root, such as '// 3', for negative values.",
+        ],
+    },
+    Explanation {
+        code: "E-CALCULATE-BADOP",
+        why: "calculate supports + - * / % ** // and parentheses only, each between two \
+              numbers, and results a number can hold. Comparisons and logic belong to decide.",
+        fixes: &[
+            "Use decide for comparisons: decide @a > @b into @is_greater.",
+            "Check the operator spelling, e.g. '**' for power and '//' for root.",
+            "Give every operator a number on both sides: 1 + 2, not 1 + or 1 2.",
+            "Split the calculation or use smaller numbers when the result is too large.",
+        ],
+    },
+    Explanation {
+        code: "E-CALCULATE-PARENMISM",
+        why: "Every '(' in an expression needs a matching ')'.",
+        fixes: &[
+            "Count the opening and closing parentheses.",
+            "Split the expression into several calculate lines.",
+        ],
+    },
+    // Decide
+    Explanation {
+        code: "E-DECIDE-NONBOOL",
+        why: "Where decide expects a boolean it found something else. There is no implicit \
+              truthiness: text, numbers and containers are not true or false.",
+        fixes: &[
+            "Compare the value instead, e.g. decide @answer == 'yes' into @agreed.",
+            "Store 'true' or 'false' in the record you use as a flag.",
+        ],
+    },
+    Explanation {
+        code: "E-DECIDE-TYPEMISMATCH",
+        why: "'>', '<', '>=' and '<=' compare numbers, and one side is not a number.",
+        fixes: &[
+            "Use '==' or '!=' to compare text.",
+            "Check the value with 'is integer' or 'is decimal' first.",
+        ],
+    },
+    Explanation {
+        code: "E-DECIDE-CHAIN",
+        why: "Comparisons take two values; '1 < @a < 5' does not mean what it says in maths.",
+        fixes: &["Join two comparisons: decide @a > 1 and @a < 5 into @inside."],
+    },
+    Explanation {
+        code: "E-DECIDE-EMPTY",
+        why: "decide was given nothing to evaluate.",
+        fixes: &["Write a condition before the sink: decide @a > 1 into @flag."],
+    },
+    Explanation {
+        code: "E-DECIDE-OPUNKNOWN",
+        why: "decide knows comparisons, 'and', 'or', 'xor', 'not' and 'is'. Arithmetic \
+              belongs to calculate, and there are no parentheses.",
+        fixes: &[
+            "Calculate values first: calculate @a + 1 into @next.",
+            "Write 'and', 'or' and 'not' instead of '&&', '||' and '!'.",
+            "Split a grouped condition into several decide lines.",
+        ],
+    },
+    Explanation {
+        code: "E-DECIDE-NULLACCESS",
+        why: "A null value reached a comparison or a place that needs a boolean.",
+        fixes: &[
+            "Check it first: decide @?x is not null and @?x > 3 into @ok.",
+            "'and' and 'or' stop early, so put the null check on their left.",
+        ],
+    },
+    Explanation {
+        code: "E-DECIDE-SCHEMAUNK",
+        why: "The schema named after 'is' is not defined.",
+        fixes: &[
+            "Check the spelling of the schema name.",
+            "Built-in schemas are &integer, &decimal, &bool and &string.",
+        ],
+    },
+    Explanation {
+        code: "E-DECIDE-PATTERN",
+        why: "The text after 'matches' is not a valid regular expression.",
+        fixes: &[
+            "Escape characters with a special meaning, such as '.', '(' or '[', with '\\'.",
+            "Use 'contains', 'starts with' or 'ends with' for plain text.",
+        ],
+    },
+    // Flow
+    Explanation {
+        code: "E-FLOW-NONBOOL",
+        why: "A block guard reads records that must hold 'true' or 'false'. There is no \
+              implicit truthiness, and null is not false.",
+        fixes: &["Compute the flag first: decide @answer equals 'yes' into @agreed."],
+    },
+    Explanation {
+        code: "E-FLOW-ARITY",
+        why: "'if' takes exactly one flag, while 'any', 'all', 'none' and 'exclusive' \
+              compare two or more.",
+        fixes: &[
+            "Write 'if @flag' for a single flag.",
+            "Add a quantifier for several: if any @a @b.",
+        ],
+    },
+    Explanation {
+        code: "E-FLOW-EXPRESSION",
+        why: "Guards without 'decide' take records computed beforehand, so that every \
+              condition has a name.",
+        fixes: &[
+            "Compute it first: decide @age > 18 into @adult, then if @adult.",
+            "Or write the condition inline: if decide @age > 18.",
+        ],
+    },
+    Explanation {
+        code: "E-FLOW-COUNT",
+        why: "'repeat' needs a whole number of times, and a counting loop needs numbers \
+              for its counter and limit and a step above zero, or it would never end.",
+        fixes: &[
+            "Store the count first: '5' into @count, then repeat @count.",
+            "Count down with 'decrease' rather than a negative step.",
+        ],
+    },
+    Explanation {
+        code: "E-FLOW-LIMIT",
+        why: "Every loop stops after a maximum number of passes, so that a loop whose \
+              condition never changes cannot hang an unattended run.",
+        fixes: &[
+            "Update what the loop checks inside its block.",
+            "Raise the limit for long loops: neko conjure spell.spell with iterations '1000000'.",
+        ],
+    },
+    Explanation {
+        code: "E-FLOW-FOREACH",
+        why: "'foreach' walks through an array, and a record item can only hold text.",
+        fixes: &[
+            "Store several values to make an array: 'a' 'b' into ::items.",
+            "Bind nested containers to a container item: foreach from ::users into ::user.",
+        ],
+    },
+    // Functions
+    Explanation {
+        code: "E-FUNC-NOTFOUND",
+        why: "No function, intrinsic or invoked module has this name. Functions must be \
+              defined with 'function <name> ... end' in the spell.",
+        fixes: &[
+            "Check the spelling of the function name.",
+            "Define the function with 'function <name> ... end'.",
+            "If it is a module action, invoke the module first: invoke <module>.",
+        ],
+    },
+    Explanation {
+        code: "E-FUNC-MISSING-POS",
+        why: "The function declares a required positional parameter that the call did not \
+              provide.",
+        fixes: &[
+            "Pass a value for every 'parameter <n>' of the function.",
+            "Declare the parameter with 'safe parameter <n>' to make it optional.",
+        ],
+    },
+    Explanation {
+        code: "E-FUNC-TOO-MANY-POS",
+        why: "The call passes more positional arguments than the function declares.",
+        fixes: &["Remove the extra arguments or declare more 'parameter <n>' lines."],
+    },
+    Explanation {
+        code: "E-FUNC-NAMED-BEFORE-POS",
+        why: "Positional arguments must come before any 'with <name> <value>' argument.",
+        fixes: &["Move the positional arguments right after the function name."],
+    },
+    Explanation {
+        code: "E-FUNC-UNKNOWN-PARAM",
+        why: "The call names a parameter that the function does not declare.",
+        fixes: &[
+            "Check the parameter spelling.",
+            "Declare it in the function with 'parameter <name> into @var.'.",
+        ],
+    },
+    Explanation {
+        code: "E-FUNC-NO-SIGNAL",
+        why: "Every function must end with 'success' or 'fail'. Reaching 'end' without one is \
+              treated as a failure.",
+        fixes: &["Add 'success.' (or 'success <value>.') as the last command of the function."],
+    },
+    Explanation {
+        code: "E-FUNC-SEALED-MODIFY",
+        why: "Parameters bound with '@!' are sealed and cannot be reassigned in the body.",
+        fixes: &["Copy the parameter into a new record before changing it."],
+    },
+    Explanation {
+        code: "E-FUNC-PARAM-GAP",
+        why: "Positional parameters are numbered 1, 2, 3... so that every argument of a \
+              call has a place.",
+        fixes: &["Renumber the 'parameter <n>' lines without gaps, starting from 1."],
+    },
+    Explanation {
+        code: "E-FUNC-PARAMETER",
+        why: "A 'parameter' line declares one input of a function: a position or a bare \
+              name, and the record or container it goes into. It only appears at the top \
+              level of a function body, once per input.",
+        fixes: &[
+            "Write it as: parameter 1 into @name. or parameter env into @env.",
+            "Move it out of blocks and into the function body itself.",
+        ],
+    },
+    Explanation {
+        code: "E-FUNC-NESTED",
+        why: "Functions are defined once for the whole spell, so they cannot be defined \
+              inside blocks or other functions.",
+        fixes: &["Move the function definition to the top level of the spell."],
+    },
+    Explanation {
+        code: "E-FUNC-DUPLICATE",
+        why: "Each function name means one function, and intrinsics such as 'say' cannot \
+              be redefined.",
+        fixes: &["Rename one of the functions."],
+    },
+    Explanation {
+        code: "E-FUNC-FAILED",
+        why: "The function ran 'fail', so the call that ran it failed too.",
+        fixes: &[
+            "Look at the value the function failed with.",
+            "Mark the call 'safe' to carry on when it fails.",
+        ],
+    },
+    Explanation {
+        code: "E-FUNC-RECURSION",
+        why: "Calls nest at most 64 levels deep, so that a function calling itself without \
+              end fails with an error instead of crashing.",
+        fixes: &["Make sure the recursion reaches 'success' or 'fail' before calling again."],
+    },
+    Explanation {
+        code: "E-ASYNC-COMMAND",
+        why: "Only commands and function calls run in the background. 'success', 'fail', \
+              'parameter', 'wait' and 'stop' steer the spell itself, and values, \
+              'calculate' and 'decide' finish at once.",
+        fixes: &["Remove 'async', or move the work into a function and launch that."],
+    },
+    Explanation {
+        code: "E-ASYNC-SINK",
+        why: "An async command finishes later, in bindings of its own, so it has nowhere \
+              to store a result. 'wait' collects the results of its group instead.",
+        fixes: &["Drop the sink and collect the result: wait 'tag' into ::results."],
+    },
+    Explanation {
+        code: "E-ASYNC-TAG",
+        why: "'wait' and 'stop' name groups by the tag of their 'async' clause, and no \
+              task was launched with this one before this line.",
+        fixes: &[
+            "Check the spelling against the 'async' clause.",
+            "Launch the task before waiting for it.",
+        ],
+    },
+    Explanation {
+        code: "E-ASYNC-SELF-WAIT",
+        why: "A task waited for a group it belongs to, or tasks waited for each \
+              other's groups. None of them can end before the others, so the wait \
+              would never end.",
+        fixes: &["Wait for the groups from the spell, or give the tasks other tags."],
+    },
+    Explanation {
+        code: "E-ASYNC-ARGUMENTS",
+        why: "'wait' takes a tag, 'all' or 'any' with tags, or an amount and a unit; \
+              'stop' takes tags or 'all'.",
+        fixes: &[
+            "Write: wait 'tag'., wait any 'a' 'b'., wait all. or wait 5 seconds.",
+            "Write: stop 'tag'. or stop all.",
+        ],
+    },
+    Explanation {
+        code: "E-ASYNC-DURATION",
+        why: "A pause lasts a number that is not negative, in ms, seconds, minutes or hours.",
+        fixes: &["Write it as: wait 5 seconds. or wait @amount @unit."],
+    },
+    Explanation {
+        code: "E-ASYNC-CANCELLED",
+        why: "'stop' cancelled a task while it paused. The task ends at that pause, \
+              even under 'safe', and its report says 'cancelled'.",
+        fixes: &["Stop only the groups whose results are no longer needed."],
+    },
+    Explanation {
+        code: "E-ASYNC-PANICKED",
+        why: "A task crashed inside the interpreter or a console it was given. It fails \
+              like any task: the first 'wait' that covers it raises the failure, and \
+              'safe async' only warns about it.",
+        fixes: &["Report the crash with the spell that caused it."],
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes_are_unique() {
+        for (index, explanation) in EXPLANATIONS.iter().enumerate() {
+            assert!(
+                EXPLANATIONS[index + 1..]
+                    .iter()
+                    .all(|other| other.code != explanation.code),
+                "duplicate explanation for {}",
+                explanation.code
+            );
+            assert!(!explanation.fixes.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_explain_lookup() {
+        assert!(explain("E-CALCULATE-DIVZERO").is_some());
+        assert!(explain("E-FUNC-NOTFOUND").is_some());
+        assert!(explain("E-NOPE").is_none());
+    }
+}
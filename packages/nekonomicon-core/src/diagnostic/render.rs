@@ -0,0 +1,503 @@
+use std::fmt::Write;
+use std::str::FromStr;
+
+use super::{Diagnostic, Label, Severity, explain};
+use crate::json;
+use crate::span::{LineIndex, Span};
+
+/// How diagnostics are presented, selected with `with mode '<type>'`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RenderMode {
+    /// One line per diagnostic.
+    Minimal,
+    /// Source excerpt with carets, notes and help.
+    #[default]
+    Pretty,
+    /// Boxed report with WHY and FIX sections for learners.
+    Educative,
+    /// Machine readable, one JSON document.
+    Json,
+}
+
+impl FromStr for RenderMode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode {
+            "minimal" => Ok(RenderMode::Minimal),
+            "pretty" => Ok(RenderMode::Pretty),
+            "educative" => Ok(RenderMode::Educative),
+            "json" => Ok(RenderMode::Json),
+            _ => Err(format!(
+                "Unknown mode '{}'. Supported modes: minimal, pretty, educative, json",
+                mode
+            )),
+        }
+    }
+}
+
+/// Renders one diagnostic against the source it points into.
+pub fn render(diagnostic: &Diagnostic, source: &str, mode: RenderMode) -> String {
+    match mode {
+        RenderMode::Minimal => render_minimal(diagnostic),
+        RenderMode::Pretty => render_pretty(diagnostic, source),
+        RenderMode::Educative => render_educative(diagnostic, source),
+        RenderMode::Json => render_json(diagnostic),
+    }
+}
+
+/// Renders several diagnostics; JSON output becomes a single array.
+pub fn render_all(diagnostics: &[Diagnostic], source: &str, mode: RenderMode) -> String {
+    match mode {
+        RenderMode::Json => json::array(diagnostics.iter().map(render_json)),
+        RenderMode::Minimal => diagnostics
+            .iter()
+            .map(render_minimal)
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => diagnostics
+            .iter()
+            .map(|diagnostic| render(diagnostic, source, mode))
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+    }
+}
+
+fn render_minimal(diagnostic: &Diagnostic) -> String {
+    let mut line = format!(
+        "[{}] {}: {}",
+        diagnostic.severity.tag(),
+        diagnostic.code,
+        diagnostic.message
+    );
+    if let Some(span) = diagnostic.span {
+        let _ = write!(line, " (line {}, column {})", span.line, span.column);
+    }
+    line
+}
+
+/// A label resolved to its line: character column and width of the underline.
+struct Marker<'a> {
+    line: usize,
+    column: usize,
+    width: usize,
+    message: &'a str,
+}
+
+/// Labels to draw, falling back to the primary span when there are none.
+fn markers<'a>(diagnostic: &'a Diagnostic, source: &str, lines: &LineIndex) -> Vec<Marker<'a>> {
+    let labels: Vec<(Span, &str)> = if diagnostic.labels.is_empty() {
+        diagnostic.span.map(|span| (span, "")).into_iter().collect()
+    } else {
+        diagnostic
+            .labels
+            .iter()
+            .map(|Label { span, message }| (*span, message.as_str()))
+            .collect()
+    };
+
+    let mut markers: Vec<Marker> = labels
+        .into_iter()
+        .filter(|(span, _)| span.start <= source.len())
+        .map(|(span, message)| {
+            // Underlines never run past the end of the line the label starts on.
+            let (line_start, line_end) = lines.line_range(source, span.line);
+            let start = span.start.clamp(line_start, line_end);
+            let end = span.end.clamp(start, line_end);
+            Marker {
+                line: span.line,
+                column: source[line_start..start].chars().count(),
+                width: source[start..end].chars().count().max(1),
+                message,
+            }
+        })
+        .collect();
+    markers.sort_by_key(|marker| (marker.line, marker.column));
+    markers
+}
+
+fn source_line<'a>(source: &'a str, lines: &LineIndex, line: usize) -> &'a str {
+    let (start, end) = lines.line_range(source, line);
+    source[start..end].trim_end_matches('\r')
+}
+
+fn render_pretty(diagnostic: &Diagnostic, source: &str) -> String {
+    let lines = LineIndex::new(source);
+    let mut output = format!(
+        "{}[{}]: {}",
+        diagnostic.severity.as_str(),
+        diagnostic.code,
+        diagnostic.message
+    );
+
+    let markers = markers(diagnostic, source, &lines);
+    let gutter = markers
+        .iter()
+        .map(|marker| marker.line.to_string().len())
+        .max()
+        .unwrap_or(1);
+
+    if let Some(span) = diagnostic.span {
+        let _ = write!(
+            output,
+            "\n{:gutter$}--> line {}, column {}",
+            "", span.line, span.column
+        );
+    }
+
+    let mut current_line = None;
+    for marker in &markers {
+        if current_line != Some(marker.line) {
+            current_line = Some(marker.line);
+            let _ = write!(output, "\n{:gutter$} |", "");
+            let _ = write!(
+                output,
+                "\n{:>gutter$} | {}",
+                marker.line,
+                source_line(source, &lines, marker.line)
+            );
+        }
+        let caret = if diagnostic.span.is_some_and(|span| span.line == marker.line) {
+            '^'
+        } else {
+            '-'
+        };
+        let _ = write!(
+            output,
+            "\n{:gutter$} | {}{} {}",
+            "",
+            " ".repeat(marker.column),
+            caret.to_string().repeat(marker.width),
+            marker.message
+        );
+        output.truncate(output.trim_end().len());
+    }
+
+    for note in &diagnostic.notes {
+        let _ = write!(output, "\n{:gutter$} = note: {}", "", note);
+    }
+    for help in &diagnostic.help {
+        let _ = write!(output, "\n{:gutter$} = help: {}", "", help);
+    }
+    output
+}
+
+const BOX_WIDTH: usize = 64;
+const BOX_INDENT: &str = "   ";
+
+fn render_educative(diagnostic: &Diagnostic, source: &str) -> String {
+    let lines = LineIndex::new(source);
+    let mut body = vec![
+        String::new(),
+        format!("{}: {}", diagnostic.code, diagnostic.message),
+    ];
+
+    let markers = markers(diagnostic, source, &lines);
+    let mut index = 0;
+    while index < markers.len() {
+        let line = markers[index].line;
+        let group: Vec<&Marker> = markers[index..]
+            .iter()
+            .take_while(|marker| marker.line == line)
+            .collect();
+        index += group.len();
+
+        body.push(String::new());
+        body.push(source_line(source, &lines, line).to_string());
+        body.extend(draw_markers(&group));
+    }
+    body.push(String::new());
+
+    let explanation = explain(&diagnostic.code);
+    let mut why: Vec<String> = Vec::new();
+    if let Some(explanation) = explanation {
+        why.push(explanation.why.to_string());
+    }
+    why.extend(diagnostic.notes.iter().cloned());
+
+    let mut fixes: Vec<String> = diagnostic.help.clone();
+    if let Some(explanation) = explanation {
+        fixes.extend(explanation.fixes.iter().map(|fix| fix.to_string()));
+    }
+
+    let width = body
+        .iter()
+        .map(|line| line.chars().count() + BOX_INDENT.len() * 2)
+        .max()
+        .unwrap_or(0)
+        .max(BOX_WIDTH);
+    let text_width = width - BOX_INDENT.len() * 2;
+
+    let title = match diagnostic.severity {
+        Severity::Error => "ERROR",
+        Severity::Warning => "WARNING",
+        Severity::Info => "INFO",
+    };
+    let mut output = vec![border('╭', title, '╮', width)];
+    output.extend(body.iter().map(|line| boxed(line, width)));
+
+    if !why.is_empty() {
+        output.push(border('├', "WHY?", '┤', width));
+        output.push(boxed("", width));
+        for (position, paragraph) in why.iter().enumerate() {
+            if position > 0 {
+                output.push(boxed("", width));
+            }
+            output.extend(
+                wrap(paragraph, text_width)
+                    .iter()
+                    .map(|line| boxed(line, width)),
+            );
+        }
+        output.push(boxed("", width));
+    }
+
+    if !fixes.is_empty() {
+        output.push(border('├', "FIX", '┤', width));
+        output.push(boxed("", width));
+        for fix in &fixes {
+            for (position, line) in wrap(fix, text_width - 2).iter().enumerate() {
+                let bullet = if position == 0 { "• " } else { "  " };
+                output.push(boxed(&format!("{}{}", bullet, line), width));
+            }
+        }
+        output.push(boxed("", width));
+    }
+
+    output.push(format!("╰{}╯", "─".repeat(width)));
+    output.join("\n")
+}
+
+/// Underlines, connectors and label text for the markers of one source line.
+fn draw_markers(markers: &[&Marker]) -> Vec<String> {
+    let mut rows = Vec::new();
+
+    let mut underline = String::new();
+    for marker in markers {
+        pad_to(&mut underline, marker.column);
+        underline.push_str(&"~".repeat(marker.width));
+    }
+    rows.push(underline);
+
+    let labelled: Vec<&&Marker> = markers
+        .iter()
+        .filter(|marker| !marker.message.is_empty())
+        .collect();
+    if labelled.is_empty() {
+        return rows;
+    }
+
+    let middle = |marker: &Marker| marker.column + (marker.width - 1) / 2;
+    let mut connectors = String::new();
+    for marker in &labelled {
+        pad_to(&mut connectors, middle(marker));
+        connectors.push('│');
+    }
+    rows.push(connectors);
+
+    // The rightmost label is written first so its connector does not cross the others.
+    for (position, marker) in labelled.iter().enumerate().rev() {
+        let mut row = String::new();
+        for left in &labelled[..position] {
+            pad_to(&mut row, middle(left));
+            row.push('│');
+        }
+        pad_to(&mut row, middle(marker));
+        row.push_str("└─ ");
+        row.push_str(marker.message);
+        rows.push(row);
+    }
+    rows
+}
+
+fn pad_to(row: &mut String, column: usize) {
+    let length = row.chars().count();
+    if length < column {
+        row.push_str(&" ".repeat(column - length));
+    } else if length > column && !row.is_empty() {
+        row.push(' ');
+    }
+}
+
+fn border(left: char, title: &str, right: char, width: usize) -> String {
+    let label = format!("─ {} ", title);
+    let fill = width.saturating_sub(label.chars().count());
+    format!("{}{}{}{}", left, label, "─".repeat(fill), right)
+}
+
+fn boxed(text: &str, width: usize) -> String {
+    let content = format!("{}{}", BOX_INDENT, text);
+    let fill = width.saturating_sub(content.chars().count());
+    format!("│{}{}│", content, " ".repeat(fill))
+}
+
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        if !current.is_empty() && current.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+fn span_json(span: &Span) -> String {
+    json::object([
+        ("start", span.start.to_string()),
+        ("end", span.end.to_string()),
+        ("line", span.line.to_string()),
+        ("column", span.column.to_string()),
+    ])
+}
+
+fn render_json(diagnostic: &Diagnostic) -> String {
+    let strings = |items: &[String]| json::array(items.iter().map(|item| json::quote(item)));
+    let explanation = explain(&diagnostic.code);
+
+    json::object([
+        ("code", json::quote(&diagnostic.code)),
+        ("severity", json::quote(diagnostic.severity.as_str())),
+        ("message", json::quote(&diagnostic.message)),
+        (
+            "span",
+            diagnostic
+                .span
+                .as_ref()
+                .map(span_json)
+                .unwrap_or_else(|| "null".to_string()),
+        ),
+        (
+            "labels",
+            json::array(diagnostic.labels.iter().map(|label| {
+                json::object([
+                    ("span", span_json(&label.span)),
+                    ("message", json::quote(&label.message)),
+                ])
+            })),
+        ),
+        ("notes", strings(&diagnostic.notes)),
+        ("help", strings(&diagnostic.help)),
+        (
+            "why",
+            explanation
+                .map(|explanation| json::quote(explanation.why))
+                .unwrap_or_else(|| "null".to_string()),
+        ),
+        (
+            "fixes",
+            json::array(
+                explanation
+                    .map(|explanation| explanation.fixes)
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|fix| json::quote(fix)),
+            ),
+        ),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "say 'ok'.\n'hello' into @age &integer.\n";
+
+    fn schema_error() -> Diagnostic {
+        let lines = LineIndex::new(SOURCE);
+        let value = lines.span(SOURCE, 10, 17);
+        let schema = lines.span(SOURCE, 28, 36);
+        Diagnostic::error("E-SCHEMA-MISMATCH", "'hello' is not an integer")
+            .with_span(value)
+            .with_label(value, "this value: 'hello'")
+            .with_label(schema, "schema requires: integer")
+            .with_note("'hello' contains letters, which are not valid.")
+            .with_help("Use a valid integer: '42' into @age &integer.")
+    }
+
+    #[test]
+    fn test_render_mode_from_str() {
+        assert_eq!("educative".parse::<RenderMode>(), Ok(RenderMode::Educative));
+        assert!("fancy".parse::<RenderMode>().is_err());
+    }
+
+    #[test]
+    fn test_render_minimal() {
+        assert_eq!(
+            render(&schema_error(), SOURCE, RenderMode::Minimal),
+            "[ERRO] E-SCHEMA-MISMATCH: 'hello' is not an integer (line 2, column 1)"
+        );
+    }
+
+    #[test]
+    fn test_render_pretty() {
+        let rendered = render(&schema_error(), SOURCE, RenderMode::Pretty);
+        let expected = "\
+error[E-SCHEMA-MISMATCH]: 'hello' is not an integer
+ --> line 2, column 1
+  |
+2 | 'hello' into @age &integer.
+  | ^^^^^^^ this value: 'hello'
+  |                   ^^^^^^^^ schema requires: integer
+  = note: 'hello' contains letters, which are not valid.
+  = help: Use a valid integer: '42' into @age &integer.";
+        assert_eq!(rendered, expected);
+    }
+
+    #[test]
+    fn test_render_educative_box() {
+        let rendered = render(&schema_error(), SOURCE, RenderMode::Educative);
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert!(lines[0].starts_with("╭─ ERROR ─"));
+        assert!(
+            lines
+                .iter()
+                .all(|line| line.chars().count() == BOX_WIDTH + 2)
+        );
+        assert!(
+            lines.contains(&"│   'hello' into @age &integer.                                  │")
+        );
+        assert!(
+            lines.contains(&"│   ~~~~~~~           ~~~~~~~~                                   │")
+        );
+        assert!(
+            lines.contains(&"│      │                 │                                       │")
+        );
+        assert!(
+            lines.contains(&"│      │                 └─ schema requires: integer             │")
+        );
+        assert!(
+            lines.contains(&"│      └─ this value: 'hello'                                    │")
+        );
+        assert!(lines.iter().any(|line| line.starts_with("├─ WHY? ─")));
+        assert!(
+            lines
+                .iter()
+                .any(|line| line.contains("• Use a valid integer"))
+        );
+    }
+
+    #[test]
+    fn test_educative_uses_catalogue() {
+        let diagnostic = Diagnostic::error("E-CALCULATE-DIVZERO", "Division by zero");
+        let rendered = render(&diagnostic, "", RenderMode::Educative);
+        assert!(rendered.contains("cannot be divided"));
+        assert!(rendered.contains("├─ FIX ─"));
+    }
+
+    #[test]
+    fn test_render_json() {
+        let diagnostic = Diagnostic::error("E-FUNC-NOTFOUND", "Function 'greet' not found");
+        let rendered = render_all(&[diagnostic], "", RenderMode::Json);
+        assert!(rendered.starts_with("[{\"code\":\"E-FUNC-NOTFOUND\",\"severity\":\"error\""));
+        assert!(rendered.contains("\"span\":null"));
+        assert!(rendered.contains("\"why\":\"No function"));
+    }
+}
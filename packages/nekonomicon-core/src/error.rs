@@ -0,0 +1,50 @@
+use std::fmt;
+
+use crate::span::Span;
+
+/// A syntax error located in the source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    /// Stable error code, e.g. `E-SYNTAX-UNEXPECTED`.
+    pub code: &'static str,
+    pub message: String,
+    pub span: Span,
+    /// What the parser would have accepted at `span`, in human words.
+    pub expected: Vec<String>,
+}
+
+impl ParseError {
+    pub const UNEXPECTED: &'static str = "E-SYNTAX-UNEXPECTED";
+    pub const UNTERMINATED_COMMENT: &'static str = "E-SYNTAX-UNTERMINATED-COMMENT";
+    pub const UNTERMINATED_LITERAL: &'static str = "E-SYNTAX-UNTERMINATED-LITERAL";
+    pub const ESCAPE: &'static str = "E-SYNTAX-ESCAPE";
+    pub const INTERPOLATION: &'static str = "E-SYNTAX-INTERPOLATION";
+    pub const SIGIL: &'static str = "E-SYNTAX-SIGIL";
+    pub const INDENTATION: &'static str = "E-SYNTAX-INDENTATION";
+    pub const PROJECTION: &'static str = "E-CTNR-INVALID-PROJ";
+
+    pub fn new(code: &'static str, message: impl Into<String>, span: Span) -> Self {
+        ParseError {
+            code,
+            message: message.into(),
+            span,
+            expected: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{}] {} at line {}, column {}",
+            self.code, self.message, self.span.line, self.span.column
+        )?;
+        if !self.expected.is_empty() {
+            write!(f, " (expected {})", self.expected.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ParseError {}
@@ -0,0 +1,308 @@
+//! Flow control: the guards that decide whether a block runs.
+//!
+//! Guards take booleans computed beforehand. `if @ready` reads one flag and
+//! `if any @a @b` several; anything more is written with `decide`, either on its
+//! own line or as `if decide <condition>`. `while` takes the same guards.
+//!
+//! Every loop stops with an error after [`MAX_ITERATIONS`] passes, or the limit the
+//! interpreter was given, so a `while` that never ends cannot hang a CI run.
+
+use crate::ast::{Guard, Instruction, Quantifier, Reference};
+use crate::decide::{self, Patterns, Token};
+use crate::diagnostic::Diagnostic;
+use crate::error::ParseError;
+use crate::number::Number;
+use crate::schema::Schemas;
+use crate::span::{Span, Spanned};
+use crate::value::Value;
+
+pub const NONBOOL: &str = "E-FLOW-NONBOOL";
+pub const ARITY: &str = "E-FLOW-ARITY";
+pub const EXPRESSION: &str = "E-FLOW-EXPRESSION";
+pub const COUNT: &str = "E-FLOW-COUNT";
+pub const LIMIT: &str = "E-FLOW-LIMIT";
+pub const FOREACH: &str = "E-FLOW-FOREACH";
+
+/// Passes a single loop may make unless the interpreter is told otherwise.
+pub const MAX_ITERATIONS: usize = 100_000;
+
+/// The flags of a guard without `decide`; `keyword` is what the block starts with,
+/// e.g. `if`, for messages.
+pub fn flags(
+    keyword: &str,
+    quantifier: Quantifier,
+    tokens: &[Spanned<Token>],
+    span: Span,
+) -> Result<Guard, ParseError> {
+    let written = match quantifier {
+        Quantifier::One => keyword.to_string(),
+        other => format!("{} {}", keyword, other.as_str()),
+    };
+
+    let mut flags = Vec::with_capacity(tokens.len());
+    for token in tokens {
+        let Token::Operand(Instruction::Reference(reference)) = &token.node else {
+            let mut error = ParseError::new(
+                EXPRESSION,
+                format!(
+                    "'{}' takes records that already hold booleans, not expressions",
+                    written
+                ),
+                token.span,
+            );
+            error.expected.push(format!(
+                "a boolean record; compute the condition with decide first, or write '{} decide'",
+                keyword
+            ));
+            return Err(error);
+        };
+        flags.push(Spanned::new(reference.clone(), token.span));
+    }
+
+    let arity = match quantifier {
+        Quantifier::One if flags.len() > 1 => Some(format!(
+            "'{}' takes one record; use '{} any', '{} all', '{} none' or '{} exclusive' \
+             for several",
+            written, keyword, keyword, keyword, keyword
+        )),
+        Quantifier::One => None,
+        _ if flags.len() < 2 => Some(format!(
+            "'{}' needs at least two records; use '{}' for one",
+            written, keyword
+        )),
+        _ => None,
+    };
+    if let Some(message) = arity {
+        return Err(ParseError::new(ARITY, message, span));
+    }
+    Ok(Guard::Flags { quantifier, flags })
+}
+
+/// Whether `guard` lets its block run, with `operand` giving the value of each operand.
+pub fn holds(
+    guard: &Guard,
+    operand: &dyn Fn(&Instruction, Span) -> Result<Value, Diagnostic>,
+    schemas: &Schemas,
+    patterns: &Patterns,
+) -> Result<bool, Diagnostic> {
+    let (quantifier, flags) = match guard {
+        Guard::Decide(condition) => {
+            return decide::evaluate(condition, operand, schemas, patterns);
+        }
+        Guard::Flags { quantifier, flags } => (quantifier, flags),
+    };
+
+    let mut count = 0;
+    for flag in flags {
+        let value = operand(&Instruction::Reference(flag.node.clone()), flag.span)?;
+        if boolean(&value, flag)? {
+            count += 1;
+        }
+    }
+    Ok(match quantifier {
+        Quantifier::One | Quantifier::All => count == flags.len(),
+        Quantifier::Any => count > 0,
+        Quantifier::None => count == 0,
+        Quantifier::Exclusive => count == 1,
+    })
+}
+
+fn boolean(value: &Value, flag: &Spanned<Reference>) -> Result<bool, Diagnostic> {
+    match value {
+        Value::Boolean(flag) => Ok(*flag),
+        Value::Text(text) if text == "true" => Ok(true),
+        Value::Text(text) if text == "false" => Ok(false),
+        other => Err(Diagnostic::error(
+            NONBOOL,
+            format!("'{}' holds {}, not a boolean", flag.node, shown(other)),
+        )
+        .with_span(flag.span)
+        .with_label(flag.span, "not a boolean")
+        .with_help(format!(
+            "Compute the flag first, e.g. decide {} equals 'yes' into @flag.",
+            flag.node
+        ))),
+    }
+}
+
+/// How many times `repeat` runs: a whole number, zero or more.
+pub fn repetitions(value: &Value, span: Span) -> Result<usize, Diagnostic> {
+    match value {
+        Value::Integer(count) => usize::try_from(*count).ok(),
+        Value::Text(text) => text.trim().parse().ok(),
+        _ => None,
+    }
+    .ok_or_else(|| {
+        count_error(
+            format!("repeat needs a whole number of times, not {}", shown(value)),
+            span,
+        )
+    })
+}
+
+/// The counter or limit of a counting loop; `what` names it in messages.
+pub fn number(value: &Value, what: &str, span: Span) -> Result<Number, Diagnostic> {
+    Number::from_value(value).ok_or_else(|| {
+        count_error(
+            format!("The {} must be a number, not {}", what, shown(value)),
+            span,
+        )
+    })
+}
+
+/// How far a counting loop moves per pass: a number above zero, whichever way it counts.
+pub fn step(value: &Value, span: Span) -> Result<Number, Diagnostic> {
+    let step = number(value, "step", span)?;
+    if step.is_zero() || step.is_negative() {
+        return Err(count_error(
+            format!("The step must be above zero, not {}", shown(value)),
+            span,
+        )
+        .with_help("Use 'decrease' to count down."));
+    }
+    Ok(step)
+}
+
+fn count_error(message: String, span: Span) -> Diagnostic {
+    Diagnostic::error(COUNT, message)
+        .with_span(span)
+        .with_label(span, "not a usable count")
+}
+
+/// The elements `foreach` walks through, those of an array, as `item` will hold them:
+/// a record item gets their text.
+pub fn items(
+    value: &Value,
+    source: &Spanned<Reference>,
+    item: &Spanned<Reference>,
+) -> Result<Vec<Value>, Diagnostic> {
+    let Value::List(items) = value else {
+        return Err(Diagnostic::error(
+            FOREACH,
+            format!(
+                "'{}' holds {}, not an array",
+                source.node,
+                value.described()
+            ),
+        )
+        .with_span(source.span)
+        .with_label(source.span, "not an array")
+        .with_help(match value {
+            Value::Map(_) => format!(
+                "Walk through one of its arrays, e.g. {}:items.",
+                source.node
+            ),
+            _ => "Store several values to make an array: 'a' 'b' into ::items.".to_string(),
+        }));
+    };
+    if let Reference::Container { .. } = item.node {
+        return Ok(items.clone());
+    }
+    items
+        .iter()
+        .map(|element| match element {
+            Value::Null => Ok(Value::Null),
+            element if element.is_scalar() => Ok(Value::Text(element.to_string())),
+            element => Err(Diagnostic::error(
+                FOREACH,
+                format!(
+                    "The record '{}' cannot hold {}",
+                    item.node,
+                    element.described()
+                ),
+            )
+            .with_span(item.span)
+            .with_label(item.span, "a record holds text")
+            .with_help(format!(
+                "Walk through it as a container: into ::{}.",
+                item.node.name()
+            ))),
+        })
+        .collect()
+}
+
+/// The error that stops a loop after `limit` passes.
+pub fn limit_reached(keyword: &str, limit: usize, span: Span) -> Diagnostic {
+    Diagnostic::error(
+        LIMIT,
+        format!(
+            "'{}' stopped after {} passes without finishing",
+            keyword, limit
+        ),
+    )
+    .with_span(span)
+    .with_label(span, "still running")
+    .with_help("Check that the block changes what the loop depends on.")
+    .with_note("Raise the limit for long loops, e.g. with iterations '1000000'.")
+}
+
+fn shown(value: &Value) -> String {
+    match value {
+        Value::Text(text) => format!("'{}'", text),
+        other => other.described(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Statement;
+    use crate::parse;
+
+    fn guard(header: &str) -> Result<Guard, String> {
+        let program = parse(&format!("if {header}\nend")).map_err(|e| e.code.to_string())?;
+        let Statement::Conditional(conditional) = &program.statements[0] else {
+            panic!("Expected an if block");
+        };
+        Ok(conditional.guard.node.clone())
+    }
+
+    fn holds_for(header: &str) -> Result<bool, String> {
+        let operand = |instruction: &Instruction, _| match instruction {
+            Instruction::Reference(reference) => Ok(match reference.name() {
+                "yes" | "on" => Value::from("true"),
+                "no" | "off" => Value::Boolean(false),
+                "nothing" => Value::Null,
+                _ => Value::from("maybe"),
+            }),
+            other => Ok(Value::from(other.text().as_ref())),
+        };
+        holds(&guard(header)?, &operand, &Schemas::new(), &Patterns::new())
+            .map_err(|error| error.code)
+    }
+
+    #[test]
+    fn test_quantifiers() {
+        for (header, result) in [
+            ("@yes", true),
+            ("@no", false),
+            ("any @no @yes", true),
+            ("any @no @off", false),
+            ("all @yes @on", true),
+            ("all @yes @no", false),
+            ("none @no @off", true),
+            ("none @no @yes", false),
+            ("exclusive @yes @no", true),
+            ("exclusive @yes @on", false),
+            ("decide @yes and not @no", true),
+        ] {
+            assert_eq!(holds_for(header), Ok(result), "{header}");
+        }
+    }
+
+    #[test]
+    fn test_guard_errors() {
+        for (header, code) in [
+            ("@maybe", NONBOOL),
+            ("all @yes @nothing", NONBOOL),
+            ("@yes @no", ARITY),
+            ("any @yes", ARITY),
+            ("@count > 3", EXPRESSION),
+            ("true", EXPRESSION),
+            ("all @a @b and @c", EXPRESSION),
+            ("decide @count + 1 > 3", decide::OPUNKNOWN),
+        ] {
+            assert_eq!(holds_for(header), Err(code.to_string()), "{header}");
+        }
+    }
+}
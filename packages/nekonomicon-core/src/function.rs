@@ -0,0 +1,350 @@
+//! User functions: the parameters they declare and how a call binds arguments to them.
+//!
+//! A function declares its inputs with `parameter` lines, positional (`parameter 1`)
+//! or named (`parameter env`). Calls pass positional arguments first, then named ones
+//! as `with env 'prod'`. Positional parameters are required unless declared `safe`;
+//! named ones are always optional. What a call leaves out holds null.
+
+use crate::ast::{
+    Clause, Command, CommandKind, Function, Instruction, ModifierMode, Parameter, ParameterKey,
+    Reference, SinkMode, Statement,
+};
+use crate::diagnostic::Diagnostic;
+use crate::error::ParseError;
+use crate::span::Spanned;
+
+pub const NOTFOUND: &str = "E-FUNC-NOTFOUND";
+pub const MISSING_POS: &str = "E-FUNC-MISSING-POS";
+pub const TOO_MANY_POS: &str = "E-FUNC-TOO-MANY-POS";
+pub const NAMED_BEFORE_POS: &str = "E-FUNC-NAMED-BEFORE-POS";
+pub const UNKNOWN_PARAM: &str = "E-FUNC-UNKNOWN-PARAM";
+pub const NO_SIGNAL: &str = "E-FUNC-NO-SIGNAL";
+pub const PARAM_GAP: &str = "E-FUNC-PARAM-GAP";
+pub const PARAMETER: &str = "E-FUNC-PARAMETER";
+pub const NESTED: &str = "E-FUNC-NESTED";
+pub const DUPLICATE: &str = "E-FUNC-DUPLICATE";
+pub const FAILED: &str = "E-FUNC-FAILED";
+pub const RECURSION: &str = "E-FUNC-RECURSION";
+
+/// Calls that may be in progress at once, so that runaway recursion fails cleanly.
+pub const MAX_DEPTH: usize = 64;
+
+/// Lifts the `parameter` lines out of a function body, checking that positions run
+/// 1, 2, 3... without gaps and that nothing is declared twice.
+pub fn signature(body: Vec<Statement>) -> Result<(Vec<Parameter>, Vec<Statement>), ParseError> {
+    let mut parameters = Vec::new();
+    let mut statements = Vec::with_capacity(body.len());
+    for statement in body {
+        match statement {
+            Statement::Command(command)
+                if command.kind == CommandKind::Intrinsic && command.name.node == "parameter" =>
+            {
+                let parameter = parameter(&command)?;
+                if let Some(previous) = parameters.iter().find(|previous: &&Parameter| {
+                    previous.key == parameter.key
+                        || previous.target.node.name() == parameter.target.node.name()
+                }) {
+                    let mut error = ParseError::new(
+                        PARAMETER,
+                        format!(
+                            "'parameter {}' repeats the one declared on line {}",
+                            describe(&parameter.key),
+                            previous.span.line
+                        ),
+                        parameter.span,
+                    );
+                    error
+                        .expected
+                        .push("one declaration per input and record".to_string());
+                    return Err(error);
+                }
+                parameters.push(parameter);
+            }
+            other => statements.push(other),
+        }
+    }
+
+    parameters.sort_by_key(|parameter| match parameter.key {
+        ParameterKey::Position(position) => (0, position),
+        ParameterKey::Name(_) => (1, 0),
+    });
+    for (index, parameter) in parameters.iter().enumerate() {
+        if let ParameterKey::Position(position) = parameter.key
+            && position != index + 1
+        {
+            let mut error = ParseError::new(
+                PARAM_GAP,
+                format!(
+                    "'parameter {}' comes without 'parameter {}'",
+                    position,
+                    index + 1
+                ),
+                parameter.span,
+            );
+            error.expected.push(format!(
+                "positions counted from 1 without gaps: {}",
+                index + 1
+            ));
+            return Err(error);
+        }
+    }
+    Ok((parameters, statements))
+}
+
+/// `[safe] parameter <position | name> into @record.`
+fn parameter(command: &Command) -> Result<Parameter, ParseError> {
+    let malformed = |message: &str| {
+        let mut error = ParseError::new(PARAMETER, message, command.span);
+        error
+            .expected
+            .push("parameter <position | name> into @record.".to_string());
+        error
+    };
+
+    let key = match (command.actions.as_slice(), command.instructions.as_slice()) {
+        ([name], []) => ParameterKey::Name(name.node.clone()),
+        ([], [position]) => match &position.node {
+            Instruction::Integer(text) => match text.parse() {
+                Ok(position) if position > 0 => ParameterKey::Position(position),
+                _ => return Err(malformed("Parameter positions count from 1")),
+            },
+            _ => return Err(malformed("A parameter has a position or a bare name")),
+        },
+        _ => return Err(malformed("A parameter has one position or name")),
+    };
+    let optional = match command.clauses.as_slice() {
+        [] => matches!(key, ParameterKey::Name(_)),
+        [clause] if clause.node == Clause::Safe => true,
+        _ => return Err(malformed("Only 'safe' may come before 'parameter'")),
+    };
+    if !command.modifiers.is_empty() || command.schema.is_some() || command.platform.is_some() {
+        return Err(malformed("A parameter only names where its argument goes"));
+    }
+    let target = match &command.sink {
+        Some(sink)
+            if sink.mode == SinkMode::Into
+                && !matches!(&sink.target.node, Reference::Container { path, .. }
+                    if !path.is_empty()) =>
+        {
+            sink.target.clone()
+        }
+        Some(_) => return Err(malformed("A parameter goes 'into' a record or container")),
+        None => return Err(malformed("A parameter needs a record to go into")),
+    };
+
+    Ok(Parameter {
+        key,
+        target,
+        optional,
+        span: command.span,
+    })
+}
+
+/// The argument `call` passes for each of the function's parameters, in order;
+/// `None` where it leaves an optional one out.
+///
+/// Bare words after the name count as positional arguments, as text.
+pub fn arguments(
+    function: &Function,
+    call: &Command,
+) -> Result<Vec<Option<Spanned<Instruction>>>, Diagnostic> {
+    if let Some(first) = call.modifiers.first()
+        && let Some(late) = call
+            .instructions
+            .iter()
+            .find(|instruction| instruction.span.start > first.span.start)
+    {
+        return Err(Diagnostic::error(
+            NAMED_BEFORE_POS,
+            format!(
+                "Positional arguments of '{}' come before named ones",
+                function.name.node
+            ),
+        )
+        .with_span(late.span)
+        .with_label(late.span, "positional argument")
+        .with_label(first.span, "after this named argument")
+        .with_help("Move the positional arguments right after the function name."));
+    }
+
+    let mut positional = call
+        .actions
+        .iter()
+        .map(|word| Spanned::new(Instruction::Word(word.node.clone()), word.span))
+        .chain(call.instructions.iter().cloned());
+    let mut arguments: Vec<Option<Spanned<Instruction>>> = Vec::new();
+    for parameter in &function.parameters {
+        arguments.push(match &parameter.key {
+            ParameterKey::Position(position) => match positional.next() {
+                Some(argument) => Some(argument),
+                None if parameter.optional => None,
+                None => {
+                    return Err(Diagnostic::error(
+                        MISSING_POS,
+                        format!(
+                            "'{}' needs argument {} for {}",
+                            function.name.node, position, parameter.target.node
+                        ),
+                    )
+                    .with_span(call.span)
+                    .with_label(call.name.span, "called here")
+                    .with_label(parameter.span, "declared here"));
+                }
+            },
+            ParameterKey::Name(_) => None,
+        });
+    }
+    if let Some(extra) = positional.next() {
+        let declared = arguments.len()
+            - function
+                .parameters
+                .iter()
+                .filter(|parameter| matches!(parameter.key, ParameterKey::Name(_)))
+                .count();
+        return Err(Diagnostic::error(
+            TOO_MANY_POS,
+            format!(
+                "'{}' takes {} positional argument{}",
+                function.name.node,
+                declared,
+                if declared == 1 { "" } else { "s" }
+            ),
+        )
+        .with_span(extra.span)
+        .with_label(extra.span, "one too many"));
+    }
+
+    for modifier in &call.modifiers {
+        let Some(index) = function.parameters.iter().position(|parameter| {
+            matches!(&parameter.key, ParameterKey::Name(name) if *name == modifier.name.node)
+        }) else {
+            let names = function
+                .parameters
+                .iter()
+                .filter_map(|parameter| match &parameter.key {
+                    ParameterKey::Name(name) => Some(format!("'{}'", name)),
+                    ParameterKey::Position(_) => None,
+                })
+                .collect::<Vec<_>>();
+            let error = Diagnostic::error(
+                UNKNOWN_PARAM,
+                format!(
+                    "'{}' has no parameter named '{}'",
+                    function.name.node, modifier.name.node
+                ),
+            )
+            .with_span(modifier.name.span)
+            .with_label(modifier.name.span, "not declared");
+            return Err(match names.is_empty() {
+                true => error.with_help("This function only takes positional arguments."),
+                false => error.with_help(format!("It takes {}.", names.join(", "))),
+            });
+        };
+        // `with verbose` alone switches a flag on, `without verbose` off.
+        arguments[index] = Some(modifier.value.clone().unwrap_or_else(|| {
+            Spanned::new(
+                Instruction::Boolean(modifier.mode == ModifierMode::With),
+                modifier.name.span,
+            )
+        }));
+    }
+    Ok(arguments)
+}
+
+/// The error for a call to a name no function has.
+pub fn not_found(name: &Spanned<String>) -> Diagnostic {
+    Diagnostic::error(NOTFOUND, format!("Function '{}' not found", name.node))
+        .with_span(name.span)
+        .with_label(name.span, "not defined")
+        .with_help(format!(
+            "Define it with 'function {} ... end', anywhere in the spell.",
+            name.node
+        ))
+}
+
+fn describe(key: &ParameterKey) -> String {
+    match key {
+        ParameterKey::Position(position) => position.to_string(),
+        ParameterKey::Name(name) => name.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    fn call(source: &str) -> Result<Vec<Option<String>>, String> {
+        let program = parse(source).map_err(|error| error.code.to_string())?;
+        let [Statement::Function(function), Statement::Command(call)] =
+            program.statements.as_slice()
+        else {
+            panic!("Expected a function and a call");
+        };
+        let arguments = arguments(function, call).map_err(|error| error.code)?;
+        Ok(arguments
+            .into_iter()
+            .map(|argument| argument.map(|argument| argument.node.text().into_owned()))
+            .collect())
+    }
+
+    const GREET: &str = "function greet
+           parameter title into @title.
+           parameter 1 into @!name.
+           safe parameter 2 into @surname.
+           success.
+         end\n";
+
+    #[test]
+    fn test_call_binding() {
+        let some = |text: &str| Some(text.to_string());
+        for (source, arguments) in [
+            ("greet 'Ada'.", vec![some("Ada"), None, None]),
+            ("greet 'Ada' 'L'.", vec![some("Ada"), some("L"), None]),
+            (
+                "greet 'Ada' with title 'Dr.'.",
+                vec![some("Ada"), None, some("Dr.")],
+            ),
+            (
+                "greet Ada with title.",
+                vec![some("Ada"), None, some("true")],
+            ),
+        ] {
+            assert_eq!(call(&format!("{GREET}{source}")), Ok(arguments), "{source}");
+        }
+    }
+
+    #[test]
+    fn test_call_errors() {
+        for (source, code) in [
+            ("greet.", MISSING_POS),
+            ("greet 'a' 'b' 'c'.", TOO_MANY_POS),
+            ("greet with title 'Dr.' 'Ada'.", NAMED_BEFORE_POS),
+            ("greet 'Ada' with name 'x'.", UNKNOWN_PARAM),
+        ] {
+            assert_eq!(
+                call(&format!("{GREET}{source}")),
+                Err(code.to_string()),
+                "{source}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_signature_errors() {
+        for (body, code) in [
+            ("parameter 1 into @a.\nparameter 3 into @c.", PARAM_GAP),
+            ("parameter 2 into @b.", PARAM_GAP),
+            ("parameter 1 into @a.\nparameter 1 into @b.", PARAMETER),
+            ("parameter name into @a.\nparameter 1 into @a.", PARAMETER),
+            ("parameter 1.", PARAMETER),
+            ("parameter 0 into @a.", PARAMETER),
+            ("parameter 'x' into @a.", PARAMETER),
+            ("function inner\nend", NESTED),
+        ] {
+            let source = format!("function f\n{body}\nend");
+            assert_eq!(parse(&source).unwrap_err().code, code, "{body}");
+        }
+        assert_eq!(parse("function say\nend").unwrap_err().code, DUPLICATE);
+    }
+}
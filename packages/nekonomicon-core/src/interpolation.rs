@@ -0,0 +1,139 @@
+//! Runtime evaluation of `@{..}` interpolations inside literals.
+
+use crate::ast::{Reference, Segment, Template};
+use crate::diagnostic::Diagnostic;
+use crate::span::Span;
+
+pub const UNBOUND: &str = "E-INTERP-UNBOUND";
+pub const SENSITIVE: &str = "E-INTERP-SENSITIVE";
+
+/// A bound value as interpolation sees it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolved {
+    pub text: String,
+    /// Tainted by `sensitive`; it may only leave the spell under `!!! sensitive`.
+    pub sensitive: bool,
+}
+
+/// Where interpolation looks references up at runtime.
+pub trait Scope {
+    /// The value behind `reference`, projections applied, or `None` when unbound.
+    /// Projections that cannot be followed fail with a diagnostic at `span`.
+    fn resolve(&self, reference: &Reference, span: Span) -> Result<Option<Resolved>, Diagnostic>;
+}
+
+impl<F: Fn(&Reference) -> Option<Resolved>> Scope for F {
+    fn resolve(&self, reference: &Reference, _span: Span) -> Result<Option<Resolved>, Diagnostic> {
+        Ok(self(reference))
+    }
+}
+
+/// Renders `template`, refusing sensitive values unless `allow_sensitive` is set,
+/// which is what the `!!! sensitive` clause does.
+pub fn interpolate(
+    template: &Template,
+    scope: &dyn Scope,
+    allow_sensitive: bool,
+) -> Result<String, Diagnostic> {
+    let mut text = String::new();
+    for segment in &template.segments {
+        match segment {
+            Segment::Text(part) => text.push_str(part),
+            Segment::Interpolation(reference) => {
+                text.push_str(&resolve(reference, reference.span, scope, allow_sensitive)?)
+            }
+        }
+    }
+    Ok(text)
+}
+
+/// The text behind one reference, with the same unbound and sensitive checks.
+pub fn resolve(
+    reference: &Reference,
+    span: Span,
+    scope: &dyn Scope,
+    allow_sensitive: bool,
+) -> Result<String, Diagnostic> {
+    let resolved = scope.resolve(reference, span)?.ok_or_else(|| {
+        let target = match reference {
+            Reference::Record { .. } => format!("@{}", reference.name()),
+            Reference::Container { .. } => format!("::{}", reference.name()),
+        };
+        Diagnostic::error(UNBOUND, format!("'{}' is not bound", reference))
+            .with_span(span)
+            .with_label(span, "used here")
+            .with_help(format!(
+                "Assign it before this line: 'value' into {target}."
+            ))
+    })?;
+    if resolved.sensitive && !allow_sensitive {
+        return Err(sensitive(reference, span));
+    }
+    Ok(resolved.text)
+}
+
+/// The error for a sensitive value about to leave the spell without `!!! sensitive`.
+pub fn sensitive(reference: &Reference, span: Span) -> Diagnostic {
+    Diagnostic::error(
+        SENSITIVE,
+        format!("'{}' holds a sensitive value", reference),
+    )
+    .with_span(span)
+    .with_label(span, "sensitive value")
+    .with_help("Start the command with '!!! sensitive' to let it leave the spell.")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Instruction;
+    use crate::parse;
+
+    fn template(literal: &str) -> Template {
+        let program = parse(&format!("say {literal}.")).expect("Parse failed");
+        match &program.statements[0] {
+            crate::Statement::Command(command) => match &command.instructions[0].node {
+                Instruction::Template(template) => template.clone(),
+                other => panic!("Expected a template, found {:?}", other),
+            },
+            other => panic!("Expected a command, found {:?}", other),
+        }
+    }
+
+    fn scope(reference: &Reference) -> Option<Resolved> {
+        let (text, sensitive) = match reference.to_string().as_str() {
+            "@name" => ("Alice", false),
+            "::user:?address:city" => ("Lyon", false),
+            "@!password" => ("hunter2", true),
+            _ => return None,
+        };
+        Some(Resolved {
+            text: text.to_string(),
+            sensitive,
+        })
+    }
+
+    #[test]
+    fn test_interpolate() {
+        let template = template("'Hello, @{name} from @{::user:?address:city}!'");
+        assert_eq!(
+            interpolate(&template, &scope, false).unwrap(),
+            "Hello, Alice from Lyon!"
+        );
+    }
+
+    #[test]
+    fn test_unbound_reference() {
+        let error = interpolate(&template("'Hi @{nobody}'"), &scope, false).unwrap_err();
+        assert_eq!(error.code, UNBOUND);
+        assert_eq!(error.span.map(|span| span.column), Some(9));
+    }
+
+    #[test]
+    fn test_sensitive_needs_egress_clause() {
+        let template = template("'pw: @!{password}'");
+        let error = interpolate(&template, &scope, false).unwrap_err();
+        assert_eq!(error.code, SENSITIVE);
+        assert_eq!(interpolate(&template, &scope, true).unwrap(), "pw: hunter2");
+    }
+}
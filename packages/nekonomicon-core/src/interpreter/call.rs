@@ -0,0 +1,150 @@
+//! Calls of user functions: hoisting their definitions, binding arguments in a
+//! scope of their own and taking the outcome of the body.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::ast::{Clause, Command, Reference, Statement};
+use crate::diagnostic::Diagnostic;
+use crate::function;
+use crate::span::{Span, Spanned};
+use crate::value::Value;
+
+use super::environment::Binding;
+use super::{Interpreter, Outcome, Status, nodes, sink};
+
+impl Interpreter {
+    /// Defines every function of the spell before it runs, so that calls may come
+    /// before definitions.
+    pub(super) fn hoist(&mut self, statements: &[Statement]) -> Result<(), Diagnostic> {
+        let mut defined: HashMap<&str, Span> = HashMap::new();
+        for statement in statements {
+            let Statement::Function(definition) = statement else {
+                continue;
+            };
+            let name = &definition.name;
+            if let Some(previous) = defined.insert(&name.node, name.span) {
+                return Err(Diagnostic::error(
+                    function::DUPLICATE,
+                    format!("Function '{}' is defined twice", name.node),
+                )
+                .with_span(name.span)
+                .with_label(name.span, "defined again here")
+                .with_label(previous, "first defined here")
+                .with_help("Rename one of them."));
+            }
+            self.functions
+                .insert(name.node.clone(), Arc::new(definition.clone()));
+        }
+        Ok(())
+    }
+
+    /// Runs the function `command` names and gives what its `success` returned, and
+    /// whether that was read from a sensitive binding.
+    ///
+    /// The body sees its parameters and the globals, never the caller's records.
+    pub(super) fn call(&mut self, command: &Command) -> Result<(Value, bool), Diagnostic> {
+        let Some(definition) = self.functions.get(&command.name.node).cloned() else {
+            return Err(function::not_found(&command.name));
+        };
+        if self.depth >= function::MAX_DEPTH {
+            return Err(Diagnostic::error(
+                function::RECURSION,
+                format!(
+                    "'{}' was called more than {} levels deep",
+                    command.name.node,
+                    function::MAX_DEPTH
+                ),
+            )
+            .with_span(command.name.span)
+            .with_label(command.name.span, "called here")
+            .with_help("Check that the recursion ends with 'success' or 'fail'."));
+        }
+
+        // Arguments are read in the caller's scope, before the body's opens.
+        let sensitive = command.has_clause(&Clause::Sensitive);
+        let mut bindings = Vec::with_capacity(definition.parameters.len());
+        for (parameter, argument) in definition
+            .parameters
+            .iter()
+            .zip(function::arguments(&definition, command)?)
+        {
+            let mut binding = match argument {
+                Some(argument) => {
+                    let argument = std::slice::from_ref(&argument);
+                    let value = argument_value(&parameter.target, self.values(argument)?)?;
+                    let mut binding = Binding::new(value, &parameter.target.node);
+                    binding.sensitive = self.tainted(nodes(argument));
+                    binding
+                }
+                None => Binding {
+                    nullable: true,
+                    ..Binding::new(Value::Null, &parameter.target.node)
+                },
+            };
+            binding.sensitive |= sensitive;
+            bindings.push((&parameter.target.node, binding));
+        }
+
+        self.environment.push_function();
+        self.depth += 1;
+        for (target, binding) in bindings {
+            self.environment.define(target, binding);
+        }
+        let result = self.statements(&definition.body);
+        self.depth -= 1;
+        self.environment.pop();
+        result?;
+
+        match self.outcome.take() {
+            Some(Outcome {
+                status: Status::Success,
+                value,
+                sensitive,
+            }) => Ok((value, sensitive)),
+            Some(Outcome { value, .. }) => {
+                let reason = match value.text() {
+                    Some(text) if !value.is_null() => format!(": {}", text),
+                    _ => String::new(),
+                };
+                Err(Diagnostic::error(
+                    function::FAILED,
+                    format!("'{}' failed{}", command.name.node, reason),
+                )
+                .with_span(command.name.span)
+                .with_label(command.name.span, "called here")
+                .with_label(definition.name.span, "failed in this function"))
+            }
+            None => Err(Diagnostic::error(
+                function::NO_SIGNAL,
+                format!("'{}' ended without 'success' or 'fail'", command.name.node),
+            )
+            .with_span(command.name.span)
+            .with_label(command.name.span, "called here")
+            .with_label(definition.name.span, "this function ends without a result")
+            .with_help("End the function with 'success.' or 'success <value>.'.")),
+        }
+    }
+}
+
+/// What a parameter holds for `value`: records keep only the text of a scalar.
+fn argument_value(target: &Spanned<Reference>, value: Value) -> Result<Value, Diagnostic> {
+    match (&target.node, &value) {
+        (Reference::Record { .. }, value) if !value.is_scalar() => Err(Diagnostic::error(
+            sink::RECORD_SCALAR,
+            format!(
+                "The record '{}' cannot hold {}",
+                target.node,
+                value.described()
+            ),
+        )
+        .with_span(target.span)
+        .with_label(target.span, "declared as a record")
+        .with_help(format!(
+            "Declare the parameter as a container: into ::{}.",
+            target.node.name()
+        ))),
+        (Reference::Record { .. }, Value::Null) | (Reference::Container { .. }, _) => Ok(value),
+        (Reference::Record { .. }, value) => Ok(Value::Text(value.to_string())),
+    }
+}
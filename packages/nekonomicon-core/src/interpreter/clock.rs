@@ -0,0 +1,65 @@
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The time a spell runs on: `wait 5 seconds` pauses it, and tasks are timed by it.
+/// Async tasks share the clock of their spell, hence `Send`.
+pub trait Clock: Send {
+    /// Time passed since the clock started.
+    fn now(&self) -> Duration;
+
+    fn sleep(&mut self, duration: Duration);
+}
+
+/// Wall-clock time; `sleep` blocks the thread.
+#[derive(Debug, Clone, Copy)]
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        SystemClock {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+/// A clock that only moves when slept on, for tests and embedders.
+///
+/// Clones share their time, so a clone kept aside reads how long a spell waited.
+#[derive(Debug, Default, Clone)]
+pub struct FakeClock {
+    elapsed: Arc<Mutex<Duration>>,
+}
+
+impl FakeClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Moves the clock forward without a spell waiting.
+    pub fn advance(&self, duration: Duration) {
+        *self.elapsed.lock().expect("clock lock poisoned") += duration;
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> Duration {
+        *self.elapsed.lock().expect("clock lock poisoned")
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        self.advance(duration);
+    }
+}
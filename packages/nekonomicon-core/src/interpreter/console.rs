@@ -0,0 +1,88 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+use std::sync::{Arc, Mutex};
+
+/// Where a spell talks to its user: `say` writes, `ask` reads. Async tasks share
+/// the console of their spell, hence `Send`.
+pub trait Console: Send {
+    fn say(&mut self, text: &str);
+
+    /// Reports a problem the spell carries on after, e.g. a failed `safe` command.
+    fn warn(&mut self, text: &str) {
+        self.say(text);
+    }
+
+    /// Shows `prompt` and reads one line, or `None` when input is closed.
+    fn ask(&mut self, prompt: &str) -> Option<String>;
+}
+
+/// The terminal: standard output and standard input.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StdConsole;
+
+impl Console for StdConsole {
+    fn say(&mut self, text: &str) {
+        println!("{}", text);
+    }
+
+    fn warn(&mut self, text: &str) {
+        eprintln!("{}", text);
+    }
+
+    fn ask(&mut self, prompt: &str) -> Option<String> {
+        print!("{} ", prompt);
+        io::stdout().flush().ok()?;
+        let mut line = String::new();
+        match io::stdin().lock().read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line.trim_end_matches(['\n', '\r']).to_string()),
+        }
+    }
+}
+
+/// A console kept in memory, for tests and embedders.
+///
+/// Clones share their buffers, so a clone kept aside reads what the interpreter said.
+#[derive(Debug, Default, Clone)]
+pub struct BufferedConsole {
+    output: Arc<Mutex<Vec<String>>>,
+    answers: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl BufferedConsole {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues the answers later `ask` calls receive, in order.
+    pub fn with_answers<I: IntoIterator<Item = S>, S: Into<String>>(answers: I) -> Self {
+        let console = Self::default();
+        console
+            .answers
+            .lock()
+            .expect("console lock poisoned")
+            .extend(answers.into_iter().map(Into::into));
+        console
+    }
+
+    /// Every line said or warned so far.
+    pub fn output(&self) -> Vec<String> {
+        self.output.lock().expect("console lock poisoned").clone()
+    }
+}
+
+impl Console for BufferedConsole {
+    fn say(&mut self, text: &str) {
+        self.output
+            .lock()
+            .expect("console lock poisoned")
+            .push(text.to_string());
+    }
+
+    fn ask(&mut self, _prompt: &str) -> Option<String> {
+        self.answers
+            .lock()
+            .expect("console lock poisoned")
+            .pop_front()
+    }
+}
@@ -0,0 +1,303 @@
+use std::collections::HashMap;
+
+use crate::ast::Reference;
+use crate::container;
+use crate::diagnostic::Diagnostic;
+use crate::interpolation::{self, Resolved, Scope};
+use crate::span::Span;
+use crate::value::Value;
+
+pub const RECORD_SEALED: &str = "E-RECORD-SEALED";
+
+/// Records and containers live in separate namespaces: `@user` and `::user` differ.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Namespace {
+    Record,
+    Container,
+}
+
+impl Namespace {
+    pub fn of(reference: &Reference) -> Namespace {
+        match reference {
+            Reference::Record { .. } => Namespace::Record,
+            Reference::Container { .. } => Namespace::Container,
+        }
+    }
+}
+
+/// A value bound to a name, with the guarantees it was bound with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Binding {
+    pub value: Value,
+    /// Bound through `@!` or `::!`; it can no longer change.
+    pub sealed: bool,
+    /// Bound through `@?` or `::?`; it may hold null.
+    pub nullable: bool,
+    /// Tainted by the `sensitive` clause.
+    pub sensitive: bool,
+}
+
+impl Binding {
+    /// A binding with the guarantees the sigil of `reference` asks for.
+    pub fn new(value: Value, reference: &Reference) -> Self {
+        Binding {
+            value,
+            sealed: reference.sigil().is_sealed(),
+            nullable: reference.sigil().is_nullable(),
+            sensitive: false,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Frame {
+    bindings: HashMap<(Namespace, String), Binding>,
+    /// Function frames see only themselves and the global frame.
+    isolated: bool,
+}
+
+/// Every binding of a running spell, in lexical frames.
+///
+/// The first frame holds the globals and is never popped. Blocks add frames that
+/// still see everything around them; function bodies add isolated frames that
+/// see only the globals, so callers' records never leak into a function.
+#[derive(Debug)]
+pub struct Environment {
+    frames: Vec<Frame>,
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Environment {
+            frames: vec![Frame::default()],
+        }
+    }
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Opens the scope of a block; what it binds disappears at its end.
+    pub fn push_block(&mut self) {
+        self.frames.push(Frame::default());
+    }
+
+    /// Opens the scope of a function body.
+    pub fn push_function(&mut self) {
+        self.frames.push(Frame {
+            isolated: true,
+            ..Frame::default()
+        });
+    }
+
+    /// Closes the innermost scope; the global frame stays.
+    pub fn pop(&mut self) {
+        if self.frames.len() > 1 {
+            self.frames.pop();
+        }
+    }
+
+    /// Number of open scopes, globals included.
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// The binding `reference` names, ignoring its projections.
+    pub fn lookup(&self, reference: &Reference) -> Option<&Binding> {
+        let key = (Namespace::of(reference), reference.name().to_string());
+        self.visible()
+            .find_map(|index| self.frames[index].bindings.get(&key))
+    }
+
+    pub fn lookup_mut(&mut self, reference: &Reference) -> Option<&mut Binding> {
+        let key = (Namespace::of(reference), reference.name().to_string());
+        let index = self
+            .visible()
+            .find(|&index| self.frames[index].bindings.contains_key(&key))?;
+        self.frames[index].bindings.get_mut(&key)
+    }
+
+    /// The value `reference` points at, projections applied.
+    pub fn value(&self, reference: &Reference, span: Span) -> Result<&Value, Diagnostic> {
+        let binding = self
+            .lookup(reference)
+            .ok_or_else(|| unbound(reference, span))?;
+        container::project(reference, &binding.value, span)
+    }
+
+    /// Binds `value` to the name of `reference`, replacing a visible binding or
+    /// creating one in the innermost scope.
+    pub fn bind(
+        &mut self,
+        reference: &Reference,
+        value: Value,
+        span: Span,
+    ) -> Result<(), Diagnostic> {
+        let mut binding = Binding::new(value, reference);
+        if let Some(existing) = self.lookup(reference) {
+            if existing.sealed {
+                return Err(sealed(reference, span));
+            }
+            binding.nullable |= existing.nullable;
+        }
+        self.store(reference, binding);
+        Ok(())
+    }
+
+    /// Puts `binding` in place of the visible one, or in the innermost scope when
+    /// there is none. Unlike [`Environment::bind`], it checks nothing.
+    pub fn store(&mut self, reference: &Reference, binding: Binding) {
+        match self.lookup_mut(reference) {
+            Some(existing) => *existing = binding,
+            None => self.define(reference, binding),
+        }
+    }
+
+    /// A copy of every binding visible from the innermost scope, as the globals of
+    /// an environment of its own, e.g. for a task that runs later.
+    pub fn capture(&self) -> Environment {
+        let mut frame = Frame::default();
+        let visible: Vec<usize> = self.visible().collect();
+        // Outermost first, so that inner bindings win.
+        for index in visible.into_iter().rev() {
+            for (key, binding) in &self.frames[index].bindings {
+                frame.bindings.insert(key.clone(), binding.clone());
+            }
+        }
+        Environment {
+            frames: vec![frame],
+        }
+    }
+
+    /// Binds in the innermost scope, shadowing anything outside it, e.g. parameters.
+    pub fn define(&mut self, reference: &Reference, binding: Binding) {
+        let key = (Namespace::of(reference), reference.name().to_string());
+        self.innermost().bindings.insert(key, binding);
+    }
+
+    fn innermost(&mut self) -> &mut Frame {
+        self.frames
+            .last_mut()
+            .expect("the global frame is never popped")
+    }
+
+    /// Indexes of the frames a lookup may read, innermost first.
+    fn visible(&self) -> impl Iterator<Item = usize> {
+        let boundary = self
+            .frames
+            .iter()
+            .rposition(|frame| frame.isolated)
+            .unwrap_or(0);
+        (boundary..self.frames.len())
+            .rev()
+            .chain((boundary > 0).then_some(0))
+    }
+}
+
+impl Scope for Environment {
+    fn resolve(&self, reference: &Reference, span: Span) -> Result<Option<Resolved>, Diagnostic> {
+        let Some(binding) = self.lookup(reference) else {
+            return Ok(None);
+        };
+        let value = container::project(reference, &binding.value, span)?;
+        Ok(Some(Resolved {
+            text: value.to_string(),
+            sensitive: binding.sensitive,
+        }))
+    }
+}
+
+fn unbound(reference: &Reference, span: Span) -> Diagnostic {
+    match reference {
+        Reference::Record { .. } => Diagnostic::error(
+            interpolation::UNBOUND,
+            format!("'{}' is not bound", reference),
+        )
+        .with_help(format!(
+            "Assign it before this line: 'value' into @{}.",
+            reference.name()
+        )),
+        Reference::Container { .. } => Diagnostic::error(
+            container::UNDEFINED,
+            format!("Container '::{}' is not defined", reference.name()),
+        )
+        .with_help(format!(
+            "Define it first: container ... into ::{}.",
+            reference.name()
+        )),
+    }
+    .with_span(span)
+    .with_label(span, "used here")
+}
+
+pub(super) fn sealed(reference: &Reference, span: Span) -> Diagnostic {
+    let code = match reference {
+        Reference::Record { .. } => RECORD_SEALED,
+        Reference::Container { .. } => container::SEALED,
+    };
+    Diagnostic::error(code, format!("'{}' is sealed and cannot change", reference))
+        .with_span(span)
+        .with_label(span, "written here")
+        .with_help("Bind the new value to another name, or drop the '!' where it is first bound.")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Sigil;
+
+    fn record(name: &str, sigil: Sigil) -> Reference {
+        Reference::Record {
+            name: name.to_string(),
+            sigil,
+        }
+    }
+
+    fn text(value: &str) -> Value {
+        Value::Text(value.to_string())
+    }
+
+    #[test]
+    fn test_block_and_function_scopes() {
+        let mut environment = Environment::new();
+        let global = record("global", Sigil::Plain);
+        let local = record("local", Sigil::Plain);
+        environment
+            .bind(&global, text("g"), Span::default())
+            .unwrap();
+
+        environment.push_block();
+        environment
+            .bind(&local, text("l"), Span::default())
+            .unwrap();
+        environment
+            .bind(&global, text("changed"), Span::default())
+            .unwrap();
+
+        environment.push_function();
+        assert!(environment.lookup(&local).is_none());
+        assert_eq!(environment.lookup(&global).unwrap().value, text("changed"));
+        environment.pop();
+
+        environment.pop();
+        assert!(environment.lookup(&local).is_none());
+        assert_eq!(environment.lookup(&global).unwrap().value, text("changed"));
+        assert_eq!(environment.depth(), 1);
+    }
+
+    #[test]
+    fn test_sealed_bindings_do_not_change() {
+        let mut environment = Environment::new();
+        let constant = record("max", Sigil::Sealed);
+        environment
+            .bind(&constant, text("1"), Span::default())
+            .unwrap();
+        let error = environment
+            .bind(&record("max", Sigil::Plain), text("2"), Span::default())
+            .unwrap_err();
+        assert_eq!(error.code, RECORD_SEALED);
+        assert_eq!(environment.lookup(&constant).unwrap().value, text("1"));
+    }
+}
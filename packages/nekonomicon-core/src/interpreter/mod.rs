@@ -0,0 +1,1160 @@
+//! Tree-walking interpreter: runs a parsed [`Program`] statement by statement.
+
+mod call;
+mod clock;
+mod console;
+mod environment;
+pub mod outcome;
+pub mod safe;
+mod scheduler;
+pub mod sink;
+
+pub use clock::{Clock, FakeClock, SystemClock};
+pub use console::{BufferedConsole, Console, StdConsole};
+pub use environment::{Binding, Environment, Namespace, RECORD_SEALED};
+pub use outcome::{Outcome, Status};
+
+use crate::ast::{
+    Assignment, Calculation, Clause, Command, CommandKind, Conditional, Container, Decision,
+    Direction, Function, Guard, Instruction, Loop, LoopKind, Platform, Program, Reference, Segment,
+    Sink, Statement,
+};
+use crate::calculate;
+use crate::container;
+use crate::decide::{self, Patterns};
+use crate::diagnostic::Diagnostic;
+use crate::flow;
+use crate::function;
+use crate::interpolation::{self, interpolate};
+use crate::schema::Schemas;
+use crate::span::{Span, Spanned};
+use crate::task;
+use crate::value::Value;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use scheduler::Scheduler;
+
+pub const UNSUPPORTED: &str = "E-RUNTIME-UNSUPPORTED";
+pub const BROKEN_STATEMENT: &str = "E-RUNTIME-BROKEN-STATEMENT";
+pub const INPUT_CLOSED: &str = "E-RUNTIME-INPUT-CLOSED";
+pub const BARE_WORD: &str = "E-RUNTIME-BARE-WORD";
+
+/// Runs `program` on the terminal.
+pub fn execute(program: &Program) -> Result<Outcome, Diagnostic> {
+    Interpreter::new().run(program)
+}
+
+/// Holds the state of a running spell: its bindings, functions, the schemas it may
+/// check against and its console.
+pub struct Interpreter {
+    environment: Environment,
+    schemas: Schemas,
+    /// Patterns `decide ... matches` compiled so far.
+    patterns: Patterns,
+    /// Shared with the tasks, like the clock and the scheduler.
+    console: Arc<Mutex<dyn Console>>,
+    /// Passes a single loop may make before it is stopped.
+    max_iterations: usize,
+    functions: HashMap<String, Arc<Function>>,
+    /// Calls in progress.
+    depth: usize,
+    /// Set by `success` or `fail`; nothing else runs in the body until it is taken.
+    outcome: Option<Outcome>,
+    /// Tasks launched with `async`.
+    scheduler: Arc<Scheduler>,
+    clock: Arc<Mutex<dyn Clock>>,
+    /// The task this interpreter runs, or `None` for the spell itself.
+    task: Option<usize>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::with_console(StdConsole)
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_console(console: impl Console + 'static) -> Self {
+        Interpreter {
+            environment: Environment::new(),
+            schemas: Schemas::new(),
+            patterns: Patterns::new(),
+            console: Arc::new(Mutex::new(console)),
+            max_iterations: flow::MAX_ITERATIONS,
+            functions: HashMap::new(),
+            depth: 0,
+            outcome: None,
+            scheduler: Arc::default(),
+            clock: Arc::new(Mutex::new(SystemClock::default())),
+            task: None,
+        }
+    }
+
+    pub fn environment(&self) -> &Environment {
+        &self.environment
+    }
+
+    pub fn environment_mut(&mut self) -> &mut Environment {
+        &mut self.environment
+    }
+
+    /// Schemas beyond the built-ins are defined here before the spell runs.
+    pub fn schemas_mut(&mut self) -> &mut Schemas {
+        &mut self.schemas
+    }
+
+    /// Caps the passes of every loop; [`flow::MAX_ITERATIONS`] by default.
+    pub fn set_max_iterations(&mut self, limit: usize) {
+        self.max_iterations = limit;
+    }
+
+    /// The clock `wait` pauses on; a [`FakeClock`] makes waits instant.
+    pub fn set_clock(&mut self, clock: impl Clock + 'static) {
+        self.clock = Arc::new(Mutex::new(clock));
+    }
+
+    // A task that panicked poisons the locks it held; the spell carries on with them.
+    fn console(&self) -> MutexGuard<'_, dyn Console + 'static> {
+        self.console.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn clock(&self) -> MutexGuard<'_, dyn Clock + 'static> {
+        self.clock.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Runs every statement of the program in order, stopping at the first failure
+    /// or at `success` or `fail`. Functions may be called before their definition.
+    ///
+    /// A spell that reaches its end without `success` or `fail` is unfinished,
+    /// which counts as a failure. Tasks still running end before it does; after a
+    /// failure they are stopped.
+    pub fn run(&mut self, program: &Program) -> Result<Outcome, Diagnostic> {
+        self.outcome = None;
+        self.scheduler = Arc::default();
+        self.forget_failure();
+        let result = self
+            .hoist(&program.statements)
+            .and_then(|()| self.statements(&program.statements))
+            .and_then(|()| self.finish_tasks());
+        self.abandon_tasks();
+        result?;
+        Ok(self.outcome.take().unwrap_or_else(Outcome::unfinished))
+    }
+
+    /// Runs `statements` until one fails or ends the spell or function.
+    pub fn statements(&mut self, statements: &[Statement]) -> Result<(), Diagnostic> {
+        for statement in statements {
+            self.statement(statement)?;
+            if self.outcome.is_some() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs `statements` in a scope of their own, closed even when one fails.
+    pub fn block(&mut self, statements: &[Statement]) -> Result<(), Diagnostic> {
+        self.environment.push_block();
+        let result = self.statements(statements);
+        self.environment.pop();
+        result
+    }
+
+    /// Runs one statement; under `safe`, a failure is recorded and the spell goes on.
+    pub fn statement(&mut self, statement: &Statement) -> Result<(), Diagnostic> {
+        let result = match statement {
+            Statement::Command(command) => self.command(command),
+            Statement::Container(container) => self.container(container),
+            Statement::Assignment(assignment) => self.assignment(assignment),
+            Statement::Calculation(calculation) => self.calculation(calculation),
+            Statement::Decision(decision) => self.decision(decision),
+            Statement::Conditional(conditional) => self.conditional(conditional),
+            Statement::Loop(repetition) => self.repetition(repetition),
+            // Defined before the spell starts, by `hoist`.
+            Statement::Function(_) => Ok(()),
+            Statement::Error(span) => Err(Diagnostic::error(
+                BROKEN_STATEMENT,
+                "Cannot run a statement that failed to parse",
+            )
+            .with_span(*span)
+            .with_help("Fix the syntax errors reported for this spell first.")),
+        };
+        self.recover(statement, result)
+    }
+
+    fn command(&mut self, command: &Command) -> Result<(), Diagnostic> {
+        // Commands bound to another platform are skipped, not failed.
+        if let Some(platform) = &command.platform
+            && Some(platform.node) != Platform::current()
+        {
+            return Ok(());
+        }
+        if let Some(tag) = task::tag(&command.clauses) {
+            self.launch(command, tag);
+            return Ok(());
+        }
+
+        let Some((value, sensitive)) = self.produce(command)? else {
+            return match command
+                .sink
+                .as_ref()
+                .map(|sink| sink.span)
+                .or(command.schema.as_ref().map(|schema| schema.span))
+            {
+                Some(span) => Err(Diagnostic::error(
+                    sink::NO_VALUE,
+                    format!("'{}' gives no value to check or store", command.name.node),
+                )
+                .with_span(span)
+                .with_label(command.name.span, "produces nothing")),
+                None => Ok(()),
+            };
+        };
+        self.store(
+            command.schema.as_ref(),
+            command.sink.as_ref(),
+            value,
+            sensitive,
+        )
+    }
+
+    /// Runs `command` and gives its value, if it has one, and whether the value is
+    /// sensitive.
+    fn produce(&mut self, command: &Command) -> Result<Option<(Value, bool)>, Diagnostic> {
+        // Only `wait` and `stop` read bare words; elsewhere they would vanish.
+        if command.kind == CommandKind::Intrinsic
+            && !matches!(command.name.as_str(), "wait" | "stop")
+            && let Some(word) = command.actions.first()
+        {
+            return Err(Diagnostic::error(
+                BARE_WORD,
+                format!(
+                    "'{}' does not take the bare word '{}'",
+                    command.name.node, word.node
+                ),
+            )
+            .with_span(word.span)
+            .with_label(word.span, "not quoted")
+            .with_help(format!(
+                "Quote it as text: {} '{}'.",
+                command.name.node, word.node
+            )));
+        }
+        let allow_sensitive = command.has_clause(&Clause::SensitiveEgress);
+        let mut returned_sensitive = false;
+        let result = match (command.kind, command.name.as_str()) {
+            (CommandKind::Intrinsic, "say") => {
+                let text = self.text(&command.instructions, allow_sensitive)?;
+                self.console().say(&text);
+                None
+            }
+            (CommandKind::Intrinsic, "ask") => {
+                let prompt = self.text(&command.instructions, allow_sensitive)?;
+                let answer = self.console().ask(&prompt).ok_or_else(|| {
+                    Diagnostic::error(INPUT_CLOSED, "No answer: input is closed")
+                        .with_span(command.name.span)
+                        .with_label(command.name.span, "asked here")
+                })?;
+                Some(Value::Text(answer))
+            }
+            (CommandKind::Intrinsic, "success" | "fail") => {
+                let value = self.values(&command.instructions)?;
+                self.outcome = Some(Outcome {
+                    status: match command.name.node.as_str() {
+                        "success" => Status::Success,
+                        _ => Status::Fail,
+                    },
+                    value,
+                    sensitive: command.has_clause(&Clause::Sensitive)
+                        || self.tainted(nodes(&command.instructions)),
+                });
+                None
+            }
+            (CommandKind::Intrinsic, "parameter") => {
+                return Err(Diagnostic::error(
+                    function::PARAMETER,
+                    "'parameter' only declares the inputs of a function",
+                )
+                .with_span(command.name.span)
+                .with_help("Move it to the top level of a function body."));
+            }
+            (CommandKind::Intrinsic, "wait") => {
+                let (value, sensitive) = match self.wait(command)? {
+                    Some(reports) => reports,
+                    None => return Ok(None),
+                };
+                returned_sensitive = sensitive;
+                Some(value)
+            }
+            (CommandKind::Intrinsic, "stop") => {
+                self.stop(command)?;
+                None
+            }
+            (CommandKind::Module, name) if self.functions.contains_key(name) => {
+                let (value, sensitive) = self.call(command)?;
+                returned_sensitive = sensitive;
+                Some(value)
+            }
+            (CommandKind::Module, _) => return Err(function::not_found(&command.name)),
+            _ => {
+                return Err(Diagnostic::error(
+                    UNSUPPORTED,
+                    format!("Command '{}' is not supported yet", command.name.node),
+                )
+                .with_span(command.name.span));
+            }
+        };
+
+        Ok(result.map(|value| {
+            let sensitive = returned_sensitive
+                || command.has_clause(&Clause::Sensitive)
+                || self.tainted(nodes(&command.instructions));
+            (value, sensitive)
+        }))
+    }
+
+    fn assignment(&mut self, assignment: &Assignment) -> Result<(), Diagnostic> {
+        if let Some(platform) = &assignment.platform
+            && Some(platform.node) != Platform::current()
+        {
+            return Ok(());
+        }
+
+        // Storing a value is not egress, so sensitive values may go in. Literals are
+        // typed for containers; a record sink turns them back into text.
+        let mut value = self.values(&assignment.values)?;
+        if let Some(lens) = &assignment.lens {
+            value = value.lens(lens.node, lens.span)?;
+        }
+        let sensitive =
+            assignment.has_clause(&Clause::Sensitive) || self.tainted(nodes(&assignment.values));
+        self.store(
+            assignment.schema.as_ref(),
+            assignment.sink.as_ref(),
+            value,
+            sensitive,
+        )
+    }
+
+    fn calculation(&mut self, calculation: &Calculation) -> Result<(), Diagnostic> {
+        if let Some(platform) = &calculation.platform
+            && Some(platform.node) != Platform::current()
+        {
+            return Ok(());
+        }
+
+        let operand = |instruction: &Instruction, span| {
+            self.evaluate(&Spanned::new(instruction.clone(), span), true)
+        };
+        let result = calculate::evaluate(&calculation.expression, &operand)?;
+        let sensitive = calculation.has_clause(&Clause::Sensitive)
+            || self.tainted(calculation.expression.node.operands());
+        self.store(
+            None,
+            calculation.sink.as_ref(),
+            result.to_value(),
+            sensitive,
+        )
+    }
+
+    fn decision(&mut self, decision: &Decision) -> Result<(), Diagnostic> {
+        if let Some(platform) = &decision.platform
+            && Some(platform.node) != Platform::current()
+        {
+            return Ok(());
+        }
+
+        let operand = |instruction: &Instruction, span| self.operand(instruction, span);
+        let holds = decide::evaluate(&decision.condition, &operand, &self.schemas, &self.patterns)?;
+        let sensitive = decision.has_clause(&Clause::Sensitive)
+            || self.tainted(decision.condition.node.operands());
+        self.store(
+            None,
+            decision.sink.as_ref(),
+            Value::Boolean(holds),
+            sensitive,
+        )
+    }
+
+    fn conditional(&mut self, conditional: &Conditional) -> Result<(), Diagnostic> {
+        if self.holds(&conditional.guard.node)? {
+            self.block(&conditional.then)
+        } else if let Some(otherwise) = &conditional.otherwise {
+            self.block(otherwise)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn repetition(&mut self, repetition: &Loop) -> Result<(), Diagnostic> {
+        let mut passes = 0;
+        match &repetition.kind {
+            LoopKind::Repeat(count) => {
+                let value = self.operand(&count.node, count.span)?;
+                for _ in 0..flow::repetitions(&value, count.span)? {
+                    if !self.pass(repetition, &mut passes, None)? {
+                        break;
+                    }
+                }
+            }
+            LoopKind::While(guard) => {
+                while self.holds(&guard.node)? {
+                    if !self.pass(repetition, &mut passes, None)? {
+                        break;
+                    }
+                }
+            }
+            LoopKind::Count {
+                direction,
+                counter,
+                step,
+                limit,
+            } => {
+                let step = flow::step(&self.operand(&step.node, step.span)?, step.span)?;
+                let limit =
+                    flow::number(&self.operand(&limit.node, limit.span)?, "limit", limit.span)?;
+                loop {
+                    // Read again each pass: the block may change the counter too.
+                    let value = self.environment.value(&counter.node, counter.span)?;
+                    let current = flow::number(value, "counter", counter.span)?;
+                    let next = match direction {
+                        Direction::Increase if current <= limit => current.plus(step),
+                        Direction::Decrease if current >= limit => current.minus(step),
+                        _ => break,
+                    };
+                    if !self.pass(repetition, &mut passes, None)? {
+                        break;
+                    }
+                    let next = next.map_err(|_| {
+                        Diagnostic::error(
+                            flow::COUNT,
+                            format!("'{}' grew too large to count", counter.node),
+                        )
+                        .with_span(counter.span)
+                    })?;
+                    self.environment.bind(
+                        &counter.node,
+                        Value::Text(next.to_string()),
+                        counter.span,
+                    )?;
+                }
+            }
+            LoopKind::Foreach { source, item } => {
+                let value = self.environment.value(&source.node, source.span)?;
+                let sensitive = self.tainted([&Instruction::Reference(source.node.clone())]);
+                for element in flow::items(value, source, item)? {
+                    let mut binding = Binding::new(element, &item.node);
+                    binding.sensitive = sensitive;
+                    if !self.pass(repetition, &mut passes, Some((&item.node, binding)))? {
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// One run of a loop's block, in a scope of its own that holds `item` if given.
+    /// False once `success` or `fail` ended the spell or function.
+    fn pass(
+        &mut self,
+        repetition: &Loop,
+        passes: &mut usize,
+        item: Option<(&Reference, Binding)>,
+    ) -> Result<bool, Diagnostic> {
+        if *passes >= self.max_iterations {
+            let keyword = repetition.kind.keyword();
+            let span = Span {
+                end: repetition.span.start + keyword.len(),
+                ..repetition.span
+            };
+            return Err(flow::limit_reached(keyword, self.max_iterations, span));
+        }
+        *passes += 1;
+
+        self.environment.push_block();
+        if let Some((reference, binding)) = item {
+            self.environment.define(reference, binding);
+        }
+        let result = self.statements(&repetition.body);
+        self.environment.pop();
+        result.map(|()| self.outcome.is_none())
+    }
+
+    /// Whether a block guard lets its block run.
+    fn holds(&self, guard: &Guard) -> Result<bool, Diagnostic> {
+        let operand = |instruction: &Instruction, span| self.operand(instruction, span);
+        flow::holds(guard, &operand, &self.schemas, &self.patterns)
+    }
+
+    /// The value of a `decide` operand: literals are typed, references looked up.
+    fn operand(&self, instruction: &Instruction, span: Span) -> Result<Value, Diagnostic> {
+        match container::typed(instruction) {
+            Some(value) => Ok(value),
+            None => self.evaluate(&Spanned::new(instruction.clone(), span), true),
+        }
+    }
+
+    fn container(&mut self, block: &Container) -> Result<(), Diagnostic> {
+        let value = container::build(&block.fields, &|instruction| {
+            self.evaluate(instruction, true)
+        })?;
+        self.sink(&block.sink, value, false)
+    }
+
+    /// Checks `value` against its schema, then hands it to its sink; a value that
+    /// fails the check is never stored.
+    fn store(
+        &mut self,
+        schema: Option<&Spanned<String>>,
+        sink: Option<&Sink>,
+        value: Value,
+        sensitive: bool,
+    ) -> Result<(), Diagnostic> {
+        if let Some(schema) = schema {
+            self.schemas.check(schema, &value)?;
+        }
+        match sink {
+            Some(sink) => self.sink(sink, value, sensitive),
+            None => Ok(()),
+        }
+    }
+
+    /// What a list of values stands for: null when empty, the value itself when
+    /// alone and an array otherwise. Literals are typed, as in containers.
+    fn values(&self, instructions: &[Spanned<Instruction>]) -> Result<Value, Diagnostic> {
+        let mut values = instructions
+            .iter()
+            .map(|instruction| match container::typed(&instruction.node) {
+                Some(value) => Ok(value),
+                None => self.evaluate(instruction, true),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(match values.len() {
+            0 => Value::Null,
+            1 => values.remove(0),
+            _ => Value::List(values),
+        })
+    }
+
+    /// The instructions evaluated and joined, as `say` and `ask` show them.
+    fn text(
+        &self,
+        instructions: &[Spanned<Instruction>],
+        allow_sensitive: bool,
+    ) -> Result<String, Diagnostic> {
+        let mut text = String::new();
+        for instruction in instructions {
+            text.push_str(&self.evaluate(instruction, allow_sensitive)?.to_string());
+        }
+        Ok(text)
+    }
+
+    /// Whether any instruction reads a sensitive binding, directly or interpolated.
+    fn tainted<'a>(&self, instructions: impl IntoIterator<Item = &'a Instruction>) -> bool {
+        let sensitive = |reference: &Reference| {
+            self.environment
+                .lookup(reference)
+                .is_some_and(|binding| binding.sensitive)
+        };
+        instructions.into_iter().any(|instruction| {
+            match instruction {
+            Instruction::Reference(reference) => sensitive(reference),
+            Instruction::Template(template) => template.segments.iter().any(|segment| {
+                matches!(segment, Segment::Interpolation(reference) if sensitive(reference))
+            }),
+            _ => false,
+        }
+        })
+    }
+
+    /// The value of an instruction: literals as text, references looked up with
+    /// their projections, templates interpolated.
+    pub fn evaluate(
+        &self,
+        instruction: &Spanned<Instruction>,
+        allow_sensitive: bool,
+    ) -> Result<Value, Diagnostic> {
+        match &instruction.node {
+            Instruction::Template(template) => {
+                interpolate(template, &self.environment, allow_sensitive).map(Value::Text)
+            }
+            Instruction::Reference(reference) => {
+                let value = self.environment.value(reference, instruction.span)?;
+                let sensitive = self
+                    .environment
+                    .lookup(reference)
+                    .is_some_and(|binding| binding.sensitive);
+                if sensitive && !allow_sensitive {
+                    return Err(interpolation::sensitive(reference, instruction.span));
+                }
+                Ok(value.clone())
+            }
+            Instruction::Null => Ok(Value::Null),
+            other => Ok(Value::Text(other.text().into_owned())),
+        }
+    }
+}
+
+fn nodes(instructions: &[Spanned<Instruction>]) -> impl Iterator<Item = &Instruction> {
+    instructions.iter().map(|instruction| &instruction.node)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, schema};
+
+    fn run(source: &str) -> (Result<Outcome, Diagnostic>, Vec<String>) {
+        let console = BufferedConsole::new();
+        let mut interpreter = Interpreter::with_console(console.clone());
+        let result = interpreter.run(&parse(source).expect("Parse failed"));
+        (result, console.output())
+    }
+
+    #[test]
+    fn test_say_reads_containers() {
+        let (result, output) = run(
+            "container\n  :name 'Alex'\n  :tags 'a' 'b'\n  :profile\n    \
+             :city 'Lyon'\ninto ::!user.\n\
+             say 'Hi ' ::user:name ' from @{::!user:profile:city}'.\n\
+             say ::user:tags#1 ::user:tags.",
+        );
+        result.unwrap();
+        assert_eq!(output, ["Hi Alex from Lyon", "b['a', 'b']"]);
+    }
+
+    #[test]
+    fn test_bare_words_are_not_text() {
+        for source in ["say hello.", "say hello 'x'.", "success done."] {
+            let (result, output) = run(source);
+            assert_eq!(result.unwrap_err().code, BARE_WORD, "{source}");
+            assert!(output.is_empty(), "{source}");
+        }
+    }
+
+    #[test]
+    fn test_calculate_stores_exact_numbers() {
+        let (result, output) = run("'4' into @four.\n\
+             calculate (2 + 3) * @four into @x.\n\
+             calculate 0.1 + 0.2 into ::sum.\n\
+             calculate 7 / 2.\n\
+             say @x ' ' ::sum.");
+        result.unwrap();
+        assert_eq!(output, ["20 0.3"]);
+
+        let (result, output) = run("'0' into @zero.\ncalculate 1 / @zero into @x.\nsay @x.");
+        assert_eq!(result.unwrap_err().code, calculate::DIVZERO);
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn test_decide_validates_input() {
+        let source = "ask 'Name:' into @!username.
+             ask 'Email:' into @!email.
+             decide @!username equals '' into @!username_is_empty.
+             decide @!email equals '' or @!email is missing '@' into @!email_is_invalid.
+             say @!username_is_empty ' ' @!email_is_invalid.";
+        for (answers, shown) in [
+            (["Ada", "ada@example.com"], "false false"),
+            (["", "ada.example.com"], "true true"),
+        ] {
+            let console = BufferedConsole::with_answers(answers);
+            let mut interpreter = Interpreter::with_console(console.clone());
+            interpreter.run(&parse(source).unwrap()).unwrap();
+            assert_eq!(console.output().last().map(String::as_str), Some(shown));
+        }
+    }
+
+    #[test]
+    fn test_spells_end_with_an_outcome() {
+        for (source, status, value) in [
+            ("say 'hi'.", Status::Unfinished, Value::Null),
+            ("success.\nsay 'never'.", Status::Success, Value::Null),
+            (
+                "repeat 3\n  fail 'stop'.\nend\nsuccess.",
+                Status::Fail,
+                Value::from("stop"),
+            ),
+            (
+                "'a' 'b' into ::list.\nsuccess ::list.",
+                Status::Success,
+                Value::List(vec![Value::from("a"), Value::from("b")]),
+            ),
+        ] {
+            let outcome = run(source).0.unwrap();
+            assert_eq!((outcome.status, outcome.value), (status, value), "{source}");
+        }
+
+        let (result, _) = run("sensitive 'hunter2' into @key.\nsuccess @key.");
+        assert!(result.unwrap().sensitive);
+    }
+
+    #[test]
+    fn test_example_spell() {
+        let source = include_str!("../../../../examples/delete_temporary_files.spell");
+        let program = parse(source).unwrap();
+        assert!(crate::analyze(&program).is_empty());
+        for (answers, status) in [
+            (["Ada", "ada@example.com", "36"], Status::Success),
+            (["Ada", "ada.example.com", "36"], Status::Fail),
+        ] {
+            let console = BufferedConsole::with_answers(answers);
+            let mut interpreter = Interpreter::with_console(console.clone());
+            assert_eq!(interpreter.run(&program).unwrap().status, status);
+        }
+    }
+
+    #[test]
+    fn test_if_blocks_choose_a_branch() {
+        let (result, output) = run("'30' into @age.
+             decide @age > 18 into @adult.
+             false into @blocked.
+             if @adult
+               'inner' into @scoped.
+               if none @blocked @blocked
+                 say 'open'.
+               end
+             else
+               say 'minor'.
+             end
+             if decide @age < 18 or @blocked
+               say 'never'.
+             else
+               say 'done'.
+             end
+             say @scoped.");
+        assert_eq!(result.unwrap_err().code, interpolation::UNBOUND);
+        assert_eq!(output, ["open", "done"]);
+
+        let (result, output) = run("'yes' into @flag.\nif @flag\n  say 'never'.\nend");
+        assert_eq!(result.unwrap_err().code, flow::NONBOOL);
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn test_loops() {
+        let (result, output) = run("repeat 2
+               say 'again'.
+             end
+             '0' into @i.
+             increase @i by '2' to '5'
+               say 'up @{i}'.
+             end
+             decrease @i by 3 to 0
+               say 'down @{i}'.
+             end
+             'a' 'b' into ::names.
+             foreach from ::names into @name
+               say 'hi @{name}'.
+             end
+             true into @more.
+             while @more
+               calculate @i + 1 into @i.
+               decide @i < 3 into @more.
+             end
+             say '@{i}'.
+             say @name.");
+        assert_eq!(result.unwrap_err().code, interpolation::UNBOUND);
+        assert_eq!(
+            output,
+            [
+                "again", "again", "up 0", "up 2", "up 4", "down 6", "down 3", "down 0", "hi a",
+                "hi b", "3"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_loop_limits() {
+        let console = BufferedConsole::new();
+        let mut interpreter = Interpreter::with_console(console.clone());
+        interpreter.set_max_iterations(3);
+        let program = parse("true into @on.\nwhile @on\n  say 'tick'.\nend").unwrap();
+        let error = interpreter.run(&program).unwrap_err();
+        assert_eq!(error.code, flow::LIMIT);
+        assert_eq!(console.output(), ["tick", "tick", "tick"]);
+
+        for (source, code) in [
+            ("repeat '-1'\nend", flow::COUNT),
+            ("'0' into @i.\nincrease @i by 0 to 3\nend", flow::COUNT),
+            (
+                "'a' into ::one.\nforeach from ::one into @item\nend",
+                flow::FOREACH,
+            ),
+        ] {
+            assert_eq!(run(source).0.unwrap_err().code, code, "{source}");
+        }
+    }
+
+    #[test]
+    fn test_functions() {
+        let (result, output) = run("half 10 into @half.
+             say @half.
+             describe 'y'.
+             repeat 1
+               'local' into @caller.
+               describe 'x' with verbose.
+             end
+             function half
+               parameter 1 into @n.
+               repeat 3
+                 calculate @n / 2 into @result.
+                 success @result.
+               end
+             end
+             function describe
+               parameter 1 into @!item.
+               parameter verbose into @verbose.
+               if decide @verbose is null
+                 say @item.
+                 success.
+               end
+               say 'item @{item}'.
+               say @caller.
+               success.
+             end");
+        assert_eq!(result.unwrap_err().code, interpolation::UNBOUND);
+        assert_eq!(output, ["5", "y", "item x"]);
+
+        for (source, code) in [
+            (
+                "broken.\nfunction broken\n  fail 'no disk'.\nend",
+                function::FAILED,
+            ),
+            (
+                "silent.\nfunction silent\n  say 'hi'.\nend",
+                function::NO_SIGNAL,
+            ),
+            (
+                "deep.\nfunction deep\n  deep.\n  success.\nend",
+                function::RECURSION,
+            ),
+            (
+                "function f\n  success.\nend\nfunction f\n  success.\nend",
+                function::DUPLICATE,
+            ),
+            ("parameter 1 into @a.", function::PARAMETER),
+        ] {
+            assert_eq!(run(source).0.unwrap_err().code, code, "{source}");
+        }
+    }
+
+    #[test]
+    fn test_runtime_errors_stop_the_spell() {
+        for (source, code) in [
+            (
+                "say 'before'.\nsay ::nothing.\nsay 'after'.",
+                container::UNDEFINED,
+            ),
+            (
+                "say 'before'.\nsay @nothing.\nsay 'after'.",
+                interpolation::UNBOUND,
+            ),
+            (
+                "say 'before'.\ncontainer :a 'x' into ::!c.\ncontainer :a 'y' into ::c.",
+                container::SEALED,
+            ),
+            ("say 'before'.\nfrobnicate.", function::NOTFOUND),
+        ] {
+            let (result, output) = run(source);
+            assert_eq!(result.unwrap_err().code, code, "{source}");
+            assert_eq!(output, ["before"]);
+        }
+    }
+
+    #[test]
+    fn test_sinks_bind_update_and_seal() {
+        let console = BufferedConsole::with_answers(["Ada"]);
+        let mut interpreter = Interpreter::with_console(console.clone());
+        let program = parse(
+            "ask 'Name?' into @name.
+             'x' into @name.
+             'Alex' update @name.
+             null into @?maybe.
+             42 save @answer.
+             'localhost' into ::config:database:host.
+             5432 into ::config:database:port.
+             '5.5' as decimal into ::size.
+             'a' 'b' into !::letters.
+             '7' is &integer into @count.
+             say @name ' ' @answer ' ' ::config ' ' ::size ' ' ::letters ' ' @count.",
+        )
+        .unwrap();
+        interpreter.run(&program).unwrap();
+        assert_eq!(
+            console.output(),
+            ["Alex 42 {database: {host: 'localhost', port: 5432}} 5.5 ['a', 'b'] 7"]
+        );
+
+        let environment = interpreter.environment();
+        let binding = |source: &str| {
+            let Statement::Command(command) =
+                &parse(&format!("say {source}.")).unwrap().statements[0]
+            else {
+                panic!("Expected a command");
+            };
+            let Instruction::Reference(reference) = &command.instructions[0].node else {
+                panic!("Expected a reference");
+            };
+            environment.lookup(reference).unwrap().clone()
+        };
+        assert!(binding("@answer").sealed);
+        assert!(binding("::letters").sealed);
+        assert!(binding("@maybe").nullable);
+        assert_eq!(binding("@answer").value, Value::from("42"));
+    }
+
+    #[test]
+    fn test_sink_errors_leave_targets_unchanged() {
+        for (source, code) in [
+            (
+                "'a' into @!x.
+'b' into @!x.",
+                sink::DOUBLE_SEAL,
+            ),
+            (
+                "'a' into @!x.
+'b' save @x.",
+                sink::DOUBLE_SEAL,
+            ),
+            (
+                "'a' into @!x.
+'b' into @x.",
+                RECORD_SEALED,
+            ),
+            (
+                "'host' into !::config:database:host.",
+                sink::SEAL_PROJECTION,
+            ),
+            (
+                "container :a 'x' into ::!c.
+'y' into ::c:a.",
+                container::SEALED,
+            ),
+            (
+                "container :a 'x' into ::!c.
+'y' into ::!c:a.",
+                container::SEALED,
+            ),
+            ("'a' update @missing.", sink::UPDATE_UNBOUND),
+            (
+                "container :a 'x' into ::c.
+'y' update ::c:b.",
+                sink::UPDATE_UNBOUND,
+            ),
+            ("null into @x.", sink::NULL),
+            ("'a' 'b' into @x.", sink::RECORD_SCALAR),
+            ("say 'hi' into @x.", sink::NO_VALUE),
+            ("'abc' is &integer into @x.", schema::MISMATCH),
+            ("'abc' is &Unknown into @x.", schema::UNKNOWN),
+            ("'x' into ::error.", sink::RESERVED),
+        ] {
+            let mut interpreter = Interpreter::with_console(BufferedConsole::new());
+            let error = interpreter.run(&parse(source).unwrap()).unwrap_err();
+            assert_eq!(error.code, code, "{source}");
+            if !source.contains('\n') {
+                let unset = parse("say @x.").unwrap();
+                assert!(interpreter.run(&unset).is_err(), "{source}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_block_scope_is_closed_after_errors() {
+        let mut interpreter = Interpreter::with_console(BufferedConsole::new());
+        let program = parse("container :a 'x' into ::inner.\nfrobnicate.").unwrap();
+        assert!(interpreter.block(&program.statements).is_err());
+        assert_eq!(interpreter.environment().depth(), 1);
+        let program = parse("say ::inner.").unwrap();
+        assert_eq!(
+            interpreter.run(&program).unwrap_err().code,
+            container::UNDEFINED
+        );
+    }
+
+    #[test]
+    fn test_safe_statements_record_the_failure() {
+        let (result, output) = run("decide ::error is null into @clean.
+             say @clean.
+             safe missing 'disk'.
+             say ::error:code.
+             safe 'abc' is &integer into @count.
+             say ::error:line.
+             safe broken.
+             say ::error:message.
+             success.
+             function broken
+               fail 'no disk'.
+             end");
+        assert!(result.unwrap().is_success());
+        assert_eq!(
+            output,
+            [
+                "true",
+                "[WARN][FAIL] Function 'missing' not found.",
+                "E-FUNC-NOTFOUND",
+                output[3].as_str(),
+                "5",
+                "[WARN][FAIL] 'broken' failed: no disk.",
+                "'broken' failed: no disk",
+            ]
+        );
+        assert!(output[3].starts_with("[WARN][FAIL] "));
+
+        let (result, _) = run("safe 'abc' is &integer into @count.\nsay @count.");
+        assert_eq!(result.unwrap_err().code, interpolation::UNBOUND);
+    }
+
+    #[test]
+    fn test_async_tasks_on_a_fake_clock() {
+        let run = |source: &str| {
+            let console = BufferedConsole::new();
+            let clock = FakeClock::new();
+            let mut interpreter = Interpreter::with_console(console.clone());
+            interpreter.set_clock(clock.clone());
+            let result = interpreter.run(&parse(source).expect("Parse failed"));
+            (result, console.output(), clock.now())
+        };
+        let tasks = "function world
+               wait 5 seconds.
+               say 'world'.
+               success 'done'.
+             end
+             function fetch
+               parameter 1 into @mirror.
+               parameter 2 into @seconds.
+               wait @seconds seconds.
+               say 'fetched @{mirror}'.
+               success @mirror.
+             end
+             function broken
+               fail 'no disk'.
+             end\n";
+
+        let (result, output, elapsed) = run(&format!(
+            "{tasks}'before' into @x.
+             async 'world' world.
+             async 'echo' say @x.
+             'after' into @x.
+             say 'hello'.
+             wait 'world' into ::results.
+             say ::results#0:status ' ' ::results#0:value ' ' ::results#0:elapsed.
+             async 'mirror' fetch 'slow' 3.
+             async 'mirror' fetch 'fast' 1.
+             wait any 'mirror' into ::first.
+             stop 'mirror'.
+             wait 'mirror' into ::mirrors.
+             say ::first:value ' ' ::mirrors#0:status.
+             success."
+        ));
+        assert!(result.unwrap().is_success());
+        assert_eq!(
+            output,
+            [
+                "hello",
+                // The echo runs while the world task waits.
+                "before",
+                "world",
+                "success done 5000",
+                // The slow mirror is stopped while it waits.
+                "fetched fast",
+                "fast cancelled",
+            ]
+        );
+        assert_eq!(elapsed, std::time::Duration::from_secs(6));
+
+        // Pauses overlap: three tasks of a second each take a second together.
+        let (result, output, elapsed) = run(&format!(
+            "{tasks}async 'w' fetch 'a' 1.
+             async 'w' fetch 'b' 1.
+             async 'w' fetch 'c' 1.
+             wait all.
+             success."
+        ));
+        assert!(result.unwrap().is_success());
+        assert_eq!(output, ["fetched a", "fetched b", "fetched c"]);
+        assert_eq!(elapsed, std::time::Duration::from_secs(1));
+
+        // The spell's own pause lets tasks run, and they end before it does.
+        let (result, output, elapsed) = run(&format!(
+            "{tasks}async fetch 'late' 4.
+             async fetch 'early' 1.
+             wait 2 seconds.
+             say 'spell'.
+             success."
+        ));
+        assert!(result.unwrap().is_success());
+        assert_eq!(output, ["fetched early", "spell", "fetched late"]);
+        assert_eq!(elapsed, std::time::Duration::from_secs(4));
+
+        let (result, output, _) = run(&format!(
+            "{tasks}safe async 'job' broken.
+             wait 'job' into ::jobs.
+             say ::jobs#0:status ' ' ::error:code.
+             async 'job' broken.
+             wait 'job'."
+        ));
+        assert_eq!(result.unwrap_err().code, function::FAILED);
+        assert_eq!(
+            output,
+            [
+                "[WARN][FAIL] 'broken' failed: no disk.",
+                "failed E-FUNC-FAILED"
+            ]
+        );
+
+        for (source, code) in [
+            ("async broken.\nsuccess.", function::FAILED),
+            ("wait 'nope'.", task::TAG),
+            ("async 'w' selfish.\nwait 'w'.", task::SELF_WAIT),
+            (
+                "async 'a' waits 'b'.\nasync 'b' waits 'a'.\nwait all.",
+                task::SELF_WAIT,
+            ),
+            ("wait 5 weeks.", task::DURATION),
+        ] {
+            let source = format!(
+                "{tasks}function selfish\n  wait 'w'.\n  success.\nend\n\
+                 function waits\n  parameter 1 into @tag.\n  wait @tag.\n  success.\nend\n{source}"
+            );
+            assert_eq!(run(&source).0.unwrap_err().code, code, "{source}");
+        }
+    }
+
+    #[test]
+    fn test_a_task_that_panics_fails() {
+        /// Panics on 'crash' and keeps every other line.
+        #[derive(Clone, Default)]
+        struct Crashing(BufferedConsole);
+
+        impl Console for Crashing {
+            fn say(&mut self, text: &str) {
+                assert_ne!(text, "crash", "the console crashed");
+                self.0.say(text);
+            }
+
+            fn ask(&mut self, prompt: &str) -> Option<String> {
+                self.0.ask(prompt)
+            }
+        }
+
+        let console = Crashing::default();
+        let mut interpreter = Interpreter::with_console(console.clone());
+        interpreter.set_clock(FakeClock::new());
+        let source = "safe async 'x' say 'crash'.
+             wait 'x' into ::x.
+             say ::x#0:status ' ' ::error:code.
+             async 'y' say 'crash'.
+             wait 'y'.";
+        let result = interpreter.run(&parse(source).expect("Parse failed"));
+        assert_eq!(result.unwrap_err().code, task::PANICKED);
+        assert_eq!(console.0.output()[1], "failed E-ASYNC-PANICKED");
+    }
+}
@@ -0,0 +1,137 @@
+//! How a spell or function ends, and the exit codes `neko conjure` reports it with.
+//!
+//! A spell ends with `success [value]` or `fail [value]`; one that reaches its end
+//! without either has failed, with no result defined.
+
+use crate::json;
+use crate::value::Value;
+
+/// `success`: the spell did what it set out to do.
+pub const EXIT_SUCCESS: i32 = 0;
+/// `fail`: the spell decided it could not.
+pub const EXIT_FAIL: i32 = 1;
+/// The spell reached its end without `success` or `fail`.
+pub const EXIT_UNFINISHED: i32 = 2;
+/// A runtime error stopped the spell.
+pub const EXIT_ERROR: i32 = 3;
+/// Syntax errors or the checks before conjuring kept the spell from starting.
+pub const EXIT_REJECTED: i32 = 4;
+/// The command line was wrong, e.g. no spell given or the file is unreadable.
+pub const EXIT_USAGE: i32 = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Success,
+    Fail,
+    /// Reached the end without `success` or `fail`, which counts as a failure.
+    Unfinished,
+}
+
+impl Status {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Status::Success => "success",
+            Status::Fail => "fail",
+            Status::Unfinished => "unfinished",
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Status::Success => EXIT_SUCCESS,
+            Status::Fail => EXIT_FAIL,
+            Status::Unfinished => EXIT_UNFINISHED,
+        }
+    }
+}
+
+/// How a spell or function ended, with the value `success` or `fail` gave.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    pub status: Status,
+    pub value: Value,
+    /// The value was read from a sensitive binding, so it is never shown.
+    pub sensitive: bool,
+}
+
+impl Outcome {
+    /// The outcome of a spell that never ran `success` or `fail`.
+    pub fn unfinished() -> Self {
+        Outcome {
+            status: Status::Unfinished,
+            value: Value::Null,
+            sensitive: false,
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.status == Status::Success
+    }
+
+    /// The closing line of a spell, e.g. `[SUCC] The script has been successful.`
+    pub fn summary(&self) -> String {
+        let result = match (&self.value, self.sensitive) {
+            (Value::Null, _) => String::new(),
+            (_, true) => ", Result: <sensitive>".to_string(),
+            (Value::Text(text), _) => format!(", Result: '{}'", text),
+            (value, _) => format!(", Result: {}", value),
+        };
+        match self.status {
+            Status::Success => format!("[SUCC] The script has been successful{}.", result),
+            Status::Fail => format!("[ERRO] The script has failed{}.", result),
+            Status::Unfinished => "[ERRO] The script has failed, no result defined.".to_string(),
+        }
+    }
+
+    /// The outcome as one JSON object, for tools that run spells.
+    pub fn to_json(&self) -> String {
+        json::object([
+            ("status", json::quote(self.status.as_str())),
+            ("exit_code", self.status.exit_code().to_string()),
+            (
+                "value",
+                match self.sensitive {
+                    true => "null".to_string(),
+                    false => self.value.to_json(),
+                },
+            ),
+            ("sensitive", self.sensitive.to_string()),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_and_json() {
+        let outcome = |status, value, sensitive| Outcome {
+            status,
+            value,
+            sensitive,
+        };
+        let done = outcome(Status::Success, Value::from("true"), false);
+        assert_eq!(
+            done.summary(),
+            "[SUCC] The script has been successful, Result: 'true'."
+        );
+        assert_eq!(
+            done.to_json(),
+            r#"{"status":"success","exit_code":0,"value":"true","sensitive":false}"#
+        );
+
+        let secret = outcome(Status::Fail, Value::from("hunter2"), true);
+        assert_eq!(
+            secret.summary(),
+            "[ERRO] The script has failed, Result: <sensitive>."
+        );
+        assert!(secret.to_json().contains(r#""value":null"#));
+
+        assert_eq!(
+            Outcome::unfinished().summary(),
+            "[ERRO] The script has failed, no result defined."
+        );
+        assert_eq!(Outcome::unfinished().status.exit_code(), EXIT_UNFINISHED);
+    }
+}
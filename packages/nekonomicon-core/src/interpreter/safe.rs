@@ -0,0 +1,101 @@
+//! `safe`: statements whose failure is reported and kept instead of stopping the spell.
+//!
+//! The last failure a `safe` statement let through is in the reserved container
+//! `::error`, with its `:code`, `:message` and `:line`. It holds null until then.
+
+use crate::ast::{Clause, Reference, Sigil, Statement};
+use crate::diagnostic::Diagnostic;
+use crate::span::Span;
+use crate::task;
+use crate::value::Value;
+
+use super::Interpreter;
+use super::environment::Binding;
+
+/// The name of the container that holds the last failure, `::error`.
+pub const LAST_FAILURE: &str = "error";
+
+impl Interpreter {
+    /// Binds `::error` to null, before anything could have failed.
+    pub(super) fn forget_failure(&mut self) {
+        self.environment.store(
+            &last_failure(),
+            Binding {
+                value: Value::Null,
+                sealed: false,
+                nullable: true,
+                sensitive: false,
+            },
+        );
+    }
+
+    /// What becomes of `result`, the run of `statement`: under `safe`, a failure is
+    /// warned about and kept in `::error`, and the spell carries on.
+    pub(super) fn recover(
+        &mut self,
+        statement: &Statement,
+        result: Result<(), Diagnostic>,
+    ) -> Result<(), Diagnostic> {
+        match result {
+            // A stopped task ends even under `safe`.
+            Err(error) if is_safe(statement) && error.code != task::CANCELLED => {
+                self.remember_failure(&error, statement.span());
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    /// Warns about `error` and keeps it in `::error`; `span` is where it happened
+    /// when the error itself does not say.
+    pub(super) fn remember_failure(&mut self, error: &Diagnostic, span: Span) {
+        self.warn_failure(error);
+        self.keep_failure(error, span);
+    }
+
+    /// Warns about `error` as a `safe` statement reports it.
+    pub(super) fn warn_failure(&self, error: &Diagnostic) {
+        let message = error.message.trim_end_matches('.');
+        self.console().warn(&format!("[WARN][FAIL] {}.", message));
+    }
+
+    /// Keeps `error` in `::error` without a warning.
+    pub(super) fn keep_failure(&mut self, error: &Diagnostic, span: Span) {
+        let message = error.message.trim_end_matches('.');
+        let line = error.span.unwrap_or(span).line;
+        let failure = Value::Map(vec![
+            ("code".to_string(), Value::Text(error.code.clone())),
+            ("message".to_string(), Value::Text(message.to_string())),
+            ("line".to_string(), Value::Integer(line as i64)),
+        ]);
+        self.environment.store(
+            &last_failure(),
+            Binding {
+                value: failure,
+                sealed: false,
+                nullable: true,
+                sensitive: false,
+            },
+        );
+    }
+}
+
+/// Whether `target` names `::error`, which only a failing `safe` statement writes.
+pub fn is_reserved(target: &Reference) -> bool {
+    matches!(target, Reference::Container { name, .. } if name == LAST_FAILURE)
+}
+
+fn is_safe(statement: &Statement) -> bool {
+    statement
+        .clauses()
+        .iter()
+        .any(|clause| clause.node == Clause::Safe)
+}
+
+fn last_failure() -> Reference {
+    Reference::Container {
+        name: LAST_FAILURE.to_string(),
+        sigil: Sigil::Plain,
+        path: Vec::new(),
+    }
+}
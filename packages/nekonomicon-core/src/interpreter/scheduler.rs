@@ -0,0 +1,612 @@
+//! The scheduler behind `async`, `wait` and `stop`.
+//!
+//! Every task runs on a thread of its own, but only one of the spell and its tasks
+//! holds the turn at any moment; the others are paused at a `wait`. Whoever pauses
+//! hands the turn to the next one ready, in the order they became ready, and when
+//! nobody is, the clock moves on to the first `wait <time>` to end. Pauses thus
+//! overlap, yet a spell prints the same lines in the same order on every run, and a
+//! [`FakeClock`](super::FakeClock) makes its waits instant.
+
+use std::any::Any;
+use std::collections::VecDeque;
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::ast::{Clause, Command, Instruction};
+use crate::decide::Patterns;
+use crate::diagnostic::Diagnostic;
+use crate::span::{Span, Spanned};
+use crate::task::{self, Wait};
+use crate::value::Value;
+
+use super::environment::Environment;
+use super::{Clock, Interpreter};
+
+/// Every task a spell launched, and whose turn it is to run.
+#[derive(Debug, Default)]
+pub struct Scheduler {
+    queue: Mutex<Queue>,
+    /// Signalled when the turn changes hands.
+    turn: Condvar,
+}
+
+/// The spell, as `None`, or the task at an index.
+type Runner = Option<usize>;
+
+#[derive(Debug, Default)]
+struct Queue {
+    tasks: Vec<Task>,
+    /// Tasks done so far, which orders their completions.
+    done: usize,
+    /// Who runs now.
+    turn: Runner,
+    /// Who may run next, in the order they became ready.
+    ready: VecDeque<Runner>,
+    /// Who waits, and for what, in the order they paused.
+    paused: Vec<(Runner, Until)>,
+    /// Woken only because nobody else could ever wake it.
+    stuck: Option<Runner>,
+    /// Failures of `safe async` tasks, kept in `::error` when the spell runs next.
+    failures: Vec<(Diagnostic, Span)>,
+    /// The thread of each task started, by task index.
+    threads: Vec<(usize, JoinHandle<()>)>,
+}
+
+#[derive(Debug)]
+struct Task {
+    tag: Option<String>,
+    command: Command,
+    safe: bool,
+    state: State,
+    /// Whether its thread has yet to end.
+    live: bool,
+}
+
+#[derive(Debug)]
+enum State {
+    /// Not started; the bindings as they were at launch.
+    Pending(Box<Environment>),
+    Running,
+    Done {
+        /// What the command gave, and whether it was read from a sensitive binding.
+        result: Result<(Value, bool), Diagnostic>,
+        elapsed: Duration,
+        /// Its place among the completions.
+        order: usize,
+        /// Handed out by `wait any`.
+        collected: bool,
+        /// Raised by a `wait` already, or only warned about under `safe`.
+        reported: bool,
+    },
+    Cancelled,
+}
+
+impl State {
+    fn as_str(&self) -> &'static str {
+        match self {
+            State::Pending(_) => "pending",
+            State::Running => "running",
+            State::Done { result: Ok(_), .. } => "success",
+            State::Done { result: Err(_), .. } => "failed",
+            State::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// What a paused runner waits for.
+#[derive(Debug)]
+enum Until {
+    /// `wait 5 seconds`: the clock to read this.
+    Time(Duration),
+    /// `wait 'tag'` and `wait all`: every task of the groups to end.
+    All(Groups),
+    /// `wait any`: a task of the groups done and not handed out, or none left to run.
+    Any(Groups),
+}
+
+/// The tags a `wait` or `stop` names; `None` covers every task, tagged or not.
+type Groups = Option<Vec<String>>;
+
+impl Task {
+    fn covered(&self, groups: &Groups) -> bool {
+        match (groups, &self.tag) {
+            (None, _) => true,
+            (Some(tags), Some(tag)) => tags.contains(tag),
+            (Some(_), None) => false,
+        }
+    }
+
+    /// Done, or cancelled with its thread gone.
+    fn ended(&self) -> bool {
+        match self.state {
+            State::Done { .. } => true,
+            State::Cancelled => !self.live,
+            State::Pending(_) | State::Running => false,
+        }
+    }
+
+    /// How the task went, as `wait ... into` hands it to the spell.
+    fn report(&self) -> (Value, bool) {
+        let text = |text: &str| Value::Text(text.to_string());
+        let (value, sensitive, code, message, elapsed) = match &self.state {
+            State::Done {
+                result, elapsed, ..
+            } => {
+                let elapsed = Value::Integer(elapsed.as_millis().try_into().unwrap_or(i64::MAX));
+                match result {
+                    Ok((value, sensitive)) => {
+                        (value.clone(), *sensitive, Value::Null, Value::Null, elapsed)
+                    }
+                    Err(error) => (
+                        Value::Null,
+                        false,
+                        text(&error.code),
+                        text(&error.message),
+                        elapsed,
+                    ),
+                }
+            }
+            _ => (Value::Null, false, Value::Null, Value::Null, Value::Null),
+        };
+        let report = Value::Map(vec![
+            (
+                "tag".to_string(),
+                self.tag.as_deref().map_or(Value::Null, text),
+            ),
+            ("status".to_string(), text(self.state.as_str())),
+            ("value".to_string(), value),
+            ("code".to_string(), code),
+            ("message".to_string(), message),
+            ("elapsed".to_string(), elapsed),
+        ]);
+        (report, sensitive)
+    }
+}
+
+impl Scheduler {
+    fn lock(&self) -> MutexGuard<'_, Queue> {
+        self.queue.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Queue {
+    fn holds(&self, until: &Until, now: Duration) -> bool {
+        let ended = |groups: &Groups| {
+            self.tasks
+                .iter()
+                .filter(|task| task.covered(groups))
+                .all(Task::ended)
+        };
+        match until {
+            Until::Time(wake) => *wake <= now,
+            Until::All(groups) => ended(groups),
+            Until::Any(groups) => ended(groups) || self.first_done(groups).is_some(),
+        }
+    }
+
+    /// The first task of `groups` that is done and was not handed out yet.
+    fn first_done(&self, groups: &Groups) -> Option<usize> {
+        self.tasks
+            .iter()
+            .enumerate()
+            .filter(|(_, task)| task.covered(groups))
+            .filter_map(|(index, task)| match task.state {
+                State::Done {
+                    order,
+                    collected: false,
+                    ..
+                } => Some((order, index)),
+                _ => None,
+            })
+            .min()
+            .map(|(_, index)| index)
+    }
+
+    /// Makes ready whoever waited for something that has come: tasks ending first,
+    /// then pauses in the order they end.
+    fn wake(&mut self, now: Duration) {
+        let (mut due, paused): (Vec<_>, Vec<_>) = mem::take(&mut self.paused)
+            .into_iter()
+            .partition(|(_, until)| self.holds(until, now));
+        self.paused = paused;
+        due.sort_by_key(|(_, until)| match until {
+            Until::Time(wake) => *wake,
+            Until::All(_) | Until::Any(_) => Duration::ZERO,
+        });
+        self.ready.extend(due.into_iter().map(|(runner, _)| runner));
+    }
+
+    /// Who runs next, moving the clock on when everyone is paused for time; `None`
+    /// when everyone waits for tasks that wait in turn.
+    fn next(&mut self, clock: &mut dyn Clock) -> Option<Runner> {
+        loop {
+            self.wake(clock.now());
+            while let Some(runner) = self.ready.pop_front() {
+                // Tasks stopped before they started never run.
+                if let Some(index) = runner
+                    && self.tasks[index].ended()
+                {
+                    continue;
+                }
+                return Some(runner);
+            }
+            let wake = self
+                .paused
+                .iter()
+                .filter_map(|(_, until)| match until {
+                    Until::Time(wake) => Some(*wake),
+                    Until::All(_) | Until::Any(_) => None,
+                })
+                .min()?;
+            clock.sleep(wake.saturating_sub(clock.now()));
+        }
+    }
+
+    /// Makes `runner` ready whatever it waits for, e.g. to end a stopped task.
+    fn unpause(&mut self, runner: Runner) {
+        if let Some(position) = self.paused.iter().position(|(paused, _)| *paused == runner) {
+            self.paused.remove(position);
+            self.ready.push_back(runner);
+        }
+    }
+
+    /// Raises the first failure among `groups`, in launch order, that was not
+    /// reported yet.
+    fn raise(&mut self, groups: &Groups) -> Result<(), Diagnostic> {
+        for index in 0..self.tasks.len() {
+            if self.tasks[index].covered(groups) {
+                self.raise_task(index)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn raise_task(&mut self, index: usize) -> Result<(), Diagnostic> {
+        let task = &mut self.tasks[index];
+        let State::Done {
+            result: Err(error),
+            reported: reported @ false,
+            ..
+        } = &mut task.state
+        else {
+            return Ok(());
+        };
+        *reported = true;
+        let launched = match &task.tag {
+            Some(tag) => format!("the async task '{}'", tag),
+            None => "an async task".to_string(),
+        };
+        Err(error.clone().with_note(format!(
+            "It failed in {} launched on line {}.",
+            launched, task.command.span.line
+        )))
+    }
+}
+
+impl Interpreter {
+    /// Queues `command` to start with the bindings it sees now, as soon as the spell
+    /// or the task launching it pauses.
+    pub(super) fn launch(&mut self, command: &Command, tag: Option<&str>) {
+        let mut queue = self.scheduler.lock();
+        let index = queue.tasks.len();
+        queue.ready.push_back(Some(index));
+        queue.tasks.push(Task {
+            tag: tag.map(str::to_string),
+            command: command.clone(),
+            safe: command.has_clause(&Clause::Safe),
+            state: State::Pending(Box::new(self.environment.capture())),
+            live: false,
+        });
+    }
+
+    /// `wait`: pauses until the time has passed or the tasks waited for have ended,
+    /// then gives their reports; a pause for time gives nothing.
+    pub(super) fn wait(&mut self, command: &Command) -> Result<Option<(Value, bool)>, Diagnostic> {
+        match task::wait(command)? {
+            Wait::Time { amount, unit } => {
+                let duration = task::duration(
+                    &self.operand(&amount.node, amount.span)?,
+                    &self.operand(&unit.node, unit.span)?,
+                    amount.span.join(unit.span),
+                )?;
+                let wake = self.clock().now() + duration;
+                self.pause(Until::Time(wake), Some(command.span))?;
+                Ok(None)
+            }
+            Wait::All(tags) => {
+                let groups = self.groups(&tags)?;
+                if let Some(index) = self.task {
+                    let queue = self.scheduler.lock();
+                    if queue.tasks[index].covered(&groups) {
+                        return Err(Diagnostic::error(
+                            task::SELF_WAIT,
+                            "A task cannot wait for the group it belongs to",
+                        )
+                        .with_span(command.span)
+                        .with_label(command.span, "waits here")
+                        .with_label(queue.tasks[index].command.span, "inside this task")
+                        .with_help("Wait for the group from the spell, after launching it."));
+                    }
+                }
+                self.pause(Until::All(groups.clone()), Some(command.span))?;
+                let mut queue = self.scheduler.lock();
+                queue.raise(&groups)?;
+                let mut sensitive = false;
+                let reports = queue
+                    .tasks
+                    .iter()
+                    .filter(|task| task.covered(&groups))
+                    .map(|task| {
+                        let (report, tainted) = task.report();
+                        sensitive |= tainted;
+                        report
+                    })
+                    .collect();
+                Ok(Some((Value::List(reports), sensitive)))
+            }
+            Wait::Any(tags) => {
+                let groups = self.groups(&tags)?;
+                self.pause(Until::Any(groups.clone()), Some(command.span))?;
+                let mut queue = self.scheduler.lock();
+                let Some(index) = queue.first_done(&groups) else {
+                    return Ok(Some((Value::Null, false)));
+                };
+                if let State::Done { collected, .. } = &mut queue.tasks[index].state {
+                    *collected = true;
+                }
+                queue.raise_task(index)?;
+                Ok(Some(queue.tasks[index].report()))
+            }
+        }
+    }
+
+    /// `stop`: cancels the tasks of the groups named that have not ended; one that
+    /// runs ends at its next pause.
+    pub(super) fn stop(&mut self, command: &Command) -> Result<(), Diagnostic> {
+        let groups = self.groups(task::stop(command)?)?;
+        let mut queue = self.scheduler.lock();
+        for index in 0..queue.tasks.len() {
+            let task = &mut queue.tasks[index];
+            if self.task == Some(index) || !task.covered(&groups) {
+                continue;
+            }
+            match task.state {
+                State::Pending(_) => task.state = State::Cancelled,
+                State::Running => {
+                    task.state = State::Cancelled;
+                    queue.unpause(Some(index));
+                }
+                State::Done { .. } | State::Cancelled => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Waits for every task left when the spell ends, and raises the first failure
+    /// no `wait` has reported.
+    pub(super) fn finish_tasks(&mut self) -> Result<(), Diagnostic> {
+        self.pause(Until::All(None), None)?;
+        self.scheduler.lock().raise(&None)
+    }
+
+    /// Stops whatever still runs once the spell is over, e.g. after it failed, and
+    /// lets the threads of the tasks end.
+    pub(super) fn abandon_tasks(&mut self) {
+        {
+            let mut queue = self.scheduler.lock();
+            for index in 0..queue.tasks.len() {
+                let task = &mut queue.tasks[index];
+                if !task.ended() {
+                    task.state = State::Cancelled;
+                    queue.unpause(Some(index));
+                }
+            }
+        }
+        // Stopped tasks only unwind, so the spell is never the one left stuck.
+        let _ = self.pause(Until::All(None), None);
+        let threads = mem::take(&mut self.scheduler.lock().threads);
+        for (index, thread) in threads {
+            // A panic in the command is a failure already; one past it still is.
+            if let Err(panic) = thread.join() {
+                let mut queue = self.scheduler.lock();
+                let order = queue.done;
+                let task = &mut queue.tasks[index];
+                if !matches!(task.state, State::Done { .. }) {
+                    let error = panicked(&task.command, panic.as_ref());
+                    task.state = State::Done {
+                        result: Err(error),
+                        elapsed: Duration::ZERO,
+                        order,
+                        collected: false,
+                        reported: false,
+                    };
+                    queue.done += 1;
+                }
+            }
+        }
+    }
+
+    /// The groups `tags` name, each of which must have had a task launched.
+    fn groups(&self, tags: &[Spanned<Instruction>]) -> Result<Groups, Diagnostic> {
+        if tags.is_empty() {
+            return Ok(None);
+        }
+        let values = tags
+            .iter()
+            .map(|tag| Ok((self.operand(&tag.node, tag.span)?, tag.span)))
+            .collect::<Result<Vec<_>, Diagnostic>>()?;
+        let mut groups = Vec::with_capacity(values.len());
+        for (tag, span) in task::tags(values)? {
+            if !self
+                .scheduler
+                .lock()
+                .tasks
+                .iter()
+                .any(|task| task.tag.as_ref() == Some(&tag))
+            {
+                return Err(Diagnostic::error(
+                    task::TAG,
+                    format!("No task was launched with the tag '{}'", tag),
+                )
+                .with_span(span)
+                .with_label(span, "unknown tag")
+                .with_help(format!(
+                    "Launch one before this line: async '{}' <command>.",
+                    tag
+                )));
+            }
+            groups.push(tag);
+        }
+        Ok(Some(groups))
+    }
+
+    /// Hands the turn over until `until` comes, then takes it back. The spell keeps
+    /// the failures `safe async` tasks let through meanwhile in `::error`.
+    fn pause(&mut self, until: Until, span: Option<Span>) -> Result<(), Diagnostic> {
+        let scheduler = Arc::clone(&self.scheduler);
+        let mut queue = scheduler.lock();
+        queue.paused.push((self.task, until));
+        self.hand_over(&mut queue);
+        let mut queue = scheduler
+            .turn
+            .wait_while(queue, |queue| queue.turn != self.task)
+            .unwrap_or_else(PoisonError::into_inner);
+
+        let stuck = queue.stuck.take_if(|stuck| *stuck == self.task).is_some();
+        let cancelled = self
+            .task
+            .is_some_and(|index| matches!(queue.tasks[index].state, State::Cancelled));
+        let failures = match self.task {
+            None => mem::take(&mut queue.failures),
+            Some(_) => Vec::new(),
+        };
+        drop(queue);
+        for (error, span) in failures {
+            self.keep_failure(&error, span);
+        }
+
+        if cancelled {
+            let mut error = Diagnostic::error(task::CANCELLED, "The task was stopped");
+            if let Some(span) = span {
+                error = error.with_span(span).with_label(span, "stopped here");
+            }
+            return Err(error);
+        }
+        if stuck {
+            let mut error = Diagnostic::error(
+                task::SELF_WAIT,
+                "Tasks wait for each other, so none of them can end",
+            )
+            .with_help("Wait for the groups from the spell, after launching them.");
+            if let Some(span) = span {
+                error = error.with_span(span).with_label(span, "waits here");
+            }
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    /// Gives the turn to whoever runs next, starting the thread of a task that has
+    /// not run yet.
+    fn hand_over(&self, queue: &mut Queue) {
+        let next = queue.next(&mut *self.clock());
+        // Everyone waits for tasks that wait in turn: the last to pause is told so.
+        let next = next.unwrap_or_else(|| {
+            let stuck = queue.paused.pop().and_then(|(runner, _)| runner);
+            queue.stuck = Some(stuck);
+            stuck
+        });
+        queue.turn = next;
+        if let Some(index) = next {
+            let task = &mut queue.tasks[index];
+            match mem::replace(&mut task.state, State::Running) {
+                State::Pending(environment) => {
+                    task.live = true;
+                    let thread = self.start(index, task.command.clone(), *environment);
+                    queue.threads.push((index, thread));
+                }
+                state => task.state = state,
+            }
+        }
+        self.scheduler.turn.notify_all();
+    }
+
+    /// Runs task `index` on a thread of its own, with an interpreter of its own that
+    /// shares the console, the clock and the scheduler of this one.
+    fn start(&self, index: usize, command: Command, environment: Environment) -> JoinHandle<()> {
+        let mut interpreter = Interpreter {
+            environment,
+            schemas: self.schemas.clone(),
+            patterns: Patterns::new(),
+            console: Arc::clone(&self.console),
+            max_iterations: self.max_iterations,
+            functions: self.functions.clone(),
+            depth: 0,
+            outcome: None,
+            scheduler: Arc::clone(&self.scheduler),
+            clock: Arc::clone(&self.clock),
+            task: Some(index),
+        };
+        thread::spawn(move || interpreter.run_task(index, &command))
+    }
+
+    /// Runs the command of task `index` to its end, then hands the turn over for good.
+    /// A panic fails the task like an error would.
+    fn run_task(&mut self, index: usize, command: &Command) {
+        let started = self.clock().now();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            self.produce(command).and_then(|produced| {
+                let (value, sensitive) = produced.unwrap_or((Value::Null, false));
+                if let Some(schema) = &command.schema {
+                    self.schemas.check(schema, &value)?;
+                }
+                Ok((value, sensitive))
+            })
+        }))
+        .unwrap_or_else(|panic| Err(panicked(command, panic.as_ref())));
+        let elapsed = self.clock().now().saturating_sub(started);
+
+        let scheduler = Arc::clone(&self.scheduler);
+        let mut queue = scheduler.lock();
+        let task = &mut queue.tasks[index];
+        task.live = false;
+        if !matches!(task.state, State::Cancelled) {
+            // Under `safe`, the failure is warned about as it happens and never raised.
+            let safe = task.safe;
+            if let Err(error) = &result
+                && safe
+            {
+                self.warn_failure(error);
+                queue.failures.push((error.clone(), command.span));
+            }
+            let order = queue.done;
+            queue.done += 1;
+            queue.tasks[index].state = State::Done {
+                result,
+                elapsed,
+                order,
+                collected: false,
+                reported: safe,
+            };
+        }
+        self.hand_over(&mut queue);
+    }
+}
+
+/// The failure of a task whose command panicked.
+fn panicked(command: &Command, panic: &(dyn Any + Send)) -> Diagnostic {
+    let reason = panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("no reason given");
+    Diagnostic::error(
+        task::PANICKED,
+        format!("The task '{}' crashed: {}", command.name.node, reason),
+    )
+    .with_span(command.span)
+    .with_label(command.span, "launched here")
+}
@@ -0,0 +1,177 @@
+//! `into`, `update` and `save`: how a value reaches the binding a sink names.
+
+use crate::ast::{Reference, Sink, SinkMode};
+use crate::container;
+use crate::diagnostic::Diagnostic;
+use crate::span::Span;
+use crate::value::Value;
+
+use super::environment::{self, Binding};
+use super::{Interpreter, safe};
+
+pub const DOUBLE_SEAL: &str = "E-SINK-DOUBLE-SEAL";
+pub const SEAL_PROJECTION: &str = "E-SINK-SEAL-PROJECTION";
+pub const UPDATE_UNBOUND: &str = "E-SINK-UPDATE-UNBOUND";
+pub const RECORD_SCALAR: &str = "E-SINK-RECORD-SCALAR";
+pub const NULL: &str = "E-SINK-NULL";
+pub const NO_VALUE: &str = "E-SINK-NO-VALUE";
+pub const RESERVED: &str = "E-SINK-RESERVED";
+
+impl Interpreter {
+    /// Stores `value` where `sink` says; `sensitive` taints what it writes.
+    ///
+    /// Nothing changes when this fails.
+    pub(super) fn sink(
+        &mut self,
+        sink: &Sink,
+        value: Value,
+        sensitive: bool,
+    ) -> Result<(), Diagnostic> {
+        let target = &sink.target.node;
+        let span = sink.target.span;
+        let path = match target {
+            Reference::Container { path, .. } => path.as_slice(),
+            Reference::Record { .. } => &[],
+        };
+        let seal = sink.mode == SinkMode::Save || target.sigil().is_sealed();
+        let error = |code: &str, message: String| {
+            Diagnostic::error(code, message)
+                .with_span(span)
+                .with_label(span, format!("'{}' is written here", sink.mode.as_str()))
+        };
+
+        if safe::is_reserved(target) {
+            return Err(reserved(target, span));
+        }
+        let existing = self.environment.lookup(target);
+        if let Some(binding) = existing
+            && binding.sealed
+        {
+            return Err(rewrite_sealed(target, seal, span));
+        }
+        if seal && !path.is_empty() {
+            return Err(seal_projection(target, span));
+        }
+        if sink.mode == SinkMode::Update {
+            let exists = existing
+                .is_some_and(|binding| container::project(target, &binding.value, span).is_ok());
+            if !exists {
+                return Err(error(
+                    UPDATE_UNBOUND,
+                    format!("Cannot update '{}': it does not exist yet", target),
+                )
+                .with_help("Use 'into' to create it."));
+            }
+        }
+
+        let value = match (target, value) {
+            (Reference::Record { .. }, value) if !value.is_scalar() => {
+                return Err(error(
+                    RECORD_SCALAR,
+                    format!("The record '{}' cannot hold {}", target, value.described()),
+                )
+                .with_help(format!(
+                    "Records hold text; store it in a container such as '::{}'.",
+                    target.name()
+                )));
+            }
+            // Records are text: typed scalars keep only their spelling.
+            (Reference::Record { .. }, value) if !value.is_null() => Value::Text(value.to_string()),
+            (_, value) => value,
+        };
+        let nullable =
+            target.sigil().is_nullable() || existing.is_some_and(|binding| binding.nullable);
+        if value.is_null() && path.is_empty() && !nullable {
+            return Err(
+                error(NULL, format!("'{}' cannot hold null", target)).with_help(format!(
+                    "Only nullable bindings may hold null: write '{}{}'.",
+                    match target {
+                        Reference::Record { .. } => "@?",
+                        Reference::Container { .. } => "::?",
+                    },
+                    target.name()
+                )),
+            );
+        }
+
+        if path.is_empty() {
+            let binding = Binding {
+                value,
+                sealed: seal,
+                nullable,
+                sensitive,
+            };
+            self.environment.store(target, binding);
+            return Ok(());
+        }
+
+        // Writing into a projection builds on the current tree, or on a new one.
+        let mut root = existing
+            .map(|binding| binding.value.clone())
+            .unwrap_or_default();
+        container::assign(&mut root, path, value, span)?;
+        match self.environment.lookup_mut(target) {
+            Some(binding) => {
+                binding.value = root;
+                binding.sensitive |= sensitive;
+            }
+            None => self.environment.define(
+                target,
+                Binding {
+                    value: root,
+                    sealed: false,
+                    nullable,
+                    sensitive,
+                },
+            ),
+        }
+        Ok(())
+    }
+}
+
+/// The error for a write to `::error`, which holds the last failure a `safe`
+/// statement let through.
+pub(crate) fn reserved(target: &Reference, span: Span) -> Diagnostic {
+    Diagnostic::error(
+        RESERVED,
+        format!("'::{}' is reserved for the last failure", target.name()),
+    )
+    .with_span(span)
+    .with_label(span, "written here")
+    .with_help("Store the value under another name; read '::error' after a 'safe' statement.")
+}
+
+/// Why a sealed binding cannot take this write: its projections are read-only, it
+/// cannot be sealed twice, and it cannot change at all.
+pub(crate) fn rewrite_sealed(target: &Reference, seal: bool, span: Span) -> Diagnostic {
+    match target {
+        Reference::Container { path, .. } if !path.is_empty() => Diagnostic::error(
+            container::SEALED,
+            format!(
+                "'::{}' is sealed, so its projections are read-only",
+                target.name()
+            ),
+        )
+        .with_span(span)
+        .with_label(span, "written here")
+        .with_help("Build a new container with the changed field instead."),
+        _ if seal => Diagnostic::error(DOUBLE_SEAL, format!("'{}' is already sealed", target))
+            .with_span(span)
+            .with_label(span, "sealed again here")
+            .with_help("A binding is sealed once, where it is first bound."),
+        _ => environment::sealed(target, span),
+    }
+}
+
+pub(crate) fn seal_projection(target: &Reference, span: Span) -> Diagnostic {
+    Diagnostic::error(
+        SEAL_PROJECTION,
+        format!("Cannot seal the projection '{}'", target),
+    )
+    .with_span(span)
+    .with_label(span, "sealed here")
+    .with_help(format!(
+        "Seal the whole container instead, e.g. 'into ::!{}'.",
+        target.name()
+    ))
+}
@@ -0,0 +1,54 @@
+//! Minimal JSON writing helpers shared by the renderers and the runtime.
+
+/// Quotes and escapes `text` as a JSON string.
+pub fn quote(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Writes a JSON array from already serialized items.
+pub fn array<I: IntoIterator<Item = String>>(items: I) -> String {
+    format!("[{}]", items.into_iter().collect::<Vec<_>>().join(","))
+}
+
+/// Writes a JSON object from keys and already serialized values.
+pub fn object<'a, I: IntoIterator<Item = (&'a str, String)>>(fields: I) -> String {
+    let fields: Vec<String> = fields
+        .into_iter()
+        .map(|(key, value)| format!("{}:{}", quote(key), value))
+        .collect();
+    format!("{{{}}}", fields.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote_escapes() {
+        assert_eq!(quote("say 'hi'\n\"x\"\\"), "\"say 'hi'\\n\\\"x\\\"\\\\\"");
+        assert_eq!(quote("\u{1}"), "\"\\u0001\"");
+    }
+
+    #[test]
+    fn test_object_and_array() {
+        let value = object([
+            ("a", array(["1".to_string(), quote("b")])),
+            ("c", "null".to_string()),
+        ]);
+        assert_eq!(value, "{\"a\":[1,\"b\"],\"c\":null}");
+    }
+}
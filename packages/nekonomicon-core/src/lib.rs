@@ -1,38 +1,515 @@
+// Diagnostics are the error type of the runtime: rich, built rarely and moved once.
+#![allow(clippy::result_large_err)]
+
+pub mod analysis;
+pub mod ast;
+pub mod calculate;
+pub mod container;
+pub mod decide;
+pub mod diagnostic;
+pub mod error;
+pub mod flow;
+pub mod function;
+pub mod interpolation;
+pub mod interpreter;
+mod json;
+pub mod literal;
+pub mod number;
 pub mod parser;
+pub mod schema;
+pub mod span;
+pub mod task;
+pub mod token;
+pub mod tokenizer;
+pub mod value;
 
-pub use parser::{Command, parse, execute};
+pub use analysis::analyze;
+pub use ast::{Command, Program, Statement};
+pub use diagnostic::{Diagnostic, RenderMode};
+pub use error::ParseError;
+pub use interpreter::{Interpreter, execute};
+pub use parser::{ParseOptions, parse, parse_with_options, parse_with_recovery};
+pub use span::{Span, Spanned};
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ast::{
+        Clause, CommandKind, CommentKind, Field, FieldNode, Instruction, ModifierMode, Platform,
+        Projection, Reference, Sigil, SinkMode,
+    };
+    use crate::value::Lens;
+
+    fn nodes<T: Clone>(spanned: &[Spanned<T>]) -> Vec<T> {
+        spanned.iter().map(|item| item.node.clone()).collect()
+    }
+
+    fn single_command(input: &str) -> Command {
+        let program = parse(input).unwrap_or_else(|e| panic!("Parse failed: {}", e));
+        match program.statements.as_slice() {
+            [Statement::Command(command)] => command.clone(),
+            other => panic!("Expected a single command, found {:?}", other),
+        }
+    }
+
+    fn say_text(command: &Command) -> &str {
+        assert_eq!(command.name.node, "say");
+        match command.instructions.as_slice() {
+            [
+                Spanned {
+                    node: Instruction::Text(text),
+                    ..
+                },
+            ] => text,
+            other => panic!("Expected a single literal, found {:?}", other),
+        }
+    }
 
     #[test]
     fn test_simple_say() {
-        let result = parse("say 'Hello, World!'.");
-        match result {
-            Ok(Command::Say { text }) => {
-                assert_eq!(text, "Hello, World!");
-            }
-            Err(e) => {
-                panic!("Parse failed: {}", e);
-            }
-        }
+        let command = single_command("say 'Hello, World!'.");
+        assert_eq!(say_text(&command), "Hello, World!");
     }
 
     #[test]
     fn test_say_with_quotes() {
-        let result = parse("say 'I\\'m learning nekonomicon!'.");
-        match result {
-            Ok(Command::Say { text }) => {
-                assert_eq!(text, "I'm learning nekonomicon!");
-            }
-            Err(e) => {
-                panic!("Parse failed: {}", e);
-            }
+        let command = single_command("say 'I\\'m learning nekonomicon!'.");
+        assert_eq!(say_text(&command), "I'm learning nekonomicon!");
+    }
+
+    #[test]
+    fn test_multiline_literals_and_escapes() {
+        let command = single_command("say 'This is\n  a multiline\\tstring \\u{1F63A}'.");
+        assert_eq!(say_text(&command), "This is\n  a multiline\tstring 😺");
+
+        let options = ParseOptions {
+            literals: literal::LiteralOptions {
+                strip_indentation: true,
+            },
+        };
+        let (program, diagnostics) = parse_with_options("say '\n    Hello\n    World'.", options);
+        assert!(diagnostics.is_empty());
+        match program.statements.as_slice() {
+            [Statement::Command(command)] => assert_eq!(say_text(command), "Hello\nWorld"),
+            other => panic!("Expected a single command, found {:?}", other),
         }
     }
-}
 
+    #[test]
+    fn test_literal_errors() {
+        let error = parse("say 'a\\qb'.").unwrap_err();
+        assert_eq!(error.code, "E-SYNTAX-ESCAPE");
+        assert_eq!((error.span.column, error.span.len()), (7, 2));
+
+        let error = parse("say 'a'.\nsay 'never closed.\n").unwrap_err();
+        assert_eq!(error.code, "E-SYNTAX-UNTERMINATED-LITERAL");
+        assert_eq!((error.span.line, error.span.column), (2, 5));
+    }
+
+    #[test]
+    fn test_number_and_boolean_literals() {
+        let command = single_command("say 0.1 -7 007 true 'x' with retries 3.");
+        assert_eq!(
+            nodes(&command.instructions),
+            [
+                Instruction::Decimal("0.1".to_string()),
+                Instruction::Integer("-7".to_string()),
+                Instruction::Integer("007".to_string()),
+                Instruction::Boolean(true),
+                Instruction::Text("x".to_string()),
+            ]
+        );
+        assert_eq!(
+            command.modifiers[0]
+                .value
+                .as_ref()
+                .map(|value| value.text().into_owned()),
+            Some("3".to_string())
+        );
+        assert_eq!(
+            nodes(&single_command("say 1.").instructions),
+            [Instruction::Integer("1".to_string())]
+        );
+        assert!(parse("say 1.5x.").is_err());
+    }
+
+    #[test]
+    fn test_reference_sigils() {
+        let command = single_command("say @a @!b @?c ::d ::!e ::?f with secret @!pw.");
+        let references: Vec<_> = command
+            .instructions
+            .iter()
+            .map(|instruction| match &instruction.node {
+                Instruction::Reference(reference) => {
+                    (reference.to_string(), reference.sigil().is_sealed())
+                }
+                other => panic!("Expected a reference, found {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            references,
+            [
+                ("@a".to_string(), false),
+                ("@!b".to_string(), true),
+                ("@?c".to_string(), false),
+                ("::d".to_string(), false),
+                ("::!e".to_string(), true),
+                ("::?f".to_string(), false),
+            ]
+        );
+        assert!(matches!(
+            &command.modifiers[0].value.as_ref().unwrap().node,
+            Instruction::Reference(Reference::Record {
+                sigil: Sigil::Sealed,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_sealed_and_nullable_are_exclusive() {
+        for (input, sigils) in [
+            ("say @!?x.", "!?"),
+            ("say ::?!x.", "?!"),
+            ("say @!!x.", "!!"),
+        ] {
+            let error = parse(input).unwrap_err();
+            assert_eq!(error.code, "E-SYNTAX-SIGIL");
+            assert_eq!(&input[error.span.start..error.span.end], sigils);
+        }
+    }
+
+    #[test]
+    fn test_container_block_nesting() {
+        let input = "container\n  :name @!username\n  :active true\n  \
+                     :preferences 'newsletter' 'notifications'\n  :emergency_contact\n    \
+                     :name 'John Doe'\n    :email 'john@example.com'\n  :?note\n\
+                     into ::!user_profile. ~ immutable container\nsay 'done'.";
+        let program = parse(input).expect("Parse failed");
+        assert_eq!(program.statements.len(), 2);
+        let Statement::Container(container) = &program.statements[0] else {
+            panic!("Expected a container block");
+        };
+        assert_eq!(container.sink.target.to_string(), "::!user_profile");
+
+        fn outline(fields: &[Field]) -> Vec<String> {
+            fields
+                .iter()
+                .map(|field| match &field.node {
+                    FieldNode::Value(value) => format!("{}={}", field.name.node, value.text()),
+                    FieldNode::Array(values) => format!("{}[{}]", field.name.node, values.len()),
+                    FieldNode::Container(children) => {
+                        format!("{}{{{}}}", field.name.node, outline(children).join(","))
+                    }
+                })
+                .collect()
+        }
+        assert_eq!(
+            outline(&container.fields),
+            [
+                "name=@!username",
+                "active=true",
+                "preferences[2]",
+                "emergency_contact{name=John Doe,email=john@example.com}",
+                "note{}",
+            ]
+        );
+        assert!(container.fields[4].sigil.is_nullable());
+    }
+
+    #[test]
+    fn test_container_block_errors() {
+        for (input, code, at) in [
+            (
+                "container\n  :!field 'value'\ninto ::!parent.",
+                "E-CTNR-SEAL-INHERITED",
+                "field",
+            ),
+            (
+                "container\n  :!db\n    :!host 'x'\ninto ::config.",
+                "E-CTNR-SEAL-INHERITED",
+                "host",
+            ),
+            (
+                "container\n  :name 'a'\n  :name 'b'\ninto ::c.",
+                "E-CTNR-DUPLICATE-FIELD",
+                "name",
+            ),
+            (
+                "container\n  :name 'a'\n    :first 'b'\ninto ::c.",
+                "E-SYNTAX-INDENTATION",
+                "first",
+            ),
+            (
+                "container\n    :a\n      :b 'x'\n   :c 'y'\ninto ::c.",
+                "E-SYNTAX-INDENTATION",
+                "c",
+            ),
+        ] {
+            let error = parse(input).unwrap_err();
+            assert_eq!(error.code, code, "{input}");
+            assert_eq!(&input[error.span.start..error.span.end], at);
+        }
+    }
+
+    #[test]
+    fn test_projection_paths() {
+        let command = single_command("say ::data:?items#?3:name ::m#0#1.");
+        let Instruction::Reference(Reference::Container { name, path, .. }) =
+            &command.instructions[0].node
+        else {
+            panic!("Expected a container reference");
+        };
+        assert_eq!(name, "data");
+        assert_eq!(
+            path,
+            &[
+                Projection::Field {
+                    name: "items".to_string(),
+                    optional: true
+                },
+                Projection::Index {
+                    index: 3,
+                    optional: true
+                },
+                Projection::Field {
+                    name: "name".to_string(),
+                    optional: false
+                },
+            ]
+        );
+        assert_eq!(command.instructions[1].text(), "::m#0#1");
+
+        let error = parse("say ::m#99999999999999999999999.").unwrap_err();
+        assert_eq!(error.code, "E-CTNR-INVALID-PROJ");
+    }
+
+    #[test]
+    fn test_sink_forms() {
+        let command = single_command("sensitive ask 'Password?' is &string save @pw.");
+        let sink = command.sink.unwrap();
+        assert_eq!(sink.mode, SinkMode::Save);
+        assert_eq!(sink.target.to_string(), "@pw");
+        assert_eq!(command.schema.unwrap().node, "string");
+        assert_eq!(nodes(&command.instructions).len(), 1);
+
+        // `is` stays a word unless a schema follows.
+        let command = single_command("check is ready into @state.");
+        assert_eq!(nodes(&command.actions), ["is", "ready"]);
+
+        let program = parse(
+            "'5.5' 'x' as decimal into !::a:b.
+null update @?maybe.",
+        )
+        .unwrap();
+        let [Statement::Assignment(first), Statement::Assignment(second)] =
+            program.statements.as_slice()
+        else {
+            panic!("Expected two assignments");
+        };
+        assert_eq!(first.values.len(), 2);
+        assert_eq!(first.lens.as_ref().unwrap().node, Lens::Decimal);
+        assert_eq!(first.sink.as_ref().unwrap().target.to_string(), "::!a:b");
+        assert_eq!(nodes(&second.values), [Instruction::Null]);
+        assert_eq!(second.sink.as_ref().unwrap().mode, SinkMode::Update);
+
+        for input in [
+            "'x'.",
+            "'x' into @a @b.",
+            "say 'x' into.",
+            "'x' into !::!a.",
+        ] {
+            assert!(parse(input).is_err(), "{input}");
+        }
+        let error = parse("container :a 'x' into @a.").unwrap_err();
+        assert_eq!(error.code, "E-SYNTAX-UNEXPECTED");
+    }
+
+    #[test]
+    fn test_program_with_comments_and_blank_lines() {
+        let input = "~ greeting spell\n\nsay 'Hello'. ~ first\n\n\nsay 'World'.\n";
+        let program = parse(input).expect("Parse failed");
+        let texts: Vec<_> = program
+            .statements
+            .iter()
+            .map(|statement| match statement {
+                Statement::Command(command) => say_text(command).to_string(),
+                other => panic!("Expected a command, found {:?}", other),
+            })
+            .collect();
+        assert_eq!(texts, ["Hello", "World"]);
+    }
 
+    #[test]
+    fn test_empty_program() {
+        let program = parse("~ nothing to see here\n").expect("Parse failed");
+        assert!(program.statements.is_empty());
+    }
 
+    #[test]
+    fn test_block_comments_are_kept() {
+        let input = "~~~\n  Demo spell.\n  say 'not code'.\n~~~\n\nsay 'Hello'. ~ greet\n";
+        let program = parse(input).expect("Parse failed");
+        assert_eq!(program.statements.len(), 1);
+        let kinds: Vec<_> = program
+            .comments
+            .iter()
+            .map(|comment| comment.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            [CommentKind::Block { terminated: true }, CommentKind::Line]
+        );
+        assert!(program.comments[0].text.contains("Demo spell."));
+    }
+
+    #[test]
+    fn test_unterminated_block_comment() {
+        let (program, diagnostics) = parse_with_recovery("say 'a'.\n~~~ forgot to close\nsay 'b'.");
+        assert_eq!(program.statements.len(), 1);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, "E-SYNTAX-UNTERMINATED-COMMENT");
+        assert_eq!(diagnostics[0].span.map(|span| span.line), Some(2));
+    }
+
+    #[test]
+    fn test_module_command_anatomy() {
+        let command = single_command("safe cabinet delete file 'x' with silence on linux.");
+        assert_eq!(nodes(&command.clauses), [Clause::Safe]);
+        assert_eq!(command.kind, CommandKind::Module);
+        assert_eq!(command.name.node, "cabinet");
+        assert_eq!(nodes(&command.actions), ["delete", "file"]);
+        assert_eq!(
+            nodes(&command.instructions),
+            [Instruction::Text("x".to_string())]
+        );
+        assert_eq!(command.modifiers.len(), 1);
+        assert_eq!(command.modifiers[0].mode, ModifierMode::With);
+        assert_eq!(command.modifiers[0].name.node, "silence");
+        assert_eq!(command.modifiers[0].value, None);
+        assert_eq!(
+            command.platform.map(|platform| platform.node),
+            Some(Platform::Linux)
+        );
+    }
+
+    #[test]
+    fn test_clauses_and_multiline_modifiers() {
+        let command = single_command(
+            "!!! sensitive async 'mirror' cabinet copy file 'a' to 'b'\n  with timeout '5m'\n  without trace\n  on mac.",
+        );
+        assert_eq!(
+            nodes(&command.clauses),
+            [
+                Clause::SensitiveEgress,
+                Clause::Async {
+                    tag: Some("mirror".to_string())
+                }
+            ]
+        );
+        assert_eq!(
+            nodes(&command.instructions),
+            [
+                Instruction::Text("a".to_string()),
+                Instruction::Word("to".to_string()),
+                Instruction::Text("b".to_string()),
+            ]
+        );
+        assert_eq!(
+            command.modifiers[0].value.as_ref().map(|value| &value.node),
+            Some(&Instruction::Text("5m".to_string()))
+        );
+        assert_eq!(command.modifiers[1].mode, ModifierMode::Without);
+        assert_eq!(
+            command.platform.map(|platform| platform.node),
+            Some(Platform::Mac)
+        );
+    }
 
+    #[test]
+    fn test_node_spans() {
+        let input = "say 'a'.\nsafe cabinet delete file 'x'\n  with silence.";
+        let program = parse(input).expect("Parse failed");
+        let Statement::Command(command) = &program.statements[1] else {
+            panic!("Expected a command");
+        };
+        assert_eq!((command.span.line, command.span.column), (2, 1));
+        assert_eq!(&input[command.span.start..command.span.end], &input[9..]);
+        assert_eq!((command.name.span.line, command.name.span.column), (2, 6));
+        let instruction = command.instructions[0].span;
+        assert_eq!(&input[instruction.start..instruction.end], "'x'");
+        let modifier = &command.modifiers[0];
+        assert_eq!((modifier.span.line, modifier.span.column), (3, 3));
+    }
+
+    #[test]
+    fn test_parse_error_location() {
+        let error = parse("say 'a'.\nsay 'b' on plan9.").unwrap_err();
+        assert_eq!(error.code, "E-SYNTAX-UNEXPECTED");
+        assert_eq!((error.span.line, error.span.column), (2, 12));
+        assert!(
+            error
+                .expected
+                .iter()
+                .any(|expected| expected.starts_with("platform"))
+        );
+    }
+
+    #[test]
+    fn test_platform_must_come_last() {
+        assert!(parse("say 'hi' on linux with trace.").is_err());
+        assert!(parse("say 'hi' on plan9.").is_err());
+    }
+
+    #[test]
+    fn test_recovery_reports_every_error() {
+        let input = "say 'a' on plan9.\nsay 'ok'.\n'oops' say.\nsay 'still ok'.";
+        let (program, diagnostics) = parse_with_recovery(input);
+        let lines: Vec<_> = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.span.map(|span| span.line))
+            .collect();
+        assert_eq!(lines, [Some(1), Some(3)]);
+        assert!(matches!(
+            program.statements.as_slice(),
+            [
+                Statement::Error(_),
+                Statement::Command(_),
+                Statement::Error(_),
+                Statement::Command(_)
+            ]
+        ));
+        let broken = program.statements[2].span();
+        assert_eq!(&input[broken.start..broken.end], "'oops' say.");
+        assert!(parse(input).is_err());
+    }
+
+    #[test]
+    fn test_recovery_skips_broken_blocks() {
+        let input = "if @a > 1\n  say 'x'.\n  if @b\n    say 'y'.\n  end\nend\nsay 'after'.";
+        let (program, diagnostics) = parse_with_recovery(input);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, flow::EXPRESSION);
+        match program.statements.as_slice() {
+            [Statement::Error(span), Statement::Command(command)] => {
+                assert!(input[span.start..span.end].ends_with("  end\nend"));
+                assert_eq!(say_text(command), "after");
+            }
+            other => panic!("Unexpected statements {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_recovery_resyncs_at_end() {
+        let input = "  'x' 'y'\n  'z'\nend\nsay 'after'. ~ trailing.";
+        let (program, diagnostics) = parse_with_recovery(input);
+        assert_eq!(diagnostics.len(), 1);
+        match program.statements.as_slice() {
+            [Statement::Error(span), Statement::Command(command)] => {
+                assert_eq!(&input[span.start..span.end], "'x' 'y'\n  'z'\nend");
+                assert_eq!(say_text(command), "after");
+            }
+            other => panic!("Unexpected statements {:?}", other),
+        }
+    }
+}
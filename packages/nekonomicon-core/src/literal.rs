@@ -0,0 +1,477 @@
+//! The string literal lexer shared by the tokenizer and the parser.
+
+use crate::ast::{Projection, Reference, Sigil};
+use crate::error::ParseError;
+
+/// How the body of a literal is turned into its value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LiteralOptions {
+    /// For multiline literals: drop a line break right after the opening quote and
+    /// remove the indentation shared by every following non-blank line.
+    pub strip_indentation: bool,
+}
+
+/// A problem inside a literal; offsets are relative to the opening quote.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiteralError {
+    /// `E-SYNTAX-ESCAPE` or `E-SYNTAX-INTERPOLATION`.
+    pub code: &'static str,
+    pub message: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A run of text or an interpolation; offsets are relative to the opening quote.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Part {
+    Text(String),
+    Reference {
+        reference: Reference,
+        start: usize,
+        end: usize,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Literal {
+    /// The text with escapes resolved and interpolations as written.
+    pub value: String,
+    /// The value split into text and `@{..}` interpolations.
+    pub parts: Vec<Part>,
+    /// Bytes read, from the opening quote to just past the closing one.
+    pub length: usize,
+    /// False when the input ended before the closing quote.
+    pub terminated: bool,
+    pub errors: Vec<LiteralError>,
+}
+
+/// Lexes the literal at the start of `input`, which must begin with `'`.
+///
+/// Supported escapes are `\'`, `\\`, `\@`, `\n`, `\t`, `\r` and `\u{..}` with one to
+/// six hexadecimal digits. Unknown escapes are kept as written and reported.
+/// `@{name}`, `@!{name}`, `@?{name}` and `@{::container:path}` become references.
+pub fn lex(input: &str, options: LiteralOptions) -> Literal {
+    debug_assert!(input.starts_with('\''));
+
+    let (body_end, length, terminated) = match find_closing_quote(input) {
+        Some(close) => (close, close + 1, true),
+        None => (input.len(), input.len(), false),
+    };
+
+    let mut literal = Literal {
+        value: String::new(),
+        parts: Vec::new(),
+        length,
+        terminated,
+        errors: Vec::new(),
+    };
+    for (offset, line) in lines(input, 1, body_end, options) {
+        literal.unescape(line, offset);
+    }
+    literal
+}
+
+impl Literal {
+    /// True when the literal has at least one interpolation.
+    pub fn is_template(&self) -> bool {
+        self.parts
+            .iter()
+            .any(|part| matches!(part, Part::Reference { .. }))
+    }
+
+    fn push(&mut self, c: char) {
+        self.value.push(c);
+        match self.parts.last_mut() {
+            Some(Part::Text(text)) => text.push(c),
+            _ => self.parts.push(Part::Text(c.to_string())),
+        }
+    }
+
+    fn push_str(&mut self, text: &str) {
+        text.chars().for_each(|c| self.push(c));
+    }
+
+    fn error(&mut self, code: &'static str, message: String, start: usize, end: usize) {
+        self.errors.push(LiteralError {
+            code,
+            message,
+            start,
+            end,
+        });
+    }
+
+    fn unescape(&mut self, raw: &str, offset: usize) {
+        let mut chars = raw.char_indices().peekable();
+        while let Some((index, c)) = chars.next() {
+            let skip_to = match c {
+                '\\' => self.escape(raw, index, offset),
+                '@' if interpolation_start(&raw[index..]).is_some() => {
+                    self.interpolation(raw, index, offset)
+                }
+                '@' if mixed_sigils(&raw[index + 1..]) > 0 => {
+                    // `@!?{x}`: sealed and nullable cannot be combined.
+                    let end = index + 1 + mixed_sigils(&raw[index + 1..]);
+                    self.push_str(&raw[index..end]);
+                    self.error(
+                        ParseError::SIGIL,
+                        format!("Invalid sigil '{}'", &raw[index + 1..end]),
+                        offset + index + 1,
+                        offset + end,
+                    );
+                    end
+                }
+                _ => {
+                    self.push(c);
+                    continue;
+                }
+            };
+            while chars.next_if(|&(next, _)| next < skip_to).is_some() {}
+        }
+    }
+
+    /// Resolves the escape at `index`, returning where reading continues.
+    fn escape(&mut self, raw: &str, index: usize, offset: usize) -> usize {
+        let Some(escaped) = raw[index + 1..].chars().next() else {
+            self.push('\\');
+            return raw.len();
+        };
+        let next = index + 1 + escaped.len_utf8();
+        match escaped {
+            '\'' | '\\' | '@' => self.push(escaped),
+            'n' => self.push('\n'),
+            't' => self.push('\t'),
+            'r' => self.push('\r'),
+            'u' => {
+                let sequence = raw[next..]
+                    .strip_prefix('{')
+                    .and_then(|inner| inner.find('}').map(|close| &inner[..close]));
+                let end = next + sequence.map(|digits| digits.len() + 2).unwrap_or(0);
+                let decoded = sequence
+                    .filter(|digits| (1..=6).contains(&digits.len()))
+                    .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+                    .and_then(char::from_u32);
+                match decoded {
+                    Some(decoded) => self.push(decoded),
+                    None => {
+                        self.push_str(&raw[index..end]);
+                        self.error(
+                            ParseError::ESCAPE,
+                            format!("Invalid unicode escape '{}'", &raw[index..end]),
+                            offset + index,
+                            offset + end,
+                        );
+                    }
+                }
+                return end;
+            }
+            other => {
+                self.push_str(&raw[index..next]);
+                self.error(
+                    ParseError::ESCAPE,
+                    format!("Unknown escape '\\{}'", other),
+                    offset + index,
+                    offset + next,
+                );
+            }
+        }
+        next
+    }
+
+    /// Reads the interpolation at `index`, returning where reading continues.
+    fn interpolation(&mut self, raw: &str, index: usize, offset: usize) -> usize {
+        let (sigil, open) = interpolation_start(&raw[index..]).unwrap_or_default();
+        let body = index + open;
+        let Some(close) = raw[body..].find('}').map(|close| body + close) else {
+            self.push_str(&raw[index..]);
+            self.error(
+                ParseError::INTERPOLATION,
+                format!("Interpolation '{}' is never closed", &raw[index..body]),
+                offset + index,
+                offset + body,
+            );
+            return raw.len();
+        };
+
+        let written = &raw[index..=close];
+        self.value.push_str(written);
+        match parse_reference(sigil, &raw[body..close]) {
+            Some(reference) => self.parts.push(Part::Reference {
+                reference,
+                start: offset + index,
+                end: offset + close + 1,
+            }),
+            None => {
+                match self.parts.last_mut() {
+                    Some(Part::Text(text)) => text.push_str(written),
+                    _ => self.parts.push(Part::Text(written.to_string())),
+                }
+                self.error(
+                    ParseError::INTERPOLATION,
+                    format!("Malformed interpolation '{}'", written),
+                    offset + index,
+                    offset + close + 1,
+                );
+            }
+        }
+        close + 1
+    }
+}
+
+/// Sigil and length of an interpolation opener: `@{`, `@!{` or `@?{`.
+fn interpolation_start(text: &str) -> Option<(Sigil, usize)> {
+    if text.starts_with("@{") {
+        Some((Sigil::Plain, 2))
+    } else if text.starts_with("@!{") {
+        Some((Sigil::Sealed, 3))
+    } else if text.starts_with("@?{") {
+        Some((Sigil::Nullable, 3))
+    } else {
+        None
+    }
+}
+
+/// Length of a run of two or more sigils directly followed by `{`, otherwise 0.
+fn mixed_sigils(text: &str) -> usize {
+    let length = text.len() - text.trim_start_matches(['!', '?']).len();
+    if length > 1 && text[length..].starts_with('{') {
+        length
+    } else {
+        0
+    }
+}
+
+/// Parses what sits between the braces: `name`, or `::name` with a projection path.
+fn parse_reference(sigil: Sigil, body: &str) -> Option<Reference> {
+    let Some(container) = body.strip_prefix("::") else {
+        return is_name(body).then(|| Reference::Record {
+            name: body.to_string(),
+            sigil,
+        });
+    };
+    // Containers carry their own sigil: `@{::!config:port}`.
+    if sigil != Sigil::Plain {
+        return None;
+    }
+
+    let (sigil, rest) = match container.chars().next() {
+        Some('!') => (Sigil::Sealed, &container[1..]),
+        Some('?') => (Sigil::Nullable, &container[1..]),
+        _ => (Sigil::Plain, container),
+    };
+    let name_end = rest.find([':', '#']).unwrap_or(rest.len());
+    let (name, mut rest) = rest.split_at(name_end);
+    if !is_name(name) {
+        return None;
+    }
+
+    let mut path = Vec::new();
+    while let Some(marker) = rest.chars().next() {
+        let step = &rest[1..];
+        let (optional, step) = match step.strip_prefix('?') {
+            Some(step) => (true, step),
+            None => (false, step),
+        };
+        let end = step.find([':', '#']).unwrap_or(step.len());
+        let target = &step[..end];
+        path.push(match marker {
+            ':' if is_name(target) => Projection::Field {
+                name: target.to_string(),
+                optional,
+            },
+            '#' => Projection::Index {
+                index: target.parse().ok()?,
+                optional,
+            },
+            _ => return None,
+        });
+        rest = &step[end..];
+    }
+
+    Some(Reference::Container {
+        name: name.to_string(),
+        sigil,
+        path,
+    })
+}
+
+fn is_name(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && text.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn find_closing_quote(input: &str) -> Option<usize> {
+    let mut chars = input.char_indices().skip(1);
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '\'' => return Some(index),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Splits the raw body into pieces to unescape, each with its offset in `input`.
+/// Line breaks stay attached to their line so the value keeps them.
+fn lines(input: &str, start: usize, end: usize, options: LiteralOptions) -> Vec<(usize, &str)> {
+    let body = &input[start..end];
+    if !options.strip_indentation || !body.contains('\n') {
+        return vec![(start, body)];
+    }
+
+    let mut pieces: Vec<(usize, &str)> = body
+        .split_inclusive('\n')
+        .scan(start, |offset, line| {
+            let piece = (*offset, line);
+            *offset += line.len();
+            Some(piece)
+        })
+        .collect();
+
+    // A literal opened with a line break starts on the next line; otherwise the
+    // text on the quote's own line is kept as written.
+    let skip = if pieces[0].1.trim().is_empty() {
+        pieces.remove(0);
+        0
+    } else {
+        1
+    };
+    let indentation = pieces
+        .iter()
+        .skip(skip)
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(_, line)| line.len() - line.trim_start_matches([' ', '\t']).len())
+        .min()
+        .unwrap_or(0);
+
+    pieces
+        .into_iter()
+        .enumerate()
+        .map(|(index, (offset, line))| {
+            if index < skip {
+                (offset, line)
+            } else if line.trim().is_empty() {
+                // Whitespace-only lines keep nothing but their line break.
+                let blank = line.len() - line.trim_start_matches([' ', '\t']).len();
+                (offset + blank, &line[blank..])
+            } else {
+                (offset + indentation, &line[indentation..])
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(input: &str) -> String {
+        let literal = lex(input, LiteralOptions::default());
+        assert!(literal.errors.is_empty(), "{:?}", literal.errors);
+        literal.value
+    }
+
+    #[test]
+    fn test_escape_table() {
+        assert_eq!(value(r"'I\'m \\ a\tb\nc'"), "I'm \\ a\tb\nc");
+        assert_eq!(value(r"'\u{1F63A} \u{e9}'"), "😺 é");
+    }
+
+    #[test]
+    fn test_length_and_termination() {
+        let literal = lex("'a\\'b' rest", LiteralOptions::default());
+        assert_eq!((literal.length, literal.terminated), (6, true));
+        let open = lex("'never closed", LiteralOptions::default());
+        assert_eq!((open.length, open.terminated), (13, false));
+    }
+
+    #[test]
+    fn test_escape_errors() {
+        let literal = lex(r"'a\qb \u{zz} \u{110000}'", LiteralOptions::default());
+        let messages: Vec<_> = literal
+            .errors
+            .iter()
+            .map(|error| error.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "Unknown escape '\\q'",
+                "Invalid unicode escape '\\u{zz}'",
+                "Invalid unicode escape '\\u{110000}'"
+            ]
+        );
+        assert_eq!((literal.errors[0].start, literal.errors[0].end), (2, 4));
+        assert_eq!(literal.value, r"a\qb \u{zz} \u{110000}");
+    }
+
+    #[test]
+    fn test_multiline_literals() {
+        let source = "'This is\n    a multiline\n      string.'";
+        assert_eq!(value(source), "This is\n    a multiline\n      string.");
+
+        let strip = LiteralOptions {
+            strip_indentation: true,
+        };
+        assert_eq!(lex(source, strip).value, "This is\na multiline\n  string.");
+        let indented = "'\n    Hello\n\n      World\\t!\n  '";
+        assert_eq!(lex(indented, strip).value, "Hello\n\n  World\t!\n");
+    }
+
+    #[test]
+    fn test_interpolation_parts() {
+        let literal = lex(
+            r"'Hi @{name}, @!{id} \@{x} @{::user:?address#0:city}!'",
+            LiteralOptions::default(),
+        );
+        assert!(literal.errors.is_empty(), "{:?}", literal.errors);
+        assert_eq!(
+            literal.value,
+            "Hi @{name}, @!{id} @{x} @{::user:?address#0:city}!"
+        );
+        let references: Vec<_> = literal
+            .parts
+            .iter()
+            .filter_map(|part| match part {
+                Part::Reference { reference, .. } => Some(reference.to_string()),
+                Part::Text(_) => None,
+            })
+            .collect();
+        assert_eq!(references, ["@name", "@!id", "::user:?address#0:city"]);
+        assert!(matches!(
+            literal.parts[1],
+            Part::Reference {
+                start: 4,
+                end: 11,
+                ..
+            }
+        ));
+        assert!(!lex("'mail@example.com'", LiteralOptions::default()).is_template());
+    }
+
+    #[test]
+    fn test_interpolation_errors() {
+        let literal = lex(
+            "'@{} @!{::x} @{a b} @!?{x} @{open'",
+            LiteralOptions::default(),
+        );
+        let messages: Vec<_> = literal
+            .errors
+            .iter()
+            .map(|error| error.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "Malformed interpolation '@{}'",
+                "Malformed interpolation '@!{::x}'",
+                "Malformed interpolation '@{a b}'",
+                "Invalid sigil '!?'",
+                "Interpolation '@{' is never closed",
+            ]
+        );
+        assert!(!literal.is_template());
+    }
+}
@@ -0,0 +1,402 @@
+//! Exact decimal numbers for `calculate`.
+//!
+//! Numbers are string-backed in spells, so arithmetic works on the decimal digits
+//! as written: `0.1 + 0.2` is `0.3`, not the nearest binary float.
+
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::value::Value;
+
+/// Digits kept after the decimal point when a result does not terminate, e.g. `1 / 3`.
+pub const MAX_SCALE: u32 = 18;
+
+/// `mantissa / 10^scale`, kept without trailing zeros.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Number {
+    mantissa: i128,
+    scale: u32,
+}
+
+/// Why an operation has no result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArithmeticError {
+    DivisionByZero,
+    /// A root of a negative number, or a fractional power of one.
+    NegativeRoot,
+    /// A result too large to represent.
+    Overflow,
+}
+
+use ArithmeticError::{DivisionByZero, NegativeRoot, Overflow};
+
+impl Number {
+    pub const ZERO: Number = Number {
+        mantissa: 0,
+        scale: 0,
+    };
+
+    /// Reads `-?digits(.digits)?`, the shape of number literals.
+    pub fn parse(text: &str) -> Option<Number> {
+        let digits = text.strip_prefix('-').unwrap_or(text);
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        let all_digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+        if whole.is_empty()
+            || !all_digits(whole)
+            || !all_digits(fraction)
+            || (digits.contains('.') && fraction.is_empty())
+        {
+            return None;
+        }
+        let fraction = fraction.trim_end_matches('0');
+        let scale = u32::try_from(fraction.len()).ok()?;
+        let mantissa: i128 = format!("{whole}{fraction}").parse().ok()?;
+        let sign = if text.starts_with('-') { -1 } else { 1 };
+        Some(Number::new(sign * mantissa, scale))
+    }
+
+    /// The number a value holds: typed numbers and text that reads as one.
+    pub fn from_value(value: &Value) -> Option<Number> {
+        match value {
+            Value::Integer(integer) => Some(Number::new(i128::from(*integer), 0)),
+            Value::Decimal(text) | Value::Text(text) => Number::parse(text.trim()),
+            _ => None,
+        }
+    }
+
+    fn new(mantissa: i128, scale: u32) -> Number {
+        let mut number = Number { mantissa, scale };
+        while number.scale > 0 && number.mantissa % 10 == 0 {
+            number.mantissa /= 10;
+            number.scale -= 1;
+        }
+        if number.mantissa == 0 {
+            number.scale = 0;
+        }
+        number
+    }
+
+    /// Rounds half away from zero to at most [`MAX_SCALE`] digits.
+    fn rounded(mantissa: i128, scale: u32) -> Number {
+        Number { mantissa, scale }.round_to(MAX_SCALE)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mantissa == 0
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.mantissa < 0
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.scale == 0
+    }
+
+    /// Both mantissas at the larger of the two scales.
+    fn aligned(self, other: Number) -> Result<(i128, i128, u32), ArithmeticError> {
+        let scale = self.scale.max(other.scale);
+        let widen = |number: Number| {
+            number
+                .mantissa
+                .checked_mul(pow10(scale - number.scale)?)
+                .ok_or(Overflow)
+        };
+        Ok((widen(self)?, widen(other)?, scale))
+    }
+
+    pub fn plus(self, other: Number) -> Result<Number, ArithmeticError> {
+        let (left, right, scale) = self.aligned(other)?;
+        Ok(Number::new(left.checked_add(right).ok_or(Overflow)?, scale))
+    }
+
+    pub fn minus(self, other: Number) -> Result<Number, ArithmeticError> {
+        self.plus(other.negate())
+    }
+
+    pub fn multiply(self, other: Number) -> Result<Number, ArithmeticError> {
+        let mantissa = self.mantissa.checked_mul(other.mantissa).ok_or(Overflow)?;
+        Ok(Number::rounded(mantissa, self.scale + other.scale))
+    }
+
+    pub fn divide(self, other: Number) -> Result<Number, ArithmeticError> {
+        if other.is_zero() {
+            return Err(DivisionByZero);
+        }
+        // Long division of the mantissas, one decimal at a time, until the quotient
+        // has MAX_SCALE + 1 decimals to round from or no room for another digit.
+        let divisor = other.mantissa.unsigned_abs();
+        let mut quotient = self.mantissa.unsigned_abs() / divisor;
+        let mut remainder = self.mantissa.unsigned_abs() % divisor;
+        let wanted = i64::from(MAX_SCALE + 1) + i64::from(other.scale) - i64::from(self.scale);
+        let mut decimals = 0;
+        while remainder != 0 && i64::from(decimals) < wanted {
+            let (Some(shifted), Some(widened)) =
+                (remainder.checked_mul(10), quotient.checked_mul(10))
+            else {
+                break;
+            };
+            if widened > i128::MAX as u128 - 9 {
+                break;
+            }
+            quotient = widened + shifted / divisor;
+            remainder = shifted % divisor;
+            decimals += 1;
+        }
+        let mut mantissa = i128::try_from(quotient).map_err(|_| Overflow)?;
+        if self.is_negative() != other.is_negative() {
+            mantissa = -mantissa;
+        }
+        // The quotient is mantissa / 10^decimals, scaled by 10^(other.scale - self.scale).
+        match (self.scale + decimals).checked_sub(other.scale) {
+            Some(scale) => Ok(Number::rounded(mantissa, scale)),
+            None => {
+                let factor = pow10(other.scale - self.scale - decimals)?;
+                Ok(Number::new(
+                    mantissa.checked_mul(factor).ok_or(Overflow)?,
+                    0,
+                ))
+            }
+        }
+    }
+
+    /// The remainder of truncated division, with the sign of `self`.
+    pub fn remainder(self, other: Number) -> Result<Number, ArithmeticError> {
+        if other.is_zero() {
+            return Err(DivisionByZero);
+        }
+        let (left, right, scale) = self.aligned(other)?;
+        Ok(Number::new(left % right, scale))
+    }
+
+    pub fn negate(self) -> Number {
+        Number::new(-self.mantissa, self.scale)
+    }
+
+    /// `self ** exponent`. Whole exponents are exact; others go through floats.
+    pub fn power(self, exponent: Number) -> Result<Number, ArithmeticError> {
+        if !exponent.is_integer() {
+            return self.float(|base| base.powf(exponent.to_f64()));
+        }
+        // Square and multiply, so that huge exponents end quickly in a result or an overflow.
+        let mut result = Number::new(1, 0);
+        let mut base = self;
+        let mut remaining = exponent.mantissa.unsigned_abs();
+        while remaining > 0 {
+            if remaining & 1 == 1 {
+                result = result.multiply(base)?;
+            }
+            remaining >>= 1;
+            if remaining > 0 {
+                base = base.multiply(base)?;
+            }
+        }
+        if exponent.is_negative() {
+            return Number::new(1, 0).divide(result);
+        }
+        Ok(result)
+    }
+
+    /// The `degree`-th root: `16 // 2` is 4. Only odd roots of negative numbers
+    /// exist: `-27 // 3` is -3.
+    pub fn root(self, degree: Number) -> Result<Number, ArithmeticError> {
+        if degree.is_zero() {
+            return Err(DivisionByZero);
+        }
+        if self.is_negative() {
+            let odd = degree.is_integer() && degree.mantissa % 2 != 0;
+            if !odd {
+                return Err(NegativeRoot);
+            }
+            return Ok(self.negate().root(degree)?.negate());
+        }
+        let root = self.float(|radicand| radicand.powf(1.0 / degree.to_f64()))?;
+        // Prefer a whole result when the float lands next to one, e.g. 27 // 3.
+        let whole = root.round_to(0);
+        Ok(match whole.power(degree) {
+            Ok(check) if check == self => whole,
+            _ => root,
+        })
+    }
+
+    /// Rounds half away from zero to at most `scale` decimals.
+    pub fn round_to(self, scale: u32) -> Number {
+        if self.scale <= scale {
+            return Number::new(self.mantissa, self.scale);
+        }
+        // Dropping more digits than an i128 holds leaves nothing to round up from.
+        let Ok(divisor) = pow10(self.scale - scale) else {
+            return Number::ZERO;
+        };
+        let mut quotient = self.mantissa / divisor;
+        if (self.mantissa % divisor).unsigned_abs() * 2 >= divisor.unsigned_abs() {
+            quotient += self.mantissa.signum();
+        }
+        Number::new(quotient, scale)
+    }
+
+    fn to_f64(self) -> f64 {
+        self.to_string().parse().unwrap_or(f64::NAN)
+    }
+
+    /// Runs `operation` on floats, keeping 12 decimals of the result.
+    fn float(self, operation: impl Fn(f64) -> f64) -> Result<Number, ArithmeticError> {
+        let result = operation(self.to_f64());
+        if result.is_nan() {
+            return Err(NegativeRoot);
+        }
+        if !result.is_finite() {
+            return Err(Overflow);
+        }
+        Number::parse(&format!("{:.12}", result)).ok_or(Overflow)
+    }
+
+    /// The value a result is stored as: an integer when it is whole and fits.
+    pub fn to_value(self) -> Value {
+        match i64::try_from(self.mantissa) {
+            Ok(integer) if self.is_integer() => Value::Integer(integer),
+            _ => Value::Decimal(self.to_string()),
+        }
+    }
+}
+
+fn pow10(exponent: u32) -> Result<i128, ArithmeticError> {
+    10i128.checked_pow(exponent).ok_or(Overflow)
+}
+
+impl Ord for Number {
+    fn cmp(&self, other: &Number) -> Ordering {
+        if let Ok((left, right, _)) = self.aligned(*other) {
+            return left.cmp(&right);
+        }
+        // Too far apart to align: compare the signs, then where the first digit
+        // stands, then the digits themselves.
+        let sign = self.mantissa.signum().cmp(&other.mantissa.signum());
+        if sign != Ordering::Equal || self.is_zero() {
+            return sign;
+        }
+        let digits = |number: &Number| number.mantissa.unsigned_abs().to_string();
+        let (left, right) = (digits(self), digits(other));
+        let magnitude =
+            |digits: &str, number: &Number| digits.len() as i64 - i64::from(number.scale);
+        let width = left.len().max(right.len());
+        let magnitude = magnitude(&left, self)
+            .cmp(&magnitude(&right, other))
+            .then_with(|| format!("{left:0<width$}").cmp(&format!("{right:0<width$}")));
+        if self.is_negative() {
+            magnitude.reverse()
+        } else {
+            magnitude
+        }
+    }
+}
+
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Number) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = self.mantissa.unsigned_abs().to_string();
+        let sign = if self.is_negative() { "-" } else { "" };
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return write!(f, "{sign}{digits}");
+        }
+        let digits = format!("{digits:0>width$}", width = scale + 1);
+        let (whole, fraction) = digits.split_at(digits.len() - scale);
+        write!(f, "{sign}{whole}.{fraction}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn number(text: &str) -> Number {
+        Number::parse(text).unwrap_or_else(|| panic!("'{text}' is not a number"))
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        for (text, shown) in [
+            ("42", "42"),
+            ("-7", "-7"),
+            ("0.10", "0.1"),
+            ("007.50", "7.5"),
+            ("-0.05", "-0.05"),
+            ("-0", "0"),
+        ] {
+            assert_eq!(number(text).to_string(), shown, "{text}");
+        }
+        for text in ["", "1.", ".5", "1e3", "abc", "--1"] {
+            assert!(Number::parse(text).is_none(), "{text}");
+        }
+    }
+
+    #[test]
+    fn test_decimal_arithmetic_is_exact() {
+        assert_eq!(number("0.1").plus(number("0.2")), Ok(number("0.3")));
+        assert_eq!(number("1.1").multiply(number("1.1")), Ok(number("1.21")));
+        assert_eq!(number("100").divide(number("4")), Ok(number("25")));
+        assert_eq!(
+            number("1").divide(number("3")),
+            Ok(number("0.333333333333333333"))
+        );
+        assert_eq!(
+            number("2").divide(number("3")),
+            Ok(number("0.666666666666666667"))
+        );
+        assert_eq!(number("10").remainder(number("3")), Ok(number("1")));
+        assert_eq!(number("5.5").remainder(number("2")), Ok(number("1.5")));
+        assert_eq!(number("1").divide(Number::ZERO), Err(DivisionByZero));
+        assert!(number("3") > number("2.99"));
+    }
+
+    #[test]
+    fn test_far_apart_numbers() {
+        let tiny = number("0.0000000000000000000000000000000000000000001");
+        let huge = number("100000000000000000000");
+        assert!(tiny < huge);
+        assert!(huge.negate() < tiny.negate());
+        assert!(tiny.negate() < Number::ZERO);
+        assert!(number("99999999999999999999.5") > number("99999999999999999999"));
+        assert!(number("-12345678901234567890123456789012345678") < number("0.5"));
+        assert_eq!(tiny.round_to(MAX_SCALE), Number::ZERO);
+        assert_eq!(
+            number("0.0000000000000000005").round_to(MAX_SCALE),
+            number("0.000000000000000001")
+        );
+        assert_eq!(huge.divide(huge), Ok(number("1")));
+        assert_eq!(
+            number("20000000000000000000").divide(number("2")),
+            Ok(number("10000000000000000000"))
+        );
+        assert_eq!(
+            number("100000000000000000000").divide(number("3")),
+            Ok(number("33333333333333333333.333333333333333333"))
+        );
+        assert_eq!(
+            number("1").divide(number("0.0000000000000000000000000000004")),
+            Ok(number("2500000000000000000000000000000"))
+        );
+        assert_eq!(number("-7").divide(number("2")), Ok(number("-3.5")));
+    }
+
+    #[test]
+    fn test_power_and_root() {
+        assert_eq!(number("2").power(number("8")), Ok(number("256")));
+        assert_eq!(number("2").power(number("-2")), Ok(number("0.25")));
+        assert_eq!(number("0").power(number("-1")), Err(DivisionByZero));
+        assert_eq!(number("16").root(number("2")), Ok(number("4")));
+        assert_eq!(number("27").root(number("3")), Ok(number("3")));
+        assert_eq!(number("2").root(number("2")), Ok(number("1.414213562373")));
+        assert_eq!(number("-4").root(number("2")), Err(NegativeRoot));
+        assert_eq!(number("-27").root(number("3")), Ok(number("-3")));
+        assert_eq!(number("-4").power(number("0.5")), Err(NegativeRoot));
+        assert_eq!(number("10").power(number("40")), Err(Overflow));
+        assert_eq!(number("2").root(Number::ZERO), Err(DivisionByZero));
+    }
+}
@@ -1,69 +1,1250 @@
 use pest::Parser;
-use pest_derive::Parser;
+use pest::error::{ErrorVariant, InputLocation};
 use pest::iterators::Pair;
+use pest_derive::Parser;
+
+use crate::ast::{
+    Assignment, Calculation, Clause, Command, CommandKind, Comment, CommentKind, Conditional,
+    Container, Decision, Direction, Field, FieldNode, Function, Guard, Instruction, Loop, LoopKind,
+    Modifier, ModifierMode, Platform, Program, Projection, Quantifier, Reference, Segment, Sigil,
+    Sink, SinkMode, Statement, Template,
+};
+use crate::calculate;
+use crate::container;
+use crate::decide;
+use crate::diagnostic::Diagnostic;
+use crate::error::ParseError;
+use crate::flow;
+use crate::function;
+use crate::literal::{self, Literal, LiteralOptions, Part};
+use crate::span::{LineIndex, Span, Spanned};
+use crate::task;
+use crate::token::INTRINSIC_KEYWORDS;
+use crate::tokenizer;
+use crate::value::Lens;
 
 #[derive(Parser)]
 #[grammar = "grammar/nekonomicon.pest"]
 pub struct NekonomiconParser;
 
-/// Simple AST for minimal nekonomicon parser
-#[derive(Debug, Clone)]
-pub enum Command {
-    Say { text: String },
+/// Options that change how a spell is read.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ParseOptions {
+    pub literals: LiteralOptions,
+}
+
+pub fn parse(input: &str) -> Result<Program, ParseError> {
+    let (program, errors) = Builder::new(input, ParseOptions::default()).recover();
+    match errors.into_iter().next() {
+        Some(error) => Err(error),
+        None => Ok(program),
+    }
 }
 
-pub fn parse(input: &str) -> Result<Command, Box<dyn std::error::Error>> {
-    let parsed = NekonomiconParser::parse(Rule::command, input)?
-        .next()
-        .ok_or("No command found")?;
-    
-    parse_command(parsed)
+/// Parses a spell without stopping at the first syntax error.
+///
+/// A broken statement becomes a [`Statement::Error`] node and parsing resumes after
+/// the next `.` terminator or `end` line, so every syntax error is reported in one pass.
+pub fn parse_with_recovery(input: &str) -> (Program, Vec<Diagnostic>) {
+    parse_with_options(input, ParseOptions::default())
+}
+
+/// Like [`parse_with_recovery`], with non-default options.
+pub fn parse_with_options(input: &str, options: ParseOptions) -> (Program, Vec<Diagnostic>) {
+    let (program, errors) = Builder::new(input, options).recover();
+    (program, errors.into_iter().map(Diagnostic::from).collect())
+}
+
+/// Turns pest pairs into AST nodes, resolving their positions against the whole source.
+struct Builder<'a> {
+    source: &'a str,
+    lines: LineIndex,
+    /// Start of the slice handed to pest; pest positions are relative to it.
+    offset: usize,
+    comments: Vec<Comment>,
+    options: ParseOptions,
+}
+
+impl<'a> Builder<'a> {
+    fn new(source: &'a str, options: ParseOptions) -> Self {
+        Builder {
+            source,
+            lines: LineIndex::new(source),
+            offset: 0,
+            comments: tokenizer::comments(source),
+            options,
+        }
+    }
+
+    fn span(&self, pair: &Pair<Rule>) -> Span {
+        let span = pair.as_span();
+        self.lines.span(
+            self.source,
+            self.offset + span.start(),
+            self.offset + span.end(),
+        )
+    }
+
+    fn error_at(&self, offset: usize, message: impl Into<String>) -> ParseError {
+        let span = self.lines.span(self.source, offset, offset);
+        ParseError::new(ParseError::UNEXPECTED, message, span)
+    }
+
+    fn unexpected(&self, pair: &Pair<Rule>, context: &str) -> ParseError {
+        ParseError::new(
+            ParseError::UNEXPECTED,
+            format!(
+                "Unexpected {} in {}",
+                describe_rule(pair.as_rule()),
+                context
+            ),
+            self.span(pair),
+        )
+    }
+
+    fn syntax_error(&self, error: pest::error::Error<Rule>) -> ParseError {
+        let (start, end) = match error.location {
+            InputLocation::Pos(position) => (position, position),
+            InputLocation::Span((start, end)) => (start, end),
+        };
+        let (start, end) = (self.offset + start, self.offset + end);
+        let expected: Vec<String> = match &error.variant {
+            ErrorVariant::ParsingError { positives, .. } => {
+                let mut expected: Vec<String> = positives
+                    .iter()
+                    .map(|rule| describe_rule(*rule).to_string())
+                    .collect();
+                expected.dedup();
+                expected
+            }
+            ErrorVariant::CustomError { .. } => Vec::new(),
+        };
+
+        // Point at the whole offending token rather than its first character.
+        let rest = &self.source[start..];
+        let word = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        // An open literal runs to the end of input, so pest reports the error there.
+        if skip_trivia(self.source, &self.comments, start) == self.source.len()
+            && let Some(quote) = unterminated_literal(self.source, &self.comments, self.offset)
+        {
+            let mut error = ParseError::new(
+                ParseError::UNTERMINATED_LITERAL,
+                "String literal is never closed",
+                self.lines.span(self.source, quote, quote + 1),
+            );
+            error.expected.push("a closing quote '".to_string());
+            return error;
+        }
+
+        let (found, length) = match rest.chars().next() {
+            None => ("end of input".to_string(), 0),
+            Some('\'') => ("string literal".to_string(), 1),
+            Some(c) if word == 0 => (format!("'{}'", c), c.len_utf8()),
+            Some(_) => (format!("'{}'", &rest[..word]), word),
+        };
+
+        ParseError {
+            code: ParseError::UNEXPECTED,
+            message: format!("Unexpected {}", found),
+            span: self.lines.span(self.source, start, end.max(start + length)),
+            expected,
+        }
+    }
+
+    /// Parses one statement at a time, resynchronising after each syntax error.
+    fn recover(mut self) -> (Program, Vec<ParseError>) {
+        let mut statements = Vec::new();
+        let mut errors = Vec::new();
+
+        while NekonomiconParser::parse(Rule::program_end, &self.source[self.offset..]).is_err() {
+            let parsed =
+                NekonomiconParser::parse(Rule::next_statement, &self.source[self.offset..])
+                    .map_err(|error| self.syntax_error(error))
+                    .and_then(|mut pairs| {
+                        let pair = pairs
+                            .next()
+                            .ok_or_else(|| self.error_at(self.offset, "No statement found"))?;
+                        let end = pair.as_span().end();
+                        Ok((self.statement(pair)?, end))
+                    });
+
+            match parsed {
+                Ok((statement, end)) => {
+                    statements.push(statement);
+                    self.offset += end;
+                }
+                Err(error) => {
+                    let start = skip_trivia(self.source, &self.comments, self.offset);
+                    let resume = resync(self.source, &self.comments, self.offset).max(start);
+                    statements.push(Statement::Error(self.lines.span(
+                        self.source,
+                        start,
+                        resume,
+                    )));
+                    errors.push(error);
+                    self.offset = resume;
+                }
+            }
+        }
+
+        // A block comment left open swallows the rest of the spell.
+        for comment in &self.comments {
+            if comment.kind == (CommentKind::Block { terminated: false }) {
+                let start = comment.span.start;
+                let mut error = ParseError::new(
+                    ParseError::UNTERMINATED_COMMENT,
+                    "Block comment is never closed",
+                    self.lines.span(self.source, start, start + "~~~".len()),
+                );
+                error
+                    .expected
+                    .push("'~~~' to close the comment".to_string());
+                errors.push(error);
+            }
+        }
+        errors.sort_by_key(|error| error.span.start);
+
+        let program = Program {
+            statements,
+            comments: self.comments,
+        };
+        (program, errors)
+    }
+
+    fn statement(&self, pair: Pair<Rule>) -> Result<Statement, ParseError> {
+        let span = self.span(&pair);
+        let command = pair
+            .into_inner()
+            .next()
+            .ok_or_else(|| self.error_at(span.start, "Empty statement"))?;
+        let statement = match command.as_rule() {
+            Rule::container => Statement::Container(self.container(command)?),
+            Rule::assignment => Statement::Assignment(self.assignment(command)?),
+            Rule::calculation => Statement::Calculation(self.calculation(command)?),
+            Rule::decision => Statement::Decision(self.decision(command)?),
+            Rule::conditional => Statement::Conditional(self.conditional(command)?),
+            Rule::repetition => Statement::Loop(self.repetition(command)?),
+            Rule::function => Statement::Function(self.function(command)?),
+            _ => Statement::Command(self.command(command)?),
+        };
+        match &statement {
+            Statement::Command(command) => task::launchable(command)?,
+            other => task::not_a_command(other.clauses())?,
+        }
+        Ok(statement)
+    }
+
+    fn container(&self, pair: Pair<Rule>) -> Result<Container, ParseError> {
+        let span = self.span(&pair);
+        let mut entries = Vec::new();
+        let mut sink = None;
+
+        for inner in pair.into_inner() {
+            match inner.as_rule() {
+                Rule::field_entry => entries.push(self.field(inner)?),
+                Rule::sink => sink = Some(self.sink(inner)?),
+                Rule::container_keyword | Rule::terminator => {}
+                _ => return Err(self.unexpected(&inner, "container")),
+            }
+        }
+
+        let sink = sink.ok_or_else(|| self.error_at(span.end, "Container has no target"))?;
+        let target = &sink.target;
+        if let Reference::Record { .. } = target.node {
+            let mut error = ParseError::new(
+                ParseError::UNEXPECTED,
+                format!(
+                    "A container block cannot be stored into the record '{}'",
+                    target.node
+                ),
+                target.span,
+            );
+            error
+                .expected
+                .push(format!("a container, e.g. ::{}", target.name()));
+            return Err(error);
+        }
+        let fields = self.nest(entries)?;
+        let sealed_by = (target.sigil().is_sealed() || sink.mode == SinkMode::Save)
+            .then(|| (target.to_string(), target.span));
+        check_fields(&fields, sealed_by)?;
+        Ok(Container { fields, sink, span })
+    }
+
+    /// A field as written on its line; values make it a scalar or an array, and a
+    /// bare field starts a nested container that [`Builder::nest`] fills.
+    fn field(&self, pair: Pair<Rule>) -> Result<Field, ParseError> {
+        let span = self.span(&pair);
+        let mut inner = pair.into_inner();
+        let key = inner
+            .next()
+            .ok_or_else(|| self.error_at(span.start, "Field has no name"))?;
+
+        let mut sigil = Sigil::Plain;
+        let mut name = None;
+        for part in key.into_inner() {
+            match part.as_rule() {
+                Rule::sigils => sigil = self.sigil(&part)?,
+                Rule::identifier => {
+                    name = Some(Spanned::new(part.as_str().to_string(), self.span(&part)))
+                }
+                _ => return Err(self.unexpected(&part, "field")),
+            }
+        }
+        let name = name.ok_or_else(|| self.error_at(span.start, "Field has no name"))?;
+
+        let mut values = inner
+            .map(|value| self.instruction(value))
+            .collect::<Result<Vec<_>, _>>()?;
+        let node = match values.len() {
+            0 => FieldNode::Container(Vec::new()),
+            1 => FieldNode::Value(values.remove(0)),
+            _ => FieldNode::Array(values),
+        };
+        Ok(Field {
+            name,
+            sigil,
+            node,
+            span,
+        })
+    }
+
+    /// Nests fields by the indentation of the lines they start.
+    ///
+    /// A field indented deeper than the one before belongs to it; that field must be
+    /// bare. Fields on the `container` line, or after another field on the same
+    /// line, stay at the level of what precedes them.
+    fn nest(&self, entries: Vec<Field>) -> Result<Vec<Field>, ParseError> {
+        // Open levels with their indentation; the top level learns it from its first line.
+        let mut levels: Vec<(Option<usize>, Vec<Field>)> = vec![(None, Vec::new())];
+
+        for field in entries {
+            let (line_start, _) = self.lines.line_range(self.source, field.span.line);
+            let leading = self.source[line_start..field.span.start].trim().is_empty();
+            if leading {
+                let indent = field.span.column;
+                while levels.len() > 1 && levels.last().and_then(|level| level.0) > Some(indent) {
+                    close_level(&mut levels);
+                }
+                let (current, fields) = levels.last_mut().expect("top level is never closed");
+                match *current {
+                    None => *current = Some(indent),
+                    Some(open) if indent > open => {
+                        match fields.last() {
+                            Some(Field {
+                                node: FieldNode::Container(children),
+                                ..
+                            }) if children.is_empty() => {}
+                            Some(parent) => {
+                                return Err(self.indentation(
+                                    &field,
+                                    format!(
+                                        "Field ':{}' is indented under ':{}', which already \
+                                         holds a value",
+                                        field.name.node, parent.name.node
+                                    ),
+                                ));
+                            }
+                            None => {
+                                return Err(self.indentation(
+                                    &field,
+                                    format!("Field ':{}' is indented too far", field.name.node),
+                                ));
+                            }
+                        }
+                        levels.push((Some(indent), Vec::new()));
+                    }
+                    Some(open) if indent < open => {
+                        return Err(self.indentation(
+                            &field,
+                            format!(
+                                "Field ':{}' does not line up with any field above",
+                                field.name.node
+                            ),
+                        ));
+                    }
+                    Some(_) => {}
+                }
+            }
+            levels
+                .last_mut()
+                .expect("top level is never closed")
+                .1
+                .push(field);
+        }
+
+        while levels.len() > 1 {
+            close_level(&mut levels);
+        }
+        Ok(levels.pop().map(|(_, fields)| fields).unwrap_or_default())
+    }
+
+    fn indentation(&self, field: &Field, message: String) -> ParseError {
+        let mut error = ParseError::new(ParseError::INDENTATION, message, field.name.span);
+        error
+            .expected
+            .push("fields of one container at the same indentation".to_string());
+        error
+    }
+
+    fn command(&self, pair: Pair<Rule>) -> Result<Command, ParseError> {
+        if pair.as_rule() != Rule::command {
+            return Err(self.unexpected(&pair, "statement"));
+        }
+
+        let span = self.span(&pair);
+        let mut clauses = Vec::new();
+        let mut name = None;
+        let mut actions = Vec::new();
+        let mut instructions = Vec::new();
+        let mut modifiers = Vec::new();
+        let mut schema = None;
+        let mut sink = None;
+        let mut platform = None;
+
+        for inner in pair.into_inner() {
+            match inner.as_rule() {
+                Rule::clause => clauses.push(self.clause(inner)?),
+                Rule::word => {
+                    name = Some(Spanned::new(inner.as_str().to_string(), self.span(&inner)))
+                }
+                Rule::instruction => {
+                    let instruction = self.instruction(inner)?;
+                    // Leading bare words form the action, e.g. `delete file`.
+                    match instruction.node {
+                        Instruction::Word(word)
+                            if instructions.is_empty() && modifiers.is_empty() =>
+                        {
+                            actions.push(Spanned::new(word, instruction.span))
+                        }
+                        _ => instructions.push(instruction),
+                    }
+                }
+                Rule::modifier => modifiers.push(self.modifier(inner)?),
+                Rule::validation => schema = Some(self.validation(inner)?),
+                Rule::sink => sink = Some(self.sink(inner)?),
+                Rule::platform => platform = Some(self.platform(inner)?),
+                Rule::terminator => {}
+                _ => return Err(self.unexpected(&inner, "command")),
+            }
+        }
+
+        let name = name.ok_or_else(|| self.error_at(span.start, "Command has no name"))?;
+        let kind = if INTRINSIC_KEYWORDS.contains(&name.as_str()) {
+            CommandKind::Intrinsic
+        } else {
+            CommandKind::Module
+        };
+
+        Ok(Command {
+            clauses,
+            kind,
+            name,
+            actions,
+            instructions,
+            modifiers,
+            schema,
+            sink,
+            platform,
+            span,
+        })
+    }
+
+    fn assignment(&self, pair: Pair<Rule>) -> Result<Assignment, ParseError> {
+        let span = self.span(&pair);
+        let mut assignment = Assignment {
+            clauses: Vec::new(),
+            values: Vec::new(),
+            lens: None,
+            schema: None,
+            sink: None,
+            platform: None,
+            span,
+        };
+
+        for inner in pair.into_inner() {
+            match inner.as_rule() {
+                Rule::clause => assignment.clauses.push(self.clause(inner)?),
+                Rule::lens => assignment.lens = Some(self.lens(inner)?),
+                Rule::validation => assignment.schema = Some(self.validation(inner)?),
+                Rule::sink => assignment.sink = Some(self.sink(inner)?),
+                Rule::platform => assignment.platform = Some(self.platform(inner)?),
+                Rule::terminator => {}
+                _ => assignment.values.push(self.instruction(inner)?),
+            }
+        }
+        Ok(assignment)
+    }
+
+    fn calculation(&self, pair: Pair<Rule>) -> Result<Calculation, ParseError> {
+        let span = self.span(&pair);
+        let mut clauses = Vec::new();
+        let mut tokens = Vec::new();
+        let mut sink = None;
+        let mut platform = None;
+
+        for inner in pair.into_inner() {
+            match inner.as_rule() {
+                Rule::clause => clauses.push(self.clause(inner)?),
+                Rule::arithmetic => {
+                    let token_span = self.span(&inner);
+                    let part = inner
+                        .into_inner()
+                        .next()
+                        .ok_or_else(|| self.error_at(token_span.start, "Empty operand"))?;
+                    let token = match part.as_rule() {
+                        Rule::operator => calculate::Token::Operator(part.as_str().to_string()),
+                        Rule::parenthesis if part.as_str() == "(" => calculate::Token::Open,
+                        Rule::parenthesis => calculate::Token::Close,
+                        _ => calculate::Token::Operand(self.instruction(part)?.node),
+                    };
+                    tokens.push(Spanned::new(token, token_span));
+                }
+                Rule::sink => sink = Some(self.sink(inner)?),
+                Rule::platform => platform = Some(self.platform(inner)?),
+                Rule::calculate_keyword | Rule::terminator => {}
+                _ => return Err(self.unexpected(&inner, "calculate")),
+            }
+        }
+
+        Ok(Calculation {
+            clauses,
+            expression: calculate::parse(&tokens, span)?,
+            sink,
+            platform,
+            span,
+        })
+    }
+
+    fn decision(&self, pair: Pair<Rule>) -> Result<Decision, ParseError> {
+        let span = self.span(&pair);
+        let mut clauses = Vec::new();
+        let mut tokens = Vec::new();
+        let mut sink = None;
+        let mut platform = None;
+
+        for inner in pair.into_inner() {
+            match inner.as_rule() {
+                Rule::clause => clauses.push(self.clause(inner)?),
+                Rule::condition => tokens.push(self.condition(inner)?),
+                Rule::sink => sink = Some(self.sink(inner)?),
+                Rule::platform => platform = Some(self.platform(inner)?),
+                Rule::decide_keyword | Rule::terminator => {}
+                _ => return Err(self.unexpected(&inner, "decide")),
+            }
+        }
+
+        Ok(Decision {
+            clauses,
+            condition: decide::parse(&tokens, span)?,
+            sink,
+            platform,
+            span,
+        })
+    }
+
+    /// One piece of a condition, for the condition parser in [`decide`].
+    fn condition(&self, pair: Pair<Rule>) -> Result<Spanned<decide::Token>, ParseError> {
+        let span = self.span(&pair);
+        let part = pair
+            .into_inner()
+            .next()
+            .ok_or_else(|| self.error_at(span.start, "Empty operand"))?;
+        let token = match part.as_rule() {
+            Rule::operator => decide::Token::Operator(part.as_str().to_string()),
+            Rule::text_operator => decide::Token::Operator(
+                part.as_str()
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+            Rule::parenthesis if part.as_str() == "(" => decide::Token::Open,
+            Rule::parenthesis => decide::Token::Close,
+            Rule::schema_ref => decide::Token::Schema(part.as_str()[1..].to_string()),
+            Rule::is_keyword => decide::Token::Operand(Instruction::Word("is".into())),
+            _ => decide::Token::Operand(self.instruction(part)?.node),
+        };
+        Ok(Spanned::new(token, span))
+    }
+
+    fn conditional(&self, pair: Pair<Rule>) -> Result<Conditional, ParseError> {
+        let span = self.span(&pair);
+        let mut guard = None;
+        let mut blocks = Vec::new();
+
+        for inner in pair.into_inner() {
+            match inner.as_rule() {
+                Rule::guard => guard = Some(self.guard("if", inner)?),
+                Rule::block => blocks.push(self.block(inner)?),
+                Rule::if_keyword | Rule::else_keyword | Rule::end_keyword => {}
+                _ => return Err(self.unexpected(&inner, "if block")),
+            }
+        }
+
+        let guard = guard.ok_or_else(|| self.error_at(span.start, "if has no condition"))?;
+        let mut blocks = blocks.into_iter();
+        Ok(Conditional {
+            guard,
+            then: blocks.next().unwrap_or_default(),
+            otherwise: blocks.next(),
+            span,
+        })
+    }
+
+    /// The line after `keyword`: flags with an optional quantifier, or `decide <condition>`.
+    fn guard(&self, keyword: &str, pair: Pair<Rule>) -> Result<Spanned<Guard>, ParseError> {
+        let span = self.span(&pair);
+        let mut quantifier = None;
+        let mut tokens = Vec::new();
+        for inner in pair.into_inner() {
+            match inner.as_rule() {
+                Rule::quantifier => quantifier = Some(inner.as_str()),
+                Rule::condition => tokens.push(self.condition(inner)?),
+                _ => return Err(self.unexpected(&inner, "condition")),
+            }
+        }
+        let guard = match quantifier {
+            Some("decide") => Guard::Decide(decide::parse(&tokens, span)?),
+            other => flow::flags(
+                keyword,
+                other
+                    .and_then(Quantifier::from_word)
+                    .unwrap_or(Quantifier::One),
+                &tokens,
+                span,
+            )?,
+        };
+        Ok(Spanned::new(guard, span))
+    }
+
+    fn repetition(&self, pair: Pair<Rule>) -> Result<Loop, ParseError> {
+        let span = self.span(&pair);
+        let mut kind = None;
+        let mut body = Vec::new();
+
+        for inner in pair.into_inner() {
+            match inner.as_rule() {
+                Rule::repeat_header
+                | Rule::while_header
+                | Rule::count_header
+                | Rule::foreach_header => kind = Some(self.loop_kind(inner)?),
+                Rule::block => body = self.block(inner)?,
+                Rule::end_keyword => {}
+                _ => return Err(self.unexpected(&inner, "loop")),
+            }
+        }
+
+        let kind = kind.ok_or_else(|| self.error_at(span.start, "Loop has no header"))?;
+        Ok(Loop { kind, body, span })
+    }
+
+    /// The first line of a loop, which says how often its block runs.
+    fn loop_kind(&self, pair: Pair<Rule>) -> Result<LoopKind, ParseError> {
+        let rule = pair.as_rule();
+        let span = self.span(&pair);
+        let mut direction = Direction::Increase;
+        let mut guard = None;
+        let mut operands = Vec::new();
+
+        for inner in pair.into_inner() {
+            match inner.as_rule() {
+                Rule::guard => guard = Some(self.guard("while", inner)?),
+                Rule::direction if inner.as_str() == "decrease" => direction = Direction::Decrease,
+                Rule::repeat_keyword
+                | Rule::while_keyword
+                | Rule::direction
+                | Rule::by_keyword
+                | Rule::to_keyword
+                | Rule::foreach_keyword
+                | Rule::from_keyword
+                | Rule::into_keyword => {}
+                _ => operands.push(self.instruction(inner)?),
+            }
+        }
+
+        let mut operands = operands.into_iter();
+        let mut operand = || {
+            operands
+                .next()
+                .ok_or_else(|| self.error_at(span.end, "Loop header is incomplete"))
+        };
+        let reference = |operand: Spanned<Instruction>| match operand.node {
+            Instruction::Reference(reference) => Ok(Spanned::new(reference, operand.span)),
+            _ => Err(self.error_at(operand.span.start, "Expected a record or container")),
+        };
+        Ok(match rule {
+            Rule::repeat_header => LoopKind::Repeat(operand()?),
+            Rule::while_header => LoopKind::While(
+                guard.ok_or_else(|| self.error_at(span.end, "while has no condition"))?,
+            ),
+            Rule::count_header => LoopKind::Count {
+                direction,
+                counter: reference(operand()?)?,
+                step: operand()?,
+                limit: operand()?,
+            },
+            _ => LoopKind::Foreach {
+                source: reference(operand()?)?,
+                item: reference(operand()?)?,
+            },
+        })
+    }
+
+    fn function(&self, pair: Pair<Rule>) -> Result<Function, ParseError> {
+        let span = self.span(&pair);
+        let mut name = None;
+        let mut body = Vec::new();
+
+        for inner in pair.into_inner() {
+            match inner.as_rule() {
+                Rule::identifier => {
+                    name = Some(Spanned::new(inner.as_str().to_string(), self.span(&inner)))
+                }
+                Rule::block => body = self.block(inner)?,
+                Rule::function_keyword | Rule::end_keyword => {}
+                _ => return Err(self.unexpected(&inner, "function")),
+            }
+        }
+
+        let name = name.ok_or_else(|| self.error_at(span.start, "Function has no name"))?;
+        if INTRINSIC_KEYWORDS.contains(&name.node.as_str()) {
+            let mut error = ParseError::new(
+                function::DUPLICATE,
+                format!("'{}' is an intrinsic and cannot be redefined", name.node),
+                name.span,
+            );
+            error.expected.push("another function name".to_string());
+            return Err(error);
+        }
+        let (parameters, body) = function::signature(body)?;
+        Ok(Function {
+            name,
+            parameters,
+            body,
+            span,
+        })
+    }
+
+    /// The statements of a block; functions are only defined at the top of a spell.
+    fn block(&self, pair: Pair<Rule>) -> Result<Vec<Statement>, ParseError> {
+        pair.into_inner()
+            .map(|statement| match self.statement(statement)? {
+                Statement::Function(function) => {
+                    let mut error = ParseError::new(
+                        function::NESTED,
+                        format!("'{}' is defined inside a block", function.name.node),
+                        function.name.span,
+                    );
+                    error
+                        .expected
+                        .push("functions defined at the top level of the spell".to_string());
+                    Err(error)
+                }
+                statement => Ok(statement),
+            })
+            .collect()
+    }
+
+    fn sink(&self, pair: Pair<Rule>) -> Result<Sink, ParseError> {
+        let span = self.span(&pair);
+        let mut inner = pair.into_inner();
+        let mode = match inner.next().map(|mode| mode.as_str()) {
+            Some("update") => SinkMode::Update,
+            Some("save") => SinkMode::Save,
+            _ => SinkMode::Into,
+        };
+        let target = inner
+            .next()
+            .ok_or_else(|| self.error_at(span.end, "Sink has no target"))?;
+        let target_span = self.span(&target);
+        Ok(Sink {
+            mode,
+            target: Spanned::new(self.reference(target)?, target_span),
+            span,
+        })
+    }
+
+    /// The schema name of `is &name`, without its `&`.
+    fn validation(&self, pair: Pair<Rule>) -> Result<Spanned<String>, ParseError> {
+        let span = self.span(&pair);
+        let schema = pair
+            .into_inner()
+            .find(|part| part.as_rule() == Rule::schema_ref)
+            .ok_or_else(|| self.error_at(span.end, "Validation has no schema"))?;
+        let name = schema.as_str().trim_start_matches('&').to_string();
+        Ok(Spanned::new(name, self.span(&schema)))
+    }
+
+    fn lens(&self, pair: Pair<Rule>) -> Result<Spanned<Lens>, ParseError> {
+        let span = self.span(&pair);
+        let name = pair
+            .into_inner()
+            .find(|part| part.as_rule() == Rule::lens_name)
+            .ok_or_else(|| self.error_at(span.end, "Lens has no name"))?;
+        let lens = name
+            .as_str()
+            .parse()
+            .map_err(|_| self.unexpected(&name, "lens"))?;
+        Ok(Spanned::new(lens, span))
+    }
+
+    fn clause(&self, pair: Pair<Rule>) -> Result<Spanned<Clause>, ParseError> {
+        let span = self.span(&pair);
+        let inner = pair
+            .into_inner()
+            .next()
+            .ok_or_else(|| self.error_at(span.start, "Empty clause"))?;
+        let clause = match inner.as_rule() {
+            Rule::safe => Clause::Safe,
+            Rule::sensitive => Clause::Sensitive,
+            Rule::sensitive_egress => Clause::SensitiveEgress,
+            Rule::elevated => Clause::Elevated,
+            Rule::async_clause => {
+                let tag = inner
+                    .into_inner()
+                    .find(|part| part.as_rule() == Rule::string_literal)
+                    .map(|literal| self.string_literal(literal))
+                    .transpose()?;
+                Clause::Async { tag }
+            }
+            _ => return Err(self.unexpected(&inner, "clause")),
+        };
+        Ok(Spanned::new(clause, span))
+    }
+
+    fn instruction(&self, pair: Pair<Rule>) -> Result<Spanned<Instruction>, ParseError> {
+        let inner = match pair.as_rule() {
+            Rule::instruction => {
+                let span = self.span(&pair);
+                pair.into_inner()
+                    .next()
+                    .ok_or_else(|| self.error_at(span.start, "Empty instruction"))?
+            }
+            _ => pair,
+        };
+        let span = self.span(&inner);
+        let instruction = match inner.as_rule() {
+            Rule::word => Instruction::Word(inner.as_str().to_string()),
+            Rule::string_literal => self.text(inner)?,
+            Rule::integer_literal => Instruction::Integer(inner.as_str().to_string()),
+            Rule::decimal_literal => Instruction::Decimal(inner.as_str().to_string()),
+            Rule::boolean_literal => Instruction::Boolean(inner.as_str() == "true"),
+            Rule::null_literal => Instruction::Null,
+            Rule::record_ref | Rule::container_ref => {
+                Instruction::Reference(self.reference(inner)?)
+            }
+            _ => return Err(self.unexpected(&inner, "instruction")),
+        };
+        Ok(Spanned::new(instruction, span))
+    }
+
+    fn reference(&self, pair: Pair<Rule>) -> Result<Reference, ParseError> {
+        let is_record = pair.as_rule() == Rule::record_ref;
+        let mut sigil = Sigil::Plain;
+        let mut name = String::new();
+        let mut path = Vec::new();
+        let mut prefix = None;
+
+        for inner in pair.into_inner() {
+            match inner.as_rule() {
+                Rule::seal_prefix => prefix = Some(self.span(&inner)),
+                Rule::sigils => sigil = self.sigil(&inner)?,
+                Rule::identifier => name = inner.as_str().to_string(),
+                Rule::field_projection | Rule::index_projection => {
+                    path.push(self.projection(inner)?)
+                }
+                _ => return Err(self.unexpected(&inner, "reference")),
+            }
+        }
+
+        if let Some(prefix) = prefix {
+            if sigil != Sigil::Plain {
+                let mut error = ParseError::new(
+                    ParseError::SIGIL,
+                    format!("'!::{}{}' is marked twice", sigil.as_str(), name),
+                    prefix,
+                );
+                error.expected.push(format!(
+                    "one sigil: '!::{}' or '::{}{}'",
+                    name,
+                    sigil.as_str(),
+                    name
+                ));
+                return Err(error);
+            }
+            sigil = Sigil::Sealed;
+        }
+
+        Ok(if is_record {
+            Reference::Record { name, sigil }
+        } else {
+            Reference::Container { name, sigil, path }
+        })
+    }
+
+    fn projection(&self, pair: Pair<Rule>) -> Result<Projection, ParseError> {
+        let span = self.span(&pair);
+        let is_field = pair.as_rule() == Rule::field_projection;
+        let mut optional = false;
+        let mut target = "";
+
+        for inner in pair.into_inner() {
+            match inner.as_rule() {
+                Rule::optional => optional = true,
+                Rule::identifier | Rule::index => target = inner.as_str(),
+                _ => return Err(self.unexpected(&inner, "projection")),
+            }
+        }
+
+        if is_field {
+            return Ok(Projection::Field {
+                name: target.to_string(),
+                optional,
+            });
+        }
+        let index = target.parse().map_err(|_| {
+            ParseError::new(
+                ParseError::PROJECTION,
+                format!("Index '{}' is too large", target),
+                span,
+            )
+        })?;
+        Ok(Projection::Index { index, optional })
+    }
+
+    fn sigil(&self, pair: &Pair<Rule>) -> Result<Sigil, ParseError> {
+        match pair.as_str() {
+            "!" => Ok(Sigil::Sealed),
+            "?" => Ok(Sigil::Nullable),
+            sigils => {
+                let mut error = ParseError::new(
+                    ParseError::SIGIL,
+                    format!("Invalid sigil '{}'", sigils),
+                    self.span(pair),
+                );
+                error
+                    .expected
+                    .push("either '!' (sealed) or '?' (nullable), not both".to_string());
+                Err(error)
+            }
+        }
+    }
+
+    fn modifier(&self, pair: Pair<Rule>) -> Result<Modifier, ParseError> {
+        let span = self.span(&pair);
+        let mut inner = pair.into_inner();
+
+        let mode = match inner.next().map(|mode| mode.as_str()) {
+            Some("with") => ModifierMode::With,
+            _ => ModifierMode::Without,
+        };
+        let name = inner
+            .next()
+            .ok_or_else(|| self.error_at(span.end, "Modifier has no name"))?;
+        let name = Spanned::new(name.as_str().to_string(), self.span(&name));
+        let value = inner
+            .next()
+            .map(|value| self.instruction(value))
+            .transpose()?;
+
+        Ok(Modifier {
+            mode,
+            name,
+            value,
+            span,
+        })
+    }
+
+    fn platform(&self, pair: Pair<Rule>) -> Result<Spanned<Platform>, ParseError> {
+        let span = self.span(&pair);
+        let name = pair
+            .into_inner()
+            .find(|part| part.as_rule() == Rule::platform_name)
+            .ok_or_else(|| self.error_at(span.end, "Platform selector has no platform"))?;
+        let platform = Platform::from_name(name.as_str())
+            .ok_or_else(|| self.unexpected(&name, "platform selector"))?;
+        Ok(Spanned::new(platform, span))
+    }
+
+    fn string_literal(&self, pair: Pair<Rule>) -> Result<String, ParseError> {
+        Ok(self.lex_literal(pair)?.value)
+    }
+
+    /// A quoted literal: plain text, or a template when it interpolates references.
+    fn text(&self, pair: Pair<Rule>) -> Result<Instruction, ParseError> {
+        let start = self.span(&pair).start;
+        let literal = self.lex_literal(pair)?;
+        if !literal.is_template() {
+            return Ok(Instruction::Text(literal.value));
+        }
+
+        let segments = literal
+            .parts
+            .into_iter()
+            .map(|part| match part {
+                Part::Text(text) => Segment::Text(text),
+                Part::Reference {
+                    reference,
+                    start: from,
+                    end,
+                } => Segment::Interpolation(Spanned::new(
+                    reference,
+                    self.lines.span(self.source, start + from, start + end),
+                )),
+            })
+            .collect();
+        Ok(Instruction::Template(Template {
+            segments,
+            raw: literal.value,
+        }))
+    }
+
+    fn lex_literal(&self, pair: Pair<Rule>) -> Result<Literal, ParseError> {
+        if pair.as_rule() != Rule::string_literal {
+            return Err(self.unexpected(&pair, "string literal"));
+        }
+
+        let start = self.span(&pair).start;
+        let literal = literal::lex(pair.as_str(), self.options.literals);
+        match literal.errors.first() {
+            Some(error) => Err(ParseError::new(
+                error.code,
+                error.message.clone(),
+                self.lines
+                    .span(self.source, start + error.start, start + error.end),
+            )),
+            None => Ok(literal),
+        }
+    }
 }
 
-fn parse_command(pair: Pair<Rule>) -> Result<Command, Box<dyn std::error::Error>> {
-    match pair.as_rule() {
-        Rule::command => {
-            // Get the say_command inside
-            let say_command = pair.into_inner().next().ok_or("No say command found")?;
-            parse_say_command(say_command)
+/// Skips whitespace and line comments, returning the offset of the next token.
+fn skip_trivia(source: &str, comments: &[Comment], from: usize) -> usize {
+    let mut offset = from;
+    loop {
+        let rest = &source[offset..];
+        offset += rest.len() - rest.trim_start().len();
+        match comment_at(comments, offset) {
+            Some(comment) => offset = comment.span.end,
+            None => return offset,
         }
-        _ => Err(format!("Expected command, found {:?}", pair.as_rule()).into()),
     }
 }
 
-fn parse_say_command(pair: Pair<Rule>) -> Result<Command, Box<dyn std::error::Error>> {
-    match pair.as_rule() {
-        Rule::say_command => {
-            let mut inner = pair.into_inner();
-            
-            // Skip "say" keyword, get string_literal
-            let string_literal = inner.next().ok_or("No string literal found")?;
-            let text = parse_string_literal(string_literal)?;
-            
-            Ok(Command::Say { text })
+/// Offset of the first literal from `from` that has no closing quote.
+fn unterminated_literal(source: &str, comments: &[Comment], from: usize) -> Option<usize> {
+    let mut offset = from;
+    while let Some(position) = source[offset..].find(['\'', '~']) {
+        let start = offset + position;
+        offset = match comment_at(comments, start) {
+            Some(comment) => comment.span.end,
+            None if source[start..].starts_with('~') => start + 1,
+            None => {
+                let literal = literal::lex(&source[start..], LiteralOptions::default());
+                if !literal.terminated {
+                    return Some(start);
+                }
+                start + literal.length
+            }
+        };
+    }
+    None
+}
+
+fn comment_at(comments: &[Comment], offset: usize) -> Option<&Comment> {
+    comments
+        .binary_search_by_key(&offset, |comment| comment.span.start)
+        .ok()
+        .map(|index| &comments[index])
+}
+
+/// Where parsing resumes after a broken statement: just past the next `.` terminator
+/// or the next line starting with `end`, ignoring both inside literals and comments.
+/// A broken block is skipped whole, up to the `end` that closes it.
+fn resync(source: &str, comments: &[Comment], from: usize) -> usize {
+    let mut in_literal = false;
+    let mut line_start = true;
+    // Blocks opened since `from`; their terminators do not end the broken statement.
+    let mut depth = 0;
+    let mut chars = source[from..].char_indices().peekable();
+
+    while let Some((index, c)) = chars.next() {
+        let position = from + index;
+        match c {
+            '\\' if in_literal => {
+                chars.next();
+            }
+            '\'' => in_literal = !in_literal,
+            _ if in_literal => {}
+            '~' => {
+                if let Some(comment) = comment_at(comments, position) {
+                    let end = comment.span.end - from;
+                    while chars.next_if(|&(index, _)| index < end).is_some() {}
+                }
+            }
+            // A dot followed by a digit is a decimal point, not a terminator.
+            '.' if depth == 0 && !chars.peek().is_some_and(|&(_, next)| next.is_ascii_digit()) => {
+                return position + 1;
+            }
+            'e' if line_start && is_keyword_at(&source[position..], "end") => {
+                if depth <= 1 {
+                    return position + "end".len();
+                }
+                depth -= 1;
+            }
+            _ if line_start
+                && BLOCK_KEYWORDS
+                    .iter()
+                    .any(|keyword| is_keyword_at(&source[position..], keyword)) =>
+            {
+                depth += 1;
+            }
+            _ => {}
         }
-        _ => Err(format!("Expected say_command, found {:?}", pair.as_rule()).into()),
+        line_start = c == '\n' || (line_start && (c == ' ' || c == '\t'));
     }
+
+    source.len()
 }
 
-fn parse_string_literal(pair: Pair<Rule>) -> Result<String, Box<dyn std::error::Error>> {
-    match pair.as_rule() {
-        Rule::string_literal => {
-            let content = pair.as_str();
-            // Remove surrounding quotes and handle basic escape sequences
-            let without_quotes = &content[1..content.len()-1];
-            let unescaped = without_quotes.replace("\\'", "'").replace("\\\\", "\\");
-            Ok(unescaped)
+/// Keywords that open a block closed by `end`.
+const BLOCK_KEYWORDS: [&str; 7] = [
+    "if", "repeat", "while", "increase", "decrease", "foreach", "function",
+];
+
+fn is_keyword_at(rest: &str, keyword: &str) -> bool {
+    rest.strip_prefix(keyword)
+        .is_some_and(|after| !after.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_'))
+}
+
+/// Hands the fields of the innermost open level to the bare field that opened it.
+fn close_level(levels: &mut Vec<(Option<usize>, Vec<Field>)>) {
+    if let Some((_, children)) = levels.pop()
+        && let Some(parent) = levels.last_mut().and_then(|level| level.1.last_mut())
+    {
+        parent.node = FieldNode::Container(children);
+    }
+}
+
+/// Rejects duplicate field names, and seals repeated under `sealed_by`, the sealed
+/// container or field the level belongs to.
+fn check_fields(fields: &[Field], sealed_by: Option<(String, Span)>) -> Result<(), ParseError> {
+    for (index, field) in fields.iter().enumerate() {
+        if let Some(first) = fields[..index]
+            .iter()
+            .find(|other| other.name.node == field.name.node)
+        {
+            let mut error = ParseError::new(
+                container::DUPLICATE_FIELD,
+                format!("Field ':{}' is defined twice", field.name.node),
+                field.name.span,
+            );
+            error.expected.push(format!(
+                "a new field name; ':{}' is already defined at line {}",
+                first.name.node, first.name.span.line
+            ));
+            return Err(error);
+        }
+
+        if field.sigil.is_sealed()
+            && let Some((parent, span)) = &sealed_by
+        {
+            let mut error = ParseError::new(
+                container::SEAL_INHERITED,
+                format!(
+                    "Field ':{}' is already sealed by '{}'",
+                    field.name.node, parent
+                ),
+                field.name.span,
+            );
+            error.expected.push(format!(
+                "':{}' without '!'; the seal comes from line {}",
+                field.name.node, span.line
+            ));
+            return Err(error);
+        }
+
+        if let FieldNode::Container(children) = &field.node {
+            let sealed_by = sealed_by.clone().or_else(|| {
+                field
+                    .sigil
+                    .is_sealed()
+                    .then(|| (format!(":!{}", field.name.node), field.name.span))
+            });
+            check_fields(children, sealed_by)?;
         }
-        _ => Err(format!("Expected string_literal, found {:?}", pair.as_rule()).into()),
     }
+    Ok(())
 }
 
-pub fn execute(command: Command) -> Result<(), Box<dyn std::error::Error>> {
-    match command {
-        Command::Say { text } => {
-            println!("{}", text);
-            Ok(())
+/// Human-readable name of a grammar rule, used in expected-token sets.
+fn describe_rule(rule: Rule) -> &'static str {
+    match rule {
+        Rule::EOI => "end of input",
+        Rule::statement | Rule::command => "command",
+        Rule::assignment => "value to store",
+        Rule::calculation | Rule::calculate_keyword => "calculation",
+        Rule::arithmetic => "number, record or operator",
+        Rule::operator => "operator (+ - * / % ** //)",
+        Rule::parenthesis => "parenthesis",
+        Rule::decision | Rule::decide_keyword => "decision",
+        Rule::condition => "value, comparison or and/or/xor/not",
+        Rule::text_operator => "text comparison",
+        Rule::conditional | Rule::if_keyword => "if block",
+        Rule::else_keyword => "else",
+        Rule::end_keyword => "end",
+        Rule::guard => "condition",
+        Rule::repetition
+        | Rule::repeat_header
+        | Rule::while_header
+        | Rule::count_header
+        | Rule::foreach_header => "loop",
+        Rule::function | Rule::function_keyword => "function",
+        Rule::repeat_keyword => "repeat",
+        Rule::while_keyword => "while",
+        Rule::direction => "increase or decrease",
+        Rule::by_keyword => "by",
+        Rule::to_keyword => "to",
+        Rule::foreach_keyword => "foreach",
+        Rule::from_keyword => "from",
+        Rule::into_keyword => "into",
+        Rule::quantifier => "any, all, none, exclusive or decide",
+        Rule::block => "statement",
+        Rule::clause
+        | Rule::safe
+        | Rule::sensitive
+        | Rule::sensitive_egress
+        | Rule::elevated
+        | Rule::async_clause
+        | Rule::async_keyword => "clause",
+        Rule::instruction => "instruction",
+        Rule::modifier | Rule::modifier_mode => "modifier ('with'/'without')",
+        Rule::platform | Rule::platform_keyword => "platform selector ('on')",
+        Rule::platform_name => "platform ('linux', 'mac' or 'windows')",
+        Rule::word | Rule::keyword => "word",
+        Rule::string_literal | Rule::escape => "string literal",
+        Rule::literal => "literal",
+        Rule::integer_literal | Rule::decimal_literal => "number",
+        Rule::boolean_literal => "boolean",
+        Rule::null_literal => "null",
+        Rule::reference | Rule::record_ref => "record (@name)",
+        Rule::container_ref => "container (::name)",
+        Rule::sigils | Rule::seal_prefix => "sigil ('!' or '?')",
+        Rule::identifier => "name",
+        Rule::field_projection => "field projection (:field)",
+        Rule::index_projection | Rule::index => "index projection (#0)",
+        Rule::optional => "safe access '?'",
+        Rule::container => "container block",
+        Rule::container_keyword => "'container'",
+        Rule::sink | Rule::sink_mode => "sink ('into', 'update' or 'save')",
+        Rule::validation | Rule::is_keyword | Rule::schema_ref => "schema check (is &schema)",
+        Rule::lens | Rule::lens_keyword | Rule::lens_name => {
+            "lens ('as integer', 'as decimal' or 'as bool')"
         }
+        Rule::field_entry | Rule::field_key => "field (:name)",
+        Rule::terminator => "terminator '.'",
+        _ => "input",
     }
-}
\ No newline at end of file
+}
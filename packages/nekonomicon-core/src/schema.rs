@@ -0,0 +1,200 @@
+//! Schemas checked by `is &schema`: the built-in `&integer`, `&decimal`, `&bool`
+//! and `&string`, plus container shapes an embedder defines.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::diagnostic::Diagnostic;
+use crate::span::Spanned;
+use crate::value::Value;
+
+pub const UNKNOWN: &str = "E-SCHEMA-UNKNOWN";
+pub const MISMATCH: &str = "E-SCHEMA-MISMATCH";
+
+/// The shape a value must have.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Schema {
+    /// A whole number, typed or written as text.
+    Integer,
+    /// Any number, typed or written as text.
+    Decimal,
+    /// `true` or `false`.
+    Bool,
+    /// Any scalar but null.
+    String,
+    /// A container with these fields.
+    Fields(Vec<SchemaField>),
+}
+
+/// `field 'name' is &schema`, or `field :?name` when it may be missing or null.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaField {
+    pub name: String,
+    pub schema: Schema,
+    pub optional: bool,
+}
+
+impl Schema {
+    pub fn builtin(name: &str) -> Option<Schema> {
+        match name {
+            "integer" => Some(Schema::Integer),
+            "decimal" => Some(Schema::Decimal),
+            "bool" | "boolean" => Some(Schema::Bool),
+            "string" | "text" => Some(Schema::String),
+            _ => None,
+        }
+    }
+
+    /// Why `value` does not match, or `None` when it does.
+    pub fn mismatch(&self, value: &Value) -> Option<String> {
+        let number = |value: &Value| match value {
+            Value::Text(text) => Value::number(text),
+            other => Some(other.clone()),
+        };
+        let matches = match self {
+            Schema::Integer => matches!(number(value), Some(Value::Integer(_))),
+            Schema::Decimal => matches!(number(value), Some(Value::Integer(_) | Value::Decimal(_))),
+            Schema::Bool => match value {
+                Value::Boolean(_) => true,
+                Value::Text(text) => text == "true" || text == "false",
+                _ => false,
+            },
+            Schema::String => value.is_scalar() && !value.is_null(),
+            Schema::Fields(fields) => {
+                if !matches!(value, Value::Map(_)) {
+                    return Some(format!("{} is not a container", value.described()));
+                }
+                return fields.iter().find_map(|field| {
+                    match value.field(&field.name).filter(|value| !value.is_null()) {
+                        None if field.optional => None,
+                        None => Some(format!("field '{}' is missing", field.name)),
+                        Some(value) => field
+                            .schema
+                            .mismatch(value)
+                            .map(|reason| format!("field '{}': {}", field.name, reason)),
+                    }
+                });
+            }
+        };
+        (!matches).then(|| format!("'{}' is not {}", value, self))
+    }
+}
+
+impl fmt::Display for Schema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Schema::Integer => write!(f, "an integer"),
+            Schema::Decimal => write!(f, "a number"),
+            Schema::Bool => write!(f, "a boolean"),
+            Schema::String => write!(f, "a string"),
+            Schema::Fields(_) => write!(f, "a container of this shape"),
+        }
+    }
+}
+
+/// Every schema a spell can name: the built-ins and those defined on top of them.
+#[derive(Debug, Clone, Default)]
+pub struct Schemas {
+    defined: HashMap<String, Schema>,
+}
+
+impl Schemas {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes `&name` available, shadowing a built-in of the same name.
+    pub fn define(&mut self, name: impl Into<String>, schema: Schema) {
+        self.defined.insert(name.into(), schema);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Schema> {
+        self.defined
+            .get(name)
+            .cloned()
+            .or_else(|| Schema::builtin(name))
+    }
+
+    /// Checks `value` against `&name`, failing at the schema reference.
+    pub fn check(&self, name: &Spanned<String>, value: &Value) -> Result<(), Diagnostic> {
+        let schema = self.get(&name.node).ok_or_else(|| {
+            Diagnostic::error(UNKNOWN, format!("Schema '&{}' is not defined", name.node))
+                .with_span(name.span)
+                .with_label(name.span, "unknown schema")
+                .with_help("Built-in schemas are &integer, &decimal, &bool and &string.")
+        })?;
+        match schema.mismatch(value) {
+            None => Ok(()),
+            Some(reason) => Err(Diagnostic::error(
+                MISMATCH,
+                format!("Value does not match '&{}'", name.node),
+            )
+            .with_span(name.span)
+            .with_label(name.span, reason)
+            .with_note("Nothing was stored: the check runs before the sink.")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::span::Span;
+
+    fn check(schemas: &Schemas, name: &str, value: Value) -> Result<(), Diagnostic> {
+        schemas.check(&Spanned::new(name.to_string(), Span::default()), &value)
+    }
+
+    #[test]
+    fn test_builtin_schemas() {
+        let schemas = Schemas::new();
+        assert!(check(&schemas, "integer", Value::from("42")).is_ok());
+        assert!(check(&schemas, "integer", Value::Integer(-7)).is_ok());
+        assert!(check(&schemas, "decimal", Value::from("4.5")).is_ok());
+        assert!(check(&schemas, "bool", Value::from("false")).is_ok());
+        assert!(check(&schemas, "string", Value::from("")).is_ok());
+
+        for (name, value) in [
+            ("integer", Value::from("4.5")),
+            ("integer", Value::from("abc")),
+            ("bool", Value::from("yes")),
+            ("string", Value::Null),
+        ] {
+            assert_eq!(check(&schemas, name, value).unwrap_err().code, MISMATCH);
+        }
+        assert_eq!(
+            check(&schemas, "User", Value::from("x")).unwrap_err().code,
+            UNKNOWN
+        );
+    }
+
+    #[test]
+    fn test_container_schema_names_the_failing_field() {
+        let mut schemas = Schemas::new();
+        let field = |name: &str, schema, optional| SchemaField {
+            name: name.to_string(),
+            schema,
+            optional,
+        };
+        schemas.define(
+            "User",
+            Schema::Fields(vec![
+                field("name", Schema::String, false),
+                field("age", Schema::Integer, false),
+                field("email", Schema::String, true),
+            ]),
+        );
+        let user = |age: &str| {
+            Value::Map(vec![
+                ("name".to_string(), Value::from("Alice")),
+                ("age".to_string(), Value::from(age)),
+            ])
+        };
+        assert!(check(&schemas, "User", user("30")).is_ok());
+        let error = check(&schemas, "User", user("thirty")).unwrap_err();
+        assert_eq!(
+            error.labels[0].message,
+            "field 'age': 'thirty' is not an integer"
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use std::ops::Deref;
+
+/// A region of the source: byte offsets plus the 1-based line/column of its start.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Smallest span covering both `self` and `other`.
+    pub fn join(self, other: Span) -> Span {
+        if other.start < self.start {
+            Span {
+                end: self.end.max(other.end),
+                ..other
+            }
+        } else {
+            Span {
+                end: self.end.max(other.end),
+                ..self
+            }
+        }
+    }
+}
+
+/// An AST value together with where it was written.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spanned<T> {
+    pub node: T,
+    pub span: Span,
+}
+
+impl<T> Spanned<T> {
+    pub fn new(node: T, span: Span) -> Self {
+        Spanned { node, span }
+    }
+}
+
+impl<T> Deref for Spanned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.node
+    }
+}
+
+/// Start offsets of every line, used to turn byte offsets into line/column pairs.
+#[derive(Debug, Clone)]
+pub struct LineIndex {
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(source: &str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(index, _)| index + 1))
+            .collect();
+        LineIndex { line_starts }
+    }
+
+    /// 1-based line and column (in characters) of a byte offset.
+    pub fn line_col(&self, source: &str, offset: usize) -> (usize, usize) {
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let line_start = self.line_starts[line - 1];
+        let column = source[line_start..offset.min(source.len())].chars().count() + 1;
+        (line, column)
+    }
+
+    pub fn span(&self, source: &str, start: usize, end: usize) -> Span {
+        let (line, column) = self.line_col(source, start);
+        Span {
+            start,
+            end,
+            line,
+            column,
+        }
+    }
+
+    /// Byte range of a 1-based line, without its newline.
+    pub fn line_range(&self, source: &str, line: usize) -> (usize, usize) {
+        let start = self.line_starts[line - 1];
+        let end = self
+            .line_starts
+            .get(line)
+            .map(|next| next - 1)
+            .unwrap_or(source.len());
+        (start, end.max(start))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_col() {
+        let source = "say 'a'.\n\nsay 'é'. say 'b'.";
+        let index = LineIndex::new(source);
+        assert_eq!(index.line_col(source, 0), (1, 1));
+        assert_eq!(index.line_col(source, 9), (2, 1));
+        assert_eq!(index.line_col(source, 10), (3, 1));
+        // 'é' is two bytes but one column.
+        let second = source.rfind("say").unwrap();
+        assert_eq!(index.line_col(source, second), (3, 10));
+    }
+
+    #[test]
+    fn test_line_range() {
+        let source = "first\nsecond\n";
+        let index = LineIndex::new(source);
+        assert_eq!(
+            &source[index.line_range(source, 1).0..index.line_range(source, 1).1],
+            "first"
+        );
+        assert_eq!(index.line_range(source, 2), (6, 12));
+        assert_eq!(index.line_range(source, 3), (13, 13));
+    }
+}
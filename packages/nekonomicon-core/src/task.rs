@@ -0,0 +1,280 @@
+//! Async tasks: what `async` may launch, and how `wait` and `stop` read their arguments.
+//!
+//! `async ['tag'] <command>.` hands a command to the spell's scheduler and moves on.
+//! Tasks start once the spell pauses, and run alongside it and each other, taking
+//! turns at every `wait`: `wait 'tag'` and `wait all` pause until their groups have
+//! ended, `wait any` until one more of their tasks is done. `stop 'tag'` cancels the
+//! tasks of a group that have not ended.
+//!
+//! A task sees the bindings of the spell as they were when it was launched, and
+//! what it binds stays its own: its result reaches the spell through
+//! `wait ... into`. A failure is raised by the first `wait` that covers the task,
+//! or at the end of the spell; under `safe async` it is only warned about.
+
+use std::time::Duration;
+
+use crate::ast::{Clause, Command, CommandKind, Instruction};
+use crate::diagnostic::Diagnostic;
+use crate::error::ParseError;
+use crate::number::Number;
+use crate::span::{Span, Spanned};
+use crate::value::Value;
+
+pub const COMMAND: &str = "E-ASYNC-COMMAND";
+pub const SINK: &str = "E-ASYNC-SINK";
+pub const TAG: &str = "E-ASYNC-TAG";
+pub const SELF_WAIT: &str = "E-ASYNC-SELF-WAIT";
+pub const ARGUMENTS: &str = "E-ASYNC-ARGUMENTS";
+pub const DURATION: &str = "E-ASYNC-DURATION";
+pub const CANCELLED: &str = "E-ASYNC-CANCELLED";
+pub const PANICKED: &str = "E-ASYNC-PANICKED";
+
+/// Intrinsics that only make sense in the flow of the spell itself.
+const FOREGROUND: [&str; 5] = ["success", "fail", "parameter", "wait", "stop"];
+
+/// The tag of an `async` clause: `Some(None)` for an untagged task, `None` when the
+/// statement does not run in the background.
+pub fn tag(clauses: &[Spanned<Clause>]) -> Option<Option<&str>> {
+    clauses.iter().find_map(|clause| match &clause.node {
+        Clause::Async { tag } => Some(tag.as_deref()),
+        _ => None,
+    })
+}
+
+/// Checks that `command` may run in the background: it must not steer the spell,
+/// and its result is collected by `wait`, not a sink of its own.
+pub fn launchable(command: &Command) -> Result<(), ParseError> {
+    let Some(clause) = command
+        .clauses
+        .iter()
+        .find(|clause| matches!(clause.node, Clause::Async { .. }))
+    else {
+        return Ok(());
+    };
+    if command.kind == CommandKind::Intrinsic && FOREGROUND.contains(&command.name.as_str()) {
+        let mut error = ParseError::new(
+            COMMAND,
+            format!("'{}' cannot run in the background", command.name.node),
+            clause.span,
+        );
+        error
+            .expected
+            .push("a module command or a function call after 'async'".to_string());
+        return Err(error);
+    }
+    if let Some(sink) = &command.sink {
+        let mut error = ParseError::new(
+            SINK,
+            format!(
+                "An async command cannot store its result with '{}'",
+                sink.mode.as_str()
+            ),
+            sink.span,
+        );
+        error
+            .expected
+            .push("the result collected with: wait 'tag' into ::results.".to_string());
+        return Err(error);
+    }
+    Ok(())
+}
+
+/// The error for `async` before a statement that is not a command.
+pub fn not_a_command(clauses: &[Spanned<Clause>]) -> Result<(), ParseError> {
+    match clauses
+        .iter()
+        .find(|clause| matches!(clause.node, Clause::Async { .. }))
+    {
+        Some(clause) => {
+            let mut error =
+                ParseError::new(COMMAND, "Only commands run in the background", clause.span);
+            error
+                .expected
+                .push("a module command or a function call after 'async'".to_string());
+            Err(error)
+        }
+        None => Ok(()),
+    }
+}
+
+/// What a `wait` waits for.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Wait {
+    /// `wait 5 seconds.`
+    Time {
+        amount: Spanned<Instruction>,
+        unit: Spanned<Instruction>,
+    },
+    /// `wait.`, `wait 'tag'.` or `wait all ['tag'...].`; no tags means every task.
+    All(Vec<Spanned<Instruction>>),
+    /// `wait any 'tag'...`
+    Any(Vec<Spanned<Instruction>>),
+}
+
+/// Reads the arguments of `wait`. A single value is a tag; two are an amount and
+/// a unit of time.
+pub fn wait(command: &Command) -> Result<Wait, Diagnostic> {
+    let instructions = command.instructions.clone();
+    match command.actions.as_slice() {
+        [] => match <[_; 2]>::try_from(instructions) {
+            Ok([amount, unit]) => Ok(Wait::Time { amount, unit }),
+            Err(instructions) if instructions.len() < 2 => Ok(Wait::All(instructions)),
+            Err(instructions) => Err(Diagnostic::error(
+                ARGUMENTS,
+                "'wait' takes one tag, or an amount and a unit of time",
+            )
+            .with_span(instructions[2].span)
+            .with_label(instructions[2].span, "unexpected")
+            .with_help("Wait for several groups with: wait all 'a' 'b'.")),
+        },
+        [action] if action.node == "all" => Ok(Wait::All(instructions)),
+        [action] if action.node == "any" && !instructions.is_empty() => Ok(Wait::Any(instructions)),
+        [action] if action.node == "any" => Err(Diagnostic::error(
+            ARGUMENTS,
+            "'wait any' needs the tags it races",
+        )
+        .with_span(action.span)
+        .with_help("Name them: wait any 'mirror'.")),
+        // `wait 5 seconds.` reads the unit as a bare word after the amount, but
+        // `wait seconds` has no amount.
+        [action, ..] => Err(Diagnostic::error(
+            ARGUMENTS,
+            format!("'wait' does not know '{}'", action.node),
+        )
+        .with_span(action.span)
+        .with_label(action.span, "expected 'all', 'any', a tag or an amount")
+        .with_help("Write: wait 'tag'., wait any 'a' 'b'., wait all. or wait 5 seconds.")),
+    }
+}
+
+/// The tags `stop` cancels: `stop 'tag'...`, or every group with `stop.` or `stop all.`
+pub fn stop(command: &Command) -> Result<&[Spanned<Instruction>], Diagnostic> {
+    match command.actions.as_slice() {
+        [] => Ok(&command.instructions),
+        [action] if action.node == "all" => Ok(&command.instructions),
+        [action, ..] => Err(Diagnostic::error(
+            ARGUMENTS,
+            format!("'stop' does not know '{}'", action.node),
+        )
+        .with_span(action.span)
+        .with_label(action.span, "expected 'all' or a tag")
+        .with_help("Write: stop 'tag'. or stop all.")),
+    }
+}
+
+/// How long `amount` of `unit` lasts: ms, seconds, minutes or hours.
+pub fn duration(amount: &Value, unit: &Value, span: Span) -> Result<Duration, Diagnostic> {
+    let error = |message: String| {
+        Diagnostic::error(DURATION, message)
+            .with_span(span)
+            .with_label(span, "not a usable duration")
+    };
+    let number = Number::from_value(amount)
+        .filter(|number| !number.is_negative())
+        .ok_or_else(|| {
+            error(format!(
+                "A wait lasts a number of units that is not negative, not {}",
+                amount.described()
+            ))
+        })?;
+    let unit = unit.text().unwrap_or_default();
+    let milliseconds = match unit.as_str() {
+        "ms" | "millisecond" | "milliseconds" => 1.0,
+        "s" | "second" | "seconds" => 1_000.0,
+        "minute" | "minutes" => 60_000.0,
+        "hour" | "hours" => 3_600_000.0,
+        _ => {
+            return Err(error(format!("'{}' is not a unit of time", unit))
+                .with_help("Use ms, seconds, minutes or hours."));
+        }
+    };
+    let amount: f64 = number.to_string().parse().unwrap_or(f64::NAN);
+    Duration::try_from_secs_f64(amount * milliseconds / 1_000.0)
+        .map_err(|_| error(format!("{} {} is too long to wait", number, unit)))
+}
+
+/// The tags `values` name, each with where it was written; a tag is text.
+pub fn tags(values: Vec<(Value, Span)>) -> Result<Vec<(String, Span)>, Diagnostic> {
+    values
+        .into_iter()
+        .map(|(value, span)| match value {
+            Value::Text(tag) => Ok((tag, span)),
+            other => Err(Diagnostic::error(
+                TAG,
+                format!("A task tag is text, not {}", other.described()),
+            )
+            .with_span(span)
+            .with_label(span, "not a tag")),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Statement;
+    use crate::parse;
+
+    fn command(source: &str) -> Command {
+        match parse(source).expect("Parse failed").statements.remove(0) {
+            Statement::Command(command) => command,
+            other => panic!("Expected a command, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_wait_forms() {
+        let kind = |source: &str| match wait(&command(source)) {
+            Ok(Wait::Time { .. }) => "time",
+            Ok(Wait::All(tags)) if tags.is_empty() => "everything",
+            Ok(Wait::All(_)) => "all",
+            Ok(Wait::Any(_)) => "any",
+            Err(_) => "error",
+        };
+        for (source, expected) in [
+            ("wait.", "everything"),
+            ("wait all.", "everything"),
+            ("wait 'upload'.", "all"),
+            ("wait all 'a' 'b'.", "all"),
+            ("wait any 'mirror'.", "any"),
+            ("wait 5 seconds.", "time"),
+            ("wait @time @unit.", "time"),
+            ("wait any.", "error"),
+            ("wait 'a' 'b' 'c'.", "error"),
+            ("wait forever.", "error"),
+        ] {
+            assert_eq!(kind(source), expected, "{source}");
+        }
+    }
+
+    #[test]
+    fn test_durations() {
+        let span = Span::default();
+        let of = |amount: Value, unit: &str| duration(&amount, &Value::from(unit), span);
+        assert_eq!(of(Value::Integer(5), "seconds"), Ok(Duration::from_secs(5)));
+        assert_eq!(of(Value::from("250"), "ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(
+            of(Value::Decimal("1.5".to_string()), "minutes"),
+            Ok(Duration::from_secs(90))
+        );
+        assert_eq!(
+            of(Value::Integer(-1), "seconds").unwrap_err().code,
+            DURATION
+        );
+        assert_eq!(of(Value::Integer(1), "weeks").unwrap_err().code, DURATION);
+    }
+
+    #[test]
+    fn test_launchable() {
+        for (source, code) in [
+            ("async 'x' fail.", COMMAND),
+            ("async wait 'x'.", COMMAND),
+            ("async 'x' fetch into @data.", SINK),
+            ("async 'x' calculate 1 + 2 into @sum.", COMMAND),
+            ("async 'x' 'a' into @a.", COMMAND),
+        ] {
+            assert_eq!(parse(source).unwrap_err().code, code, "{source}");
+        }
+        assert!(parse("safe async 'x' fetch 'a'.").is_ok());
+    }
+}
@@ -1,30 +1,43 @@
+use crate::span::Span;
+
 pub struct Token {
     pub kind: TokenType,
     pub value: String,
+    pub span: Span,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TokenType {
-    Modifier, // as, is, with, etc.
-    Variable, // @variable
-    Interpolation, // ${variable}
-    Literal, // 'content' 'content with spaces'
-    Logical, // and, or, not, is, <, >, <=, >=
-    Instruction, // module specific instructions
-    Function, // #function
+    Modifier,      // as, is, with, etc.
+    Variable,      // @variable, @!sealed, @?nullable
+    Container,     // ::container, ::!sealed, ::?nullable
+    Projection,    // :field, #0, :?field, #?0 after a container
+    Interpolation, // @{name} inside literals
+    Literal,       // 'content' 'content with spaces'
+    Number,        // 42, -7, 0.1
+    Boolean,       // true, false
+    Logical,       // and, or, xor, not, <, >, <=, >=, ==, !=
+    Instruction,   // module specific instructions
+    Function,      // user function names
+    Comment,       // ~ line or ~~~ block ~~~
 }
 
-
 // Reserved Keywords
-pub const LOGICAL_TOKEN_MODIFIER: [&str; 4] = [
-    "as", "is", "with", "without",
+pub const LOGICAL_TOKEN_MODIFIER: [&str; 4] = ["as", "is", "with", "without"];
+
+pub const LOGICAL_TOKEN_KEYWORDS: [&str; 10] =
+    ["and", "or", "xor", "not", "<", ">", "<=", ">=", "==", "!="];
+
+pub const INTRINSIC_KEYWORDS: [&str; 11] = [
+    "ask",
+    "calculate",
+    "container",
+    "decide",
+    "fail",
+    "invoke",
+    "parameter",
+    "say",
+    "stop",
+    "success",
+    "wait",
 ];
-
-pub const LOGICAL_TOKEN_KEYWORDS: [&str; 7] = [
-    "and", "or", "not", "<", ">", "<=", ">="
-];
-
-
-
-
-
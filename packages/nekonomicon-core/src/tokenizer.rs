@@ -1,4 +1,9 @@
-use crate::token::{Token, TokenType, LOGICAL_TOKEN_KEYWORDS, LOGICAL_TOKEN_MODIFIER };
+use crate::ast::{Comment, CommentKind};
+use crate::literal::{self, Literal, LiteralOptions, Part};
+use crate::span::LineIndex;
+use crate::token::{LOGICAL_TOKEN_KEYWORDS, LOGICAL_TOKEN_MODIFIER, Token, TokenType};
+
+type Chars<'a> = std::iter::Peekable<std::str::CharIndices<'a>>;
 
 // Tokenizes the input string into a vector of tokens.
 // This function handles keywords, variables, string literals,
@@ -6,61 +11,116 @@ use crate::token::{Token, TokenType, LOGICAL_TOKEN_KEYWORDS, LOGICAL_TOKEN_MODIF
 pub fn tokenize(input: &str) -> Vec<Token> {
     use TokenType::*;
 
+    let lines = LineIndex::new(input);
     let mut tokens = Vec::new();
-    let mut chars = input.chars().peekable();
-    while let Some(c) = chars.peek().copied() {
+    let mut chars = input.char_indices().peekable();
+    while let Some((start, c)) = chars.peek().copied() {
         match c {
             ' ' | '\t' | '\n' => {
                 skip_whitespace(&mut chars);
             }
             '~' => {
-                skip_comment_until_end(&mut chars);
+                let comment = read_comment(&mut chars, input, &lines);
+                tokens.push(Token {
+                    kind: TokenType::Comment,
+                    value: comment.text,
+                    span: comment.span,
+                });
             }
             '\'' => {
-                // Parse string literal
-                chars.next(); // Skip starting quote
-                let mut literal = String::new();
-                while let Some(next) = chars.next() {
-                    if next == '\'' {
-                        break;
+                // Parse string literal and the interpolations inside it
+                let literal = read_literal(&mut chars, input);
+                let span = lines.span(input, start, offset(&mut chars, input));
+                tokens.push(Token {
+                    kind: Literal,
+                    value: literal.value,
+                    span,
+                });
+                for part in literal.parts {
+                    if let Part::Reference {
+                        reference,
+                        start: from,
+                        end,
+                    } = part
+                    {
+                        tokens.push(Token {
+                            kind: Interpolation,
+                            value: reference.to_string(),
+                            span: lines.span(input, start + from, start + end),
+                        });
                     }
-                    literal.push(next);
                 }
-                tokens.push(Token { kind: Literal, value: literal });
             }
-            '@' => {
-                // Parse variable
-                chars.next(); // skip '@'
-                let mut name = String::new();
-                
-                while let Some(&ch) = chars.peek() {
-                    if ch.is_alphanumeric() || ch == '_' || ch == '-' {
-                        name.push(ch);
+            '@' | ':' if c == '@' || input[start..].starts_with("::") => {
+                // Parse record or container with its sigils, kept as written
+                let reference = read_reference(&mut chars, input);
+                let kind = if c == '@' { Variable } else { Container };
+                let span = lines.span(input, start, start + reference.len());
+                tokens.push(Token {
+                    kind,
+                    value: reference,
+                    span,
+                });
+                if kind == Container {
+                    while let Some(projection) = read_projection(&mut chars, input) {
+                        let from = offset(&mut chars, input) - projection.len();
+                        tokens.push(Token {
+                            kind: Projection,
+                            span: lines.span(input, from, from + projection.len()),
+                            value: projection,
+                        });
+                    }
+                }
+            }
+            ':' => {
+                // A field key such as `:name` or `:!host` inside a container block
+                match read_projection(&mut chars, input) {
+                    Some(field) => tokens.push(Token {
+                        kind: Projection,
+                        span: lines.span(input, start, start + field.len()),
+                        value: field,
+                    }),
+                    None => {
                         chars.next();
-                    } else if ch == '{' {
-                        // Handle variable with hint
-                        chars.next(); // Skip '{'
-                        let mut hint = String::new();
-                        while let Some(next) = chars.next() {
-                            if next == '}' {
-                                break; // End of hint
-                            }
-                            hint.push(next);
-                        }
-                        tokens.push(Token { kind: Interpolation, value: hint });
-                        break; // Exit variable parsing
-                    } else {
-                        break; // End of variable name
                     }
                 }
-
-                tokens.push(Token { kind: Variable, value: name});
+            }
+            '<' | '>' | '=' | '!' => {
+                // Comparison symbols used by decide; anything else is skipped.
+                let symbol: String = input[start..]
+                    .chars()
+                    .take_while(|c| matches!(c, '<' | '>' | '=' | '!'))
+                    .collect();
+                if !LOGICAL_TOKEN_KEYWORDS.contains(&symbol.as_str()) {
+                    chars.next();
+                    continue;
+                }
+                for _ in 0..symbol.len() {
+                    chars.next();
+                }
+                tokens.push(Token {
+                    kind: Logical,
+                    span: lines.span(input, start, start + symbol.len()),
+                    value: symbol,
+                });
+            }
+            c if c.is_ascii_digit()
+                || (c == '-' && input[start + 1..].starts_with(|d: char| d.is_ascii_digit())) =>
+            {
+                // Parse number, keeping its text as written
+                let number = read_number(&mut chars, input);
+                let span = lines.span(input, start, start + number.len());
+                tokens.push(Token {
+                    kind: Number,
+                    value: number,
+                    span,
+                });
             }
             _ => {
                 // Parse keyword/command/identifier
                 let mut word = String::new();
 
-                while let Some(&ch) = chars.peek() {
+                while let Some(&(_, ch)) = chars.peek() {
                     if ch.is_alphanumeric() || ch == '_' || ch == '-' {
                         word.push(ch);
                         chars.next();
@@ -69,15 +129,28 @@ pub fn tokenize(input: &str) -> Vec<Token> {
                     }
                 }
 
+                // Symbols such as '.' are not part of any word; keep moving.
+                if word.is_empty() {
+                    chars.next();
+                    continue;
+                }
+
                 let kind = if LOGICAL_TOKEN_MODIFIER.contains(&word.as_str()) {
                     TokenType::Modifier
                 } else if LOGICAL_TOKEN_KEYWORDS.contains(&word.as_str()) {
                     TokenType::Logical
+                } else if word == "true" || word == "false" {
+                    TokenType::Boolean
                 } else {
                     TokenType::Instruction // Treat as a command or instruction
                 };
 
-                tokens.push(Token { kind, value: word });
+                let span = lines.span(input, start, start + word.len());
+                tokens.push(Token {
+                    kind,
+                    value: word,
+                    span,
+                });
             }
         }
     }
@@ -85,21 +158,301 @@ pub fn tokenize(input: &str) -> Vec<Token> {
     tokens
 }
 
+// Byte offset of the next unread character.
+fn offset(chars: &mut Chars<'_>, input: &str) -> usize {
+    chars.peek().map(|&(index, _)| index).unwrap_or(input.len())
+}
+
+// Collects every comment of the input, skipping `~` inside string literals.
+pub fn comments(input: &str) -> Vec<Comment> {
+    let lines = LineIndex::new(input);
+    let mut comments = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some(&(_, c)) = chars.peek() {
+        match c {
+            '~' => comments.push(read_comment(&mut chars, input, &lines)),
+            '\'' => {
+                read_literal(&mut chars, input);
+            }
+            _ => {
+                chars.next();
+            }
+        }
+    }
+    comments
+}
+
 // Helper functions to skip whitespace and empty lines.
-fn skip_whitespace(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) {
+fn skip_whitespace(chars: &mut Chars<'_>) {
     chars.next();
 }
 
+// Reads `@` or `::`, any `!`/`?` sigils and the name; the parser validates sigils.
+fn read_reference(chars: &mut Chars<'_>, input: &str) -> String {
+    let start = offset(chars, input);
+    let rest = &input[start..];
+    let marker = if rest.starts_with("::") { 2 } else { 1 };
+    let sigils = rest[marker..]
+        .find(|c: char| c != '!' && c != '?')
+        .map(|length| marker + length)
+        .unwrap_or(rest.len());
+    let end = rest[sigils..]
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .map(|length| sigils + length)
+        .unwrap_or(rest.len());
+    while chars.next_if(|&(index, _)| index < start + end).is_some() {}
+    rest[..end].to_string()
+}
 
-fn skip_comment_until_end(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) {
-    chars.next();
-    // Skip '~'
+// Reads one `:field`, `:?field`, `#0` or `#?0` step right after a container, or a
+// `:field` key, possibly sealed with `:!field`, inside a container block.
+fn read_projection(chars: &mut Chars<'_>, input: &str) -> Option<String> {
+    let start = offset(chars, input);
+    let rest = &input[start..];
+    let marker = rest.chars().next().filter(|&c| c == ':' || c == '#')?;
+    let target = 1 + usize::from(rest[1..].starts_with(['?', '!']));
+    let end = rest[target..]
+        .find(|c: char| {
+            if marker == '#' {
+                !c.is_ascii_digit()
+            } else {
+                !(c.is_alphanumeric() || c == '_')
+            }
+        })
+        .map(|length| target + length)
+        .unwrap_or(rest.len());
+    if end == target {
+        return None;
+    }
+    while chars.next_if(|&(index, _)| index < start + end).is_some() {}
+    Some(rest[..end].to_string())
+}
 
-    // This is a comment, skip until newline
-    while let Some(&ch) = chars.peek() {
-      chars.next();
-        if ch == '\n' {
-            break; // End of comment
-        }
+// Reads `-`, digits and an optional fraction; a trailing `.` is the terminator.
+fn read_number(chars: &mut Chars<'_>, input: &str) -> String {
+    let start = offset(chars, input);
+    let rest = &input[start..];
+    let sign = usize::from(rest.starts_with('-'));
+    let digits = |from: usize| {
+        rest[from..]
+            .find(|c: char| !c.is_ascii_digit())
+            .map(|length| from + length)
+            .unwrap_or(rest.len())
+    };
+    let mut end = digits(sign);
+    if rest[end..].starts_with('.') && rest[end + 1..].starts_with(|c: char| c.is_ascii_digit()) {
+        end = digits(end + 1);
+    }
+    while chars.next_if(|&(index, _)| index < start + end).is_some() {}
+    rest[..end].to_string()
+}
+
+// Reads a quoted literal with the shared literal lexer, resolving its escapes.
+fn read_literal(chars: &mut Chars<'_>, input: &str) -> Literal {
+    let start = offset(chars, input);
+    let literal = literal::lex(&input[start..], LiteralOptions::default());
+    let end = start + literal.length;
+    while chars.next_if(|&(index, _)| index < end).is_some() {}
+    literal
+}
+
+// Reads a `~` line comment or a `~~~` block comment, which may span several lines.
+// A block without its closing `~~~` runs to the end of the input.
+fn read_comment(chars: &mut Chars<'_>, input: &str, lines: &LineIndex) -> Comment {
+    let start = offset(chars, input);
+
+    if input[start..].starts_with("~~~") {
+        let body = start + 3;
+        let (text_end, end, terminated) = match input[body..].find("~~~") {
+            Some(close) => (body + close, body + close + 3, true),
+            None => (input.len(), input.len(), false),
+        };
+        while chars.next_if(|&(index, _)| index < end).is_some() {}
+        return Comment {
+            kind: CommentKind::Block { terminated },
+            text: input[body..text_end].to_string(),
+            span: lines.span(input, start, end),
+        };
+    }
+
+    // This is a line comment, skip until newline
+    let end = input[start..]
+        .find('\n')
+        .map(|newline| start + newline)
+        .unwrap_or(input.len());
+    while chars.next_if(|&(index, _)| index < end).is_some() {}
+    Comment {
+        kind: CommentKind::Line,
+        text: input[start + 1..end].to_string(),
+        span: lines.span(input, start, end),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_spans() {
+        let input = "say 'hi'.\n  ask @name.";
+        let tokens = tokenize(input);
+        let summary: Vec<_> = tokens
+            .iter()
+            .map(|token| (token.value.as_str(), token.span.line, token.span.column))
+            .collect();
+        assert_eq!(
+            summary,
+            [("say", 1, 1), ("hi", 1, 5), ("ask", 2, 3), ("@name", 2, 7)]
+        );
+        assert_eq!(&input[tokens[1].span.start..tokens[1].span.end], "'hi'");
+        assert_eq!(&input[tokens[3].span.start..tokens[3].span.end], "@name");
+    }
+
+    #[test]
+    fn test_block_comments() {
+        let input = "~~~\n  header. 'quoted'\n~~~\nsay 'a ~ b'. ~ note\n~~~ open";
+        let found = comments(input);
+        let summary: Vec<_> = found
+            .iter()
+            .map(|comment| (comment.kind, comment.text.as_str(), comment.span.line))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (
+                    CommentKind::Block { terminated: true },
+                    "\n  header. 'quoted'\n",
+                    1
+                ),
+                (CommentKind::Line, " note", 4),
+                (CommentKind::Block { terminated: false }, " open", 5),
+            ]
+        );
+        let values: Vec<_> = tokenize(input)
+            .into_iter()
+            .filter(|token| token.kind != TokenType::Comment)
+            .map(|token| token.value)
+            .collect();
+        assert_eq!(values, ["say", "a ~ b"]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_literal_escapes() {
+        let values: Vec<_> = tokenize("say 'I\\'m\\tin'. say 'next'.")
+            .into_iter()
+            .map(|token| token.value)
+            .collect();
+        assert_eq!(values, ["say", "I'm\tin", "say", "next"]);
+    }
+
+    #[test]
+    fn test_number_and_boolean_tokens() {
+        let summary: Vec<_> = tokenize("say 0.1 -7 42. say true.")
+            .into_iter()
+            .map(|token| (token.kind, token.value))
+            .collect();
+        let expected = [
+            (TokenType::Instruction, "say"),
+            (TokenType::Number, "0.1"),
+            (TokenType::Number, "-7"),
+            (TokenType::Number, "42"),
+            (TokenType::Instruction, "say"),
+            (TokenType::Boolean, "true"),
+        ];
+        assert_eq!(
+            summary,
+            expected.map(|(kind, value)| (kind, value.to_string()))
+        );
+    }
+
+    #[test]
+    fn test_logical_tokens() {
+        let summary: Vec<_> = tokenize("decide @a >= 3 xor not @b != 'x' into @c.")
+            .into_iter()
+            .filter(|token| token.kind == TokenType::Logical)
+            .map(|token| token.value)
+            .collect();
+        assert_eq!(summary, [">=", "xor", "not", "!="]);
+    }
+
+    #[test]
+    fn test_interpolation_tokens() {
+        let input = "say 'Hi @{name} and @{::team:lead}'.";
+        let tokens = tokenize(input);
+        let interpolations: Vec<_> = tokens
+            .iter()
+            .filter(|token| token.kind == TokenType::Interpolation)
+            .map(|token| {
+                (
+                    token.value.as_str(),
+                    &input[token.span.start..token.span.end],
+                )
+            })
+            .collect();
+        assert_eq!(
+            interpolations,
+            [("@name", "@{name}"), ("::team:lead", "@{::team:lead}")]
+        );
+    }
+
+    #[test]
+    fn test_projection_tokens() {
+        let summary: Vec<_> = tokenize("say ::data:?tags#?1#0 @a.")
+            .into_iter()
+            .skip(1)
+            .map(|token| (token.kind, token.value, token.span.column))
+            .collect();
+        let expected = [
+            (TokenType::Container, "::data", 5),
+            (TokenType::Projection, ":?tags", 11),
+            (TokenType::Projection, "#?1", 17),
+            (TokenType::Projection, "#0", 20),
+            (TokenType::Variable, "@a", 23),
+        ];
+        assert_eq!(
+            summary,
+            expected.map(|(kind, value, column)| (kind, value.to_string(), column))
+        );
+    }
+
+    #[test]
+    fn test_field_key_tokens() {
+        let summary: Vec<_> = tokenize("container\n  :!host 'x'\ninto ::c.")
+            .into_iter()
+            .map(|token| (token.kind, token.value))
+            .collect();
+        let expected = [
+            (TokenType::Instruction, "container"),
+            (TokenType::Projection, ":!host"),
+            (TokenType::Literal, "x"),
+            (TokenType::Instruction, "into"),
+            (TokenType::Container, "::c"),
+        ];
+        assert_eq!(
+            summary,
+            expected.map(|(kind, value)| (kind, value.to_string()))
+        );
+    }
+
+    #[test]
+    fn test_sigil_tokens() {
+        let summary: Vec<_> = tokenize("say @a @!b @?c ::d ::!e ::?f @!?g.")
+            .into_iter()
+            .skip(1)
+            .map(|token| (token.kind, token.value))
+            .collect();
+        let expected = [
+            (TokenType::Variable, "@a"),
+            (TokenType::Variable, "@!b"),
+            (TokenType::Variable, "@?c"),
+            (TokenType::Container, "::d"),
+            (TokenType::Container, "::!e"),
+            (TokenType::Container, "::?f"),
+            (TokenType::Variable, "@!?g"),
+        ];
+        assert_eq!(
+            summary,
+            expected.map(|(kind, value)| (kind, value.to_string()))
+        );
+    }
+}
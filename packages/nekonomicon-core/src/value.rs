@@ -0,0 +1,410 @@
+//! Runtime values held by records and containers.
+//!
+//! Records are always text. Containers hold trees whose leaves may be typed:
+//! `:age 30` stores an integer node and `:active true` a boolean one, while
+//! `:name 'Alex'` stays text. Lenses (`as integer`, `as decimal`, `as bool`)
+//! read text as one of those types.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::diagnostic::Diagnostic;
+use crate::json;
+use crate::span::Span;
+
+pub const LENS_FAILED: &str = "E-LENS-INVALID";
+
+/// A record holds text; containers nest lists and maps of values.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Value {
+    #[default]
+    Null,
+    Text(String),
+    Integer(i64),
+    /// Kept as written, e.g. `0.10`, so no precision is ever lost.
+    Decimal(String),
+    Boolean(bool),
+    List(Vec<Value>),
+    /// Named fields in the order they were written.
+    Map(Vec<(String, Value)>),
+}
+
+/// How the `as` keyword reads a value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lens {
+    Integer,
+    Decimal,
+    Bool,
+}
+
+impl Lens {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Lens::Integer => "integer",
+            Lens::Decimal => "decimal",
+            Lens::Bool => "bool",
+        }
+    }
+}
+
+impl FromStr for Lens {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "integer" => Ok(Lens::Integer),
+            "decimal" => Ok(Lens::Decimal),
+            "bool" => Ok(Lens::Bool),
+            other => Err(format!(
+                "Unknown lens '{}'; use 'integer', 'decimal' or 'bool'",
+                other
+            )),
+        }
+    }
+}
+
+impl Value {
+    /// A typed number from literal text: an integer when it fits, a decimal otherwise.
+    pub fn number(text: &str) -> Option<Value> {
+        if !is_decimal(text) {
+            return None;
+        }
+        Some(match text.parse() {
+            Ok(integer) => Value::Integer(integer),
+            Err(_) => Value::Decimal(text.to_string()),
+        })
+    }
+
+    /// The value of a named field, when this is a map that has it.
+    pub fn field(&self, name: &str) -> Option<&Value> {
+        match self {
+            Value::Map(fields) => fields
+                .iter()
+                .find(|(field, _)| field == name)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// What kind of node this is, in the words used by error messages.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Value::Null => "null",
+            Value::Text(_) => "value",
+            Value::Integer(_) => "integer",
+            Value::Decimal(_) => "decimal",
+            Value::Boolean(_) => "boolean",
+            Value::List(_) => "array",
+            Value::Map(_) => "container",
+        }
+    }
+
+    /// The kind with its article, e.g. "an array".
+    pub fn described(&self) -> String {
+        match self.kind().chars().next() {
+            Some('a' | 'e' | 'i' | 'o' | 'u') => format!("an {}", self.kind()),
+            _ => format!("a {}", self.kind()),
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    /// Leaves of the tree: everything but arrays and containers.
+    pub fn is_scalar(&self) -> bool {
+        !matches!(self, Value::List(_) | Value::Map(_))
+    }
+
+    /// The text a record would hold for this value; arrays and containers have none.
+    pub fn text(&self) -> Option<String> {
+        self.is_scalar().then(|| self.to_string())
+    }
+
+    /// Reads the value through a lens, failing at `span` when it does not fit.
+    ///
+    /// `as bool` accepts `true`, `false`, `1` and `0` and yields the bool container
+    /// documented by the `bool` module, with `:verbose`, `:binary` and `:value`.
+    pub fn lens(&self, lens: Lens, span: Span) -> Result<Value, Diagnostic> {
+        let converted = match (lens, self) {
+            (Lens::Integer, Value::Integer(_)) => Some(self.clone()),
+            (Lens::Integer, Value::Text(text)) if is_decimal(text) && !text.contains('.') => {
+                text.parse().ok().map(Value::Integer)
+            }
+            (Lens::Decimal, Value::Decimal(_)) => Some(self.clone()),
+            (Lens::Decimal, Value::Integer(integer)) => Some(Value::Decimal(integer.to_string())),
+            (Lens::Decimal, Value::Text(text)) => {
+                is_decimal(text).then(|| Value::Decimal(text.clone()))
+            }
+            (Lens::Bool, Value::Boolean(flag)) => Some(bool_container(*flag, &flag.to_string())),
+            (Lens::Bool, Value::Integer(integer @ (0 | 1))) => {
+                Some(bool_container(*integer == 1, &integer.to_string()))
+            }
+            (Lens::Bool, Value::Text(text)) => match text.as_str() {
+                "true" | "1" => Some(bool_container(true, text)),
+                "false" | "0" => Some(bool_container(false, text)),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        converted.ok_or_else(|| {
+            let shown = match self.text() {
+                Some(text) => format!("'{}'", text),
+                None => self.described(),
+            };
+            let hint = match lens {
+                Lens::Integer => "Integers are whole numbers such as '42' or '-7'.",
+                Lens::Decimal => "Decimals are numbers such as '4.5', '-0.25' or '3'.",
+                Lens::Bool => "Booleans are 'true', 'false', '1' or '0'.",
+            };
+            Diagnostic::error(
+                LENS_FAILED,
+                format!("{} cannot be read as {}", shown, lens.as_str()),
+            )
+            .with_span(span)
+            .with_label(span, format!("as {}", lens.as_str()))
+            .with_help(hint)
+        })
+    }
+
+    /// Equality as the language sees it: numbers compare by value whatever their
+    /// spelling (`'30'`, `30` and `30.0` are equal), other scalars by text, and
+    /// arrays and containers node by node. Null only equals null.
+    pub fn equals(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Null, Value::Null) => true,
+            (Value::Null, _) | (_, Value::Null) => false,
+            (Value::List(left), Value::List(right)) => {
+                left.len() == right.len()
+                    && left
+                        .iter()
+                        .zip(right)
+                        .all(|(left, right)| left.equals(right))
+            }
+            (Value::Map(left), Value::Map(right)) => {
+                left.len() == right.len()
+                    && left.iter().all(|(name, value)| {
+                        other
+                            .field(name)
+                            .is_some_and(|candidate| value.equals(candidate))
+                    })
+                    && right.iter().all(|(name, _)| self.field(name).is_some())
+            }
+            (Value::List(_) | Value::Map(_), _) | (_, Value::List(_) | Value::Map(_)) => false,
+            _ => {
+                let (left, right) = (self.to_string(), other.to_string());
+                match (canonical_number(&left), canonical_number(&right)) {
+                    (Some(left), Some(right)) => left == right,
+                    _ => left == right,
+                }
+            }
+        }
+    }
+
+    /// The value as JSON: typed nodes keep their type, text stays a string.
+    pub fn to_json(&self) -> String {
+        match self {
+            Value::Null => "null".to_string(),
+            Value::Text(text) => json::quote(text),
+            Value::Integer(integer) => integer.to_string(),
+            Value::Decimal(decimal) => {
+                canonical_number(decimal).unwrap_or_else(|| json::quote(decimal))
+            }
+            Value::Boolean(flag) => flag.to_string(),
+            Value::List(items) => json::array(items.iter().map(Value::to_json)),
+            Value::Map(fields) => json::object(
+                fields
+                    .iter()
+                    .map(|(name, value)| (name.as_str(), value.to_json())),
+            ),
+        }
+    }
+
+    /// How a value reads inside an array or container: text is quoted.
+    fn nested(&self) -> String {
+        match self {
+            Value::Text(text) => format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'")),
+            other => other.to_string(),
+        }
+    }
+}
+
+/// How `say` shows a value: scalars as they are, arrays as `['a', 'b']` and
+/// containers as `{name: 'Alice', age: 30}`.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "null"),
+            Value::Text(text) => write!(f, "{}", text),
+            Value::Integer(integer) => write!(f, "{}", integer),
+            Value::Decimal(decimal) => write!(f, "{}", decimal),
+            Value::Boolean(flag) => write!(f, "{}", flag),
+            Value::List(items) => {
+                let items: Vec<String> = items.iter().map(Value::nested).collect();
+                write!(f, "[{}]", items.join(", "))
+            }
+            Value::Map(fields) => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|(name, value)| format!("{}: {}", name, value.nested()))
+                    .collect();
+                write!(f, "{{{}}}", fields.join(", "))
+            }
+        }
+    }
+}
+
+impl From<&str> for Value {
+    fn from(text: &str) -> Self {
+        Value::Text(text.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(text: String) -> Self {
+        Value::Text(text)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(flag: bool) -> Self {
+        Value::Boolean(flag)
+    }
+}
+
+/// `-?digits(.digits)?`, the shape of number literals.
+fn is_decimal(text: &str) -> bool {
+    let digits = text.strip_prefix('-').unwrap_or(text);
+    let (whole, fraction) = match digits.split_once('.') {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (digits, None),
+    };
+    let all_digits = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+    all_digits(whole) && fraction.is_none_or(all_digits)
+}
+
+/// The shortest spelling of a number, so that `007`, `7.0` and `7` compare equal.
+fn canonical_number(text: &str) -> Option<String> {
+    if !is_decimal(text) {
+        return None;
+    }
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    let whole = whole.trim_start_matches('0');
+    let fraction = fraction.trim_end_matches('0');
+    let mut canonical = if whole.is_empty() { "0" } else { whole }.to_string();
+    if !fraction.is_empty() {
+        canonical.push('.');
+        canonical.push_str(fraction);
+    }
+    if negative && canonical != "0" {
+        canonical.insert(0, '-');
+    }
+    Some(canonical)
+}
+
+fn bool_container(flag: bool, written: &str) -> Value {
+    Value::Map(vec![
+        ("verbose".to_string(), Value::from(flag.to_string())),
+        (
+            "binary".to_string(),
+            Value::from(if flag { "1" } else { "0" }),
+        ),
+        ("value".to_string(), Value::from(written)),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let fruits = Value::List(vec!["apple".into(), "it's".into(), Value::Integer(3)]);
+        assert_eq!(fruits.to_string(), "['apple', 'it\\'s', 3]");
+        let person = Value::Map(vec![
+            ("name".to_string(), "Alice".into()),
+            ("age".to_string(), Value::Integer(30)),
+            ("phone".to_string(), Value::Null),
+            ("tags".to_string(), Value::List(vec!["a".into()])),
+        ]);
+        assert_eq!(
+            person.to_string(),
+            "{name: 'Alice', age: 30, phone: null, tags: ['a']}"
+        );
+        assert_eq!(Value::Decimal("0.10".to_string()).to_string(), "0.10");
+    }
+
+    #[test]
+    fn test_lenses() {
+        let lens = |value: &str, lens: Lens| {
+            Value::from(value)
+                .lens(lens, Span::default())
+                .map_err(|error| error.code)
+        };
+        assert_eq!(lens("-42", Lens::Integer), Ok(Value::Integer(-42)));
+        assert_eq!(
+            lens("5.5", Lens::Decimal),
+            Ok(Value::Decimal("5.5".to_string()))
+        );
+        assert_eq!(
+            Value::Integer(3).lens(Lens::Decimal, Span::default()),
+            Ok(Value::Decimal("3".to_string()))
+        );
+        for (input, lens_to) in [
+            ("4.5", Lens::Integer),
+            ("abc", Lens::Decimal),
+            ("1e3", Lens::Decimal),
+            ("yes", Lens::Bool),
+        ] {
+            assert_eq!(lens(input, lens_to), Err(LENS_FAILED.to_string()));
+        }
+
+        let flag = lens("0", Lens::Bool).unwrap();
+        assert_eq!(flag.field("verbose"), Some(&Value::from("false")));
+        assert_eq!(flag.field("binary"), Some(&Value::from("0")));
+        assert_eq!(flag.field("value"), Some(&Value::from("0")));
+    }
+
+    #[test]
+    fn test_equality() {
+        assert!(Value::from("30").equals(&Value::Integer(30)));
+        assert!(Value::Decimal("30.0".to_string()).equals(&Value::Integer(30)));
+        assert!(Value::from("-0").equals(&Value::from("0.00")));
+        assert!(Value::Boolean(true).equals(&Value::from("true")));
+        assert!(!Value::from("abc").equals(&Value::from("ABC")));
+        assert!(!Value::Null.equals(&Value::from("null")));
+
+        let left = Value::Map(vec![
+            ("a".to_string(), Value::Integer(1)),
+            ("b".to_string(), "x".into()),
+        ]);
+        let right = Value::Map(vec![
+            ("b".to_string(), "x".into()),
+            ("a".to_string(), "1".into()),
+        ]);
+        assert!(left.equals(&right));
+        assert!(!left.equals(&Value::List(vec![])));
+    }
+
+    #[test]
+    fn test_json() {
+        let value = Value::Map(vec![
+            ("name".to_string(), "Al \"x\"".into()),
+            ("age".to_string(), Value::Integer(30)),
+            ("ratio".to_string(), Value::Decimal("0.50".to_string())),
+            ("ok".to_string(), Value::Boolean(true)),
+            ("none".to_string(), Value::Null),
+            ("tags".to_string(), Value::List(vec!["a".into()])),
+        ]);
+        assert_eq!(
+            value.to_json(),
+            "{\"name\":\"Al \\\"x\\\"\",\"age\":30,\"ratio\":0.5,\"ok\":true,\"none\":null,\
+             \"tags\":[\"a\"]}"
+        );
+    }
+}